@@ -0,0 +1,22 @@
+//! Selects the signal engines compiled in for the target platform.
+
+use std::env;
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(signalfd)");
+    println!("cargo:rustc-check-cfg=cfg(kqueue)");
+    println!("cargo:rustc-check-cfg=cfg(sigwait)");
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let native = match os.as_str() {
+        "linux" | "android" => Some("signalfd"),
+        "macos" | "ios" | "freebsd" | "dragonfly" | "netbsd" | "openbsd" => Some("kqueue"),
+        _ => None,
+    };
+    if let Some(native) = native {
+        println!("cargo:rustc-cfg={}", native);
+    }
+    // The sigwait thread is the only engine where there's no native one.
+    if native.is_none() || env::var_os("CARGO_FEATURE_SIGWAIT_THREAD").is_some() {
+        println!("cargo:rustc-cfg=sigwait");
+    }
+}
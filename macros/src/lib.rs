@@ -0,0 +1,135 @@
+//! Attribute macros for `tokio-signalfd`, re-exported from it with the
+//! `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::Parser;
+use syn::{Error, Expr, ExprArray, FnArg, ItemFn, LitStr, Result};
+
+/// Runs an async `main` on a tokio runtime, handing it a signalfd for
+/// `signals`.
+///
+/// The signals are blocked before the runtime is built, so that every one of
+/// its threads inherits the mask and none of them can have the signals
+/// delivered to their handlers instead. Creating the runtime first and the
+/// signalfd inside it leaves the threads started in between unmasked.
+///
+/// ```
+/// use tokio_signalfd::tokio::SignalFd;
+/// use tokio_signalfd::{signal, Signal};
+///
+/// #[tokio_signalfd::main(signals = [Signal::Usr1, Signal::Term])]
+/// async fn main(mut signals: SignalFd) -> std::io::Result<()> {
+///     signal::send(std::process::id() as _, Signal::Usr1)?;
+///     assert_eq!(signals.recv().await?.signo(), tokio_signalfd::SIGUSR1);
+///     Ok(())
+/// }
+/// ```
+///
+/// The signals can be anything converting into an `i32`. The runtime is
+/// multi-threaded unless `flavor = "current_thread"` is given, and has IO and
+/// timers enabled. Failing to set things up panics, as `#[tokio::main]`
+/// does.
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    match expand(args.into(), item.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Args {
+    signals: Vec<Expr>,
+    current_thread: bool,
+}
+
+fn parse_args(args: TokenStream2) -> Result<Args> {
+    let mut signals = None;
+    let mut current_thread = false;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("signals") {
+            let array: ExprArray = meta.value()?.parse()?;
+            signals = Some(array.elems.into_iter().collect());
+            Ok(())
+        } else if meta.path.is_ident("flavor") {
+            let flavor: LitStr = meta.value()?.parse()?;
+            current_thread = match flavor.value().as_str() {
+                "multi_thread" => false,
+                "current_thread" => true,
+                _ => {
+                    return Err(Error::new(
+                        flavor.span(),
+                        "expected `multi_thread` or `current_thread`",
+                    ))
+                }
+            };
+            Ok(())
+        } else {
+            Err(meta.error("expected `signals` or `flavor`"))
+        }
+    });
+    parser.parse2(args)?;
+    let signals =
+        signals.ok_or_else(|| Error::new(Span::call_site(), "missing `signals = [...]`"))?;
+    Ok(Args {
+        signals,
+        current_thread,
+    })
+}
+
+fn expand(args: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    let Args {
+        signals,
+        current_thread,
+    } = parse_args(args)?;
+    let mut function: ItemFn = syn::parse2(item)?;
+    if function.sig.asyncness.take().is_none() {
+        return Err(Error::new_spanned(
+            function.sig.fn_token,
+            "the function must be `async`",
+        ));
+    }
+    let inputs = std::mem::take(&mut function.sig.inputs);
+    let param = match inputs.into_iter().collect::<Vec<_>>().as_slice() {
+        [FnArg::Typed(param)] => param.clone(),
+        _ => {
+            return Err(Error::new_spanned(
+                &function.sig,
+                "expected a single parameter receiving the `tokio::SignalFd`",
+            ))
+        }
+    };
+    let (pat, ty) = (&param.pat, &param.ty);
+    let builder = if current_thread {
+        quote!(new_current_thread)
+    } else {
+        quote!(new_multi_thread)
+    };
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = &function;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __set = ::tokio_signalfd::SigSet::from_signals(&[
+                #(::core::convert::Into::<i32>::into(#signals)),*
+            ])
+            .expect("invalid signal");
+            // Before the runtime starts any threads, so that they inherit it.
+            __set.block().expect("failed to block the signals");
+            let __runtime = ::tokio_signalfd::__private::tokio1::runtime::Builder::#builder()
+                .enable_all()
+                .build()
+                .expect("failed to build the tokio runtime");
+            __runtime.block_on(async move {
+                let #pat: #ty = ::tokio_signalfd::tokio::SignalFd::from_set(&__set)
+                    .expect("failed to create the signalfd");
+                #block
+            })
+        }
+    })
+}
@@ -0,0 +1,37 @@
+//! signalfd integration for async-std.
+//!
+//! async-std runs on the async-io reactor, so this is the same type as
+//! [`AsyncSignalFd`](crate::AsyncSignalFd) under the name used by the other
+//! runtime modules.
+
+use std::future::poll_fn;
+use std::io::Result;
+
+use crate::signal::Signal;
+
+/// A stream of signals delivered through a signalfd registered with
+/// async-std's reactor.
+pub type SignalFd<T = i32> = crate::asyncio::AsyncSignalFd<T>;
+
+/// Waits for the first of `signals` to arrive.
+///
+/// The signals are blocked on the thread first polling the returned future, and
+/// unblocked again once it completes (if it completes on that same thread).
+pub async fn any_of<S: Copy + Into<i32>>(signals: &[S]) -> Result<Signal> {
+    let mut signals = SignalFd::new(signals)?;
+    poll_fn(|cx| signals.poll_recv(cx)).await.map(Signal::from)
+}
+
+/// Waits for `SIGINT`, as sent by Ctrl-C in a terminal.
+///
+/// See [`any_of`] for how the signal mask is affected.
+pub async fn ctrl_c() -> Result<()> {
+    any_of(&[Signal::Int]).await.map(|_| ())
+}
+
+/// Waits for `SIGTERM`, the conventional request to shut down.
+///
+/// See [`any_of`] for how the signal mask is affected.
+pub async fn terminate() -> Result<()> {
+    any_of(&[Signal::Term]).await.map(|_| ())
+}
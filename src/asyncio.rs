@@ -0,0 +1,492 @@
+//! signalfd integration for async-io based runtimes, such as smol and
+//! async-std.
+
+use std::convert::TryFrom;
+use std::future::{poll_fn, Future};
+use std::io::{self, Result};
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::pin::{pin, Pin};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_io::Async;
+
+use crate::builder::Builder;
+use crate::error::SignalFdError;
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::source::SignalSource;
+use crate::sys::Backend;
+use crate::terminal::TakeUntilTerminal;
+
+/// A stream of signals delivered through a signalfd registered with the
+/// async-io reactor.
+///
+/// The descriptor is registered with async-io's global reactor through
+/// [`Async`](async_io::Async), which smol and async-std both run on, so no
+/// runtime context is needed to create one.
+///
+/// The stream yields signal numbers by default; use
+/// [`signal_stream`](AsyncSignalFd::signal_stream) or
+/// [`info_stream`](AsyncSignalFd::info_stream) to receive typed [`Signal`]s or full
+/// [`SignalInfo`] records instead.
+///
+/// The stream never ends, and is [`Unpin`] whatever it yields, so it can be
+/// used with `futures::select!` as is.
+pub struct AsyncSignalFd<T = i32> {
+    io: Async<RawSignalFd>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl AsyncSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    ///
+    /// Signals can be given either as raw numbers or as [`Signal`]s.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        let inner = RawSignalFd::new(signals)?;
+        AsyncSignalFd::from_inner(inner)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        let inner = RawSignalFd::from_set(set)?;
+        AsyncSignalFd::from_inner(inner)
+    }
+
+    /// Starts receiving the signals in `list`, separated by commas or
+    /// whitespace, e.g. `"TERM,HUP,USR1"`.
+    ///
+    /// See [`SigSet::from_str_list`].
+    pub fn from_str_list(list: &str) -> Result<Self> {
+        AsyncSignalFd::from_set(&SigSet::from_str_list(list)?)
+    }
+
+    /// Starts receiving the signals listed in the environment variable `var`,
+    /// or `default` if it isn't set, so that deployments can choose them.
+    ///
+    /// See [`SigSet::from_env`].
+    pub fn from_env<S: Copy + Into<i32>>(var: &str, default: &[S]) -> Result<Self> {
+        match SigSet::from_env(var)? {
+            Some(set) => AsyncSignalFd::from_set(&set),
+            None => AsyncSignalFd::new(default),
+        }
+    }
+
+    /// Starts receiving the signals asking the process to shut down, see
+    /// [`SigSet::terminating`].
+    pub fn terminating() -> Result<Self> {
+        AsyncSignalFd::from_set(&SigSet::terminating())
+    }
+
+    /// Starts receiving every signal that can be caught, see
+    /// [`SigSet::catchable`].
+    pub fn catchable() -> Result<Self> {
+        AsyncSignalFd::from_set(&SigSet::catchable())
+    }
+
+    /// Starts receiving the job control signals, see
+    /// [`SigSet::job_control`].
+    pub fn job_control() -> Result<Self> {
+        AsyncSignalFd::from_set(&SigSet::job_control())
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(AsyncSignalFd::from_inner)
+    }
+}
+
+impl<T> AsyncSignalFd<T> {
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        Ok(AsyncSignalFd {
+            io: Async::new(inner).map_err(SignalFdError::Register)?,
+            _item: PhantomData,
+        })
+    }
+
+    fn inner_mut(&mut self) -> &mut RawSignalFd {
+        // The descriptor itself is never replaced or closed through this
+        // reference, which is all `Async` requires.
+        unsafe { self.io.get_mut() }
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.io.get_ref().signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.io.get_ref().backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.io.get_ref().previous_mask()
+    }
+
+    /// Gives up the descriptor without closing it, e.g. to hand it to another
+    /// component.
+    ///
+    /// With `restore_mask` unset the signals stay blocked, so that they keep
+    /// arriving through the descriptor. Otherwise the mask change is
+    /// undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals read
+    /// from the descriptor but not yet returned are lost.
+    ///
+    /// The descriptor is close-on-exec; clear `FD_CLOEXEC` to pass it to a
+    /// program started with `exec`, which inherits the mask as well.
+    ///
+    /// Fails if the descriptor can't be deregistered from the reactor.
+    pub fn into_raw_fd_with(self, restore_mask: bool) -> Result<RawFd> {
+        Ok(self.io.into_inner()?.into_fd(restore_mask).into_raw_fd())
+    }
+
+    /// Returns the underlying descriptor, as registered with async-io's
+    /// reactor.
+    pub fn get_ref(&self) -> &Async<RawSignalFd> {
+        &self.io
+    }
+
+    /// Returns the underlying descriptor mutably, as registered with
+    /// async-io's reactor.
+    pub fn get_mut(&mut self) -> &mut Async<RawSignalFd> {
+        &mut self.io
+    }
+
+    /// Consumes the stream, returning the underlying descriptor still
+    /// registered with async-io's reactor.
+    ///
+    /// Signals the stream had read but not yet returned stay buffered in it.
+    pub fn into_inner(self) -> Async<RawSignalFd> {
+        self.io
+    }
+
+    /// Stops receiving the signals, handing them back to their regular
+    /// disposition.
+    ///
+    /// The descriptor is deregistered from the reactor and closed, and the mask
+    /// change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals that
+    /// were pending on the descriptor are returned rather than delivered to
+    /// their disposition once unblocked.
+    pub fn close(self) -> Result<Vec<SignalInfo>> {
+        self.io.into_inner()?.close()
+    }
+
+    /// Duplicates the descriptor and registers the copy with the reactor on
+    /// its own, so that two tasks can take turns draining the same signals.
+    ///
+    /// Both read from one kernel queue: every signal is received by exactly
+    /// one of them, whichever reads first, and since reads take several
+    /// pending signals at once, one reader can end up with all of them. The
+    /// set of signals is shared too, so [`add`](Self::add) and
+    /// [`remove`](Self::remove) through either affect both, though only the
+    /// caller's [`signals`](Self::signals) reflects the change. Filters are
+    /// copied, and only the original restores the signal mask when dropped.
+    ///
+    /// Fails with `Unsupported` for the sigwait thread and signal-hook
+    /// backends.
+    pub fn try_clone(&self) -> Result<Self> {
+        AsyncSignalFd::from_inner(self.io.get_ref().try_clone()?)
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner_mut().add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner_mut().remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.inner_mut().pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.inner_mut().resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.io.get_ref().is_paused()
+    }
+
+    /// Converts this stream into one yielding typed [`Signal`]s.
+    pub fn signal_stream(self) -> AsyncSignalFd<Signal> {
+        AsyncSignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one yielding full [`SignalInfo`] records.
+    pub fn info_stream(self) -> AsyncSignalFd<SignalInfo> {
+        AsyncSignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one ending after the first signal from
+    /// `terminal`.
+    pub fn take_until_terminal(self, terminal: &SigSet) -> TakeUntilTerminal<Self> {
+        TakeUntilTerminal::new(self, terminal)
+    }
+
+    /// Polls for the next signals, appending up to `limit` of them to `out`.
+    ///
+    /// Once at least one signal is available, every signal that can be
+    /// received without waiting is taken. Returns the number of signals
+    /// appended, which is only zero when `limit` is zero.
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        out: &mut Vec<SignalInfo>,
+        limit: usize,
+    ) -> Poll<Result<usize>> {
+        let mut count = 0;
+        while count < limit {
+            match self.poll_recv(cx) {
+                Poll::Ready(Ok(info)) => {
+                    out.push(info);
+                    count += 1;
+                }
+                Poll::Ready(Err(err)) if count == 0 => return Poll::Ready(Err(err)),
+                Poll::Ready(Err(err)) => {
+                    // Reported by the next call, after these signals.
+                    self.inner_mut().defer_error(err);
+                    break;
+                }
+                Poll::Pending if count == 0 => return Poll::Pending,
+                Poll::Pending => break,
+            }
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    /// Waits for the next signals, appending up to `limit` of them to `out`.
+    ///
+    /// See [`poll_recv_many`](AsyncSignalFd::poll_recv_many).
+    pub async fn recv_many(&mut self, out: &mut Vec<SignalInfo>, limit: usize) -> Result<usize> {
+        poll_fn(|cx| self.poll_recv_many(cx, out, limit)).await
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    ///
+    /// Useful to check for signals once per iteration of a loop driven by
+    /// something other than the reactor.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.inner_mut().read_one()
+    }
+
+    /// Returns every signal that can be received without waiting, e.g. to
+    /// deal with the ones that arrived between creating the signalfd and
+    /// starting to poll it.
+    pub fn drain_pending(&mut self) -> Result<Vec<SignalInfo>> {
+        self.inner_mut().drain()
+    }
+
+    /// Runs `fut` while holding the signals back, to be received once it
+    /// completes.
+    ///
+    /// The stream is borrowed for the duration, so nothing can receive from
+    /// it meanwhile. Signals arriving in the meantime are read into its
+    /// buffer all the same, so that repeated instances of a standard signal
+    /// aren't merged into one by the kernel.
+    pub async fn masked<F: Future>(&mut self, fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        poll_fn(|cx| {
+            if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+            self.poll_fill(cx);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Buffers pending signals until none are left, registering for
+    /// readiness. An error is kept for the next read to report, and doesn't
+    /// stop the signals after it from being buffered.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) {
+        loop {
+            match self.inner_mut().fill() {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                _ => return,
+            }
+            if !matches!(self.io.poll_readable(cx), Poll::Ready(Ok(()))) {
+                return;
+            }
+        }
+    }
+
+    /// Polls for the next signal.
+    ///
+    /// Signals that are already pending are received without waiting for the
+    /// reactor.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        loop {
+            match self.inner_mut().read_info() {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+            ready!(self.io.poll_readable(cx))?;
+        }
+    }
+
+    /// Waits for the next signal, without going through the stream.
+    ///
+    /// Cancelling the returned future is safe: a signal is only taken off the
+    /// descriptor when the future completes with it.
+    pub async fn recv(&mut self) -> Result<SignalInfo> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Waits for the next signal for at most `timeout`, resolving to `None`
+    /// if none arrived in time.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<SignalInfo>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline).await,
+            None => self.recv().await.map(Some),
+        }
+    }
+
+    /// Waits for the next signal until `deadline`, resolving to `None` if none
+    /// arrived by then.
+    pub async fn recv_deadline(&mut self, deadline: Instant) -> Result<Option<SignalInfo>> {
+        let mut timer = async_io::Timer::at(deadline);
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = self.poll_recv(cx) {
+                return Poll::Ready(result.map(Some));
+            }
+            ready!(Pin::new(&mut timer).poll(cx));
+            Poll::Ready(Ok(None))
+        })
+        .await
+    }
+}
+
+impl<T> AsRawFd for AsyncSignalFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().as_raw_fd()
+    }
+}
+
+impl<T> AsFd for AsyncSignalFd<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.io.get_ref().as_fd()
+    }
+}
+
+/// Adopts an existing signalfd whose signal set is unknown.
+///
+/// Fails if the descriptor can't be registered with the reactor.
+impl<T> TryFrom<OwnedFd> for AsyncSignalFd<T> {
+    type Error = io::Error;
+
+    fn try_from(fd: OwnedFd) -> Result<Self> {
+        AsyncSignalFd::from_inner(RawSignalFd::from_fd(fd))
+    }
+}
+
+/// Fails if the descriptor can't be deregistered from the reactor.
+///
+/// The signal mask is left as is, so that the signals keep arriving through
+/// the descriptor.
+impl<T> TryFrom<AsyncSignalFd<T>> for OwnedFd {
+    type Error = io::Error;
+
+    fn try_from(signals: AsyncSignalFd<T>) -> Result<OwnedFd> {
+        Ok(signals.io.into_inner()?.into_fd(false))
+    }
+}
+
+impl<T> SignalSource for AsyncSignalFd<T> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        AsyncSignalFd::poll_recv(self, cx)
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::Stream for AsyncSignalFd<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .poll_recv(cx)
+            .map(|info| Some(info.map(T::from)))
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::FusedStream for AsyncSignalFd<T> {
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::Stream for TakeUntilTerminal<AsyncSignalFd<T>> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let result = ready!(this.stream.poll_recv(cx));
+        if let Ok(info) = &result {
+            this.observe(info.signo());
+        }
+        Poll::Ready(Some(result.map(T::from)))
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::FusedStream for TakeUntilTerminal<AsyncSignalFd<T>> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[test]
+    fn receives_signals() {
+        async_io::block_on(async {
+            let mut signals = AsyncSignalFd::new(&[Signal::Usr1, Signal::Usr2])
+                .unwrap()
+                .signal_stream();
+            unsafe { libc::raise(libc::SIGUSR2) };
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(signals.next().await.unwrap().unwrap(), Signal::Usr1);
+            assert_eq!(signals.next().await.unwrap().unwrap(), Signal::Usr2);
+        });
+    }
+}
@@ -0,0 +1,332 @@
+//! Receiving signals on plain threads, without an async runtime.
+//!
+//! [`SignalFd`] manages the signal mask and parses records like the async
+//! types do, but waits for signals by blocking the calling thread.
+
+use std::io::{self, Result};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::time::{Duration, Instant};
+
+use crate::builder::Builder;
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// A signalfd read by blocking the calling thread.
+///
+/// Waiting is done with `poll(2)` before reading, which lets
+/// [`recv_timeout`](SignalFd::recv_timeout) give up and works the same with
+/// every backend, whether or not the descriptor is made blocking with
+/// [`Builder::nonblocking`]. As with the async types, the signals
+/// have to be blocked in every thread, so create it before spawning any.
+///
+/// It is also an endless iterator, calling [`recv`](SignalFd::recv) for
+/// each item:
+///
+/// ```no_run
+/// use tokio_signalfd::blocking::SignalFd;
+/// use tokio_signalfd::Signal;
+///
+/// let signals = SignalFd::new(&[Signal::Hup, Signal::Term])?;
+/// for info in signals {
+///     match info?.signo() {
+///         libc::SIGHUP => println!("reloading"),
+///         _ => break,
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct SignalFd {
+    inner: RawSignalFd,
+}
+
+impl SignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::new(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::from_set(set)?)
+    }
+
+    /// Starts receiving the signals in `list`, separated by commas or
+    /// whitespace, e.g. `"TERM,HUP,USR1"`.
+    ///
+    /// See [`SigSet::from_str_list`].
+    pub fn from_str_list(list: &str) -> Result<Self> {
+        SignalFd::from_set(&SigSet::from_str_list(list)?)
+    }
+
+    /// Starts receiving the signals listed in the environment variable `var`,
+    /// or `default` if it isn't set, so that deployments can choose them.
+    ///
+    /// See [`SigSet::from_env`].
+    pub fn from_env<S: Copy + Into<i32>>(var: &str, default: &[S]) -> Result<Self> {
+        match SigSet::from_env(var)? {
+            Some(set) => SignalFd::from_set(&set),
+            None => SignalFd::new(default),
+        }
+    }
+
+    /// Starts receiving the signals asking the process to shut down, see
+    /// [`SigSet::terminating`].
+    pub fn terminating() -> Result<Self> {
+        SignalFd::from_set(&SigSet::terminating())
+    }
+
+    /// Starts receiving every signal that can be caught, see
+    /// [`SigSet::catchable`].
+    pub fn catchable() -> Result<Self> {
+        SignalFd::from_set(&SigSet::catchable())
+    }
+
+    /// Starts receiving the job control signals, see
+    /// [`SigSet::job_control`].
+    pub fn job_control() -> Result<Self> {
+        SignalFd::from_set(&SigSet::job_control())
+    }
+
+    /// Takes over a signalfd created by another process, such as a supervisor
+    /// or the process this one replaces, recovering the signals it receives.
+    ///
+    /// See [`RawSignalFd::adopt`].
+    pub fn adopt(fd: OwnedFd) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::adopt(fd)?)
+    }
+
+    /// Adopts the first signalfd passed with systemd's `LISTEN_FDS`
+    /// protocol, among those named `name` if given.
+    ///
+    /// See [`RawSignalFd::from_listen_fds`].
+    pub fn from_listen_fds(name: Option<&str>) -> Result<Option<Self>> {
+        RawSignalFd::from_listen_fds(name)?
+            .map(SignalFd::from_inner)
+            .transpose()
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(SignalFd::from_inner).allow_blocking()
+    }
+
+    pub(crate) fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        Ok(SignalFd { inner })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.remove(signal.into())
+    }
+
+    /// Replaces the descriptor with a new one for the same signals, in a
+    /// child process after `fork(2)`.
+    ///
+    /// The child would otherwise share the descriptor with its parent, and
+    /// with the sigwait thread backend it would have no thread feeding it.
+    /// Signals read before the fork but not yet returned are dropped. Set
+    /// `block` to block the signals on the calling thread again, e.g. when
+    /// forking from a thread other than the one that created the descriptor.
+    pub fn after_fork(&mut self, block: bool) -> Result<()> {
+        self.inner.reopen_with(block)
+    }
+
+    /// Replaces the descriptor with a new one for the same signals, e.g.
+    /// once the process was restored from a checkpoint.
+    ///
+    /// Filters and the mask change are kept. Signals read but not yet
+    /// returned are dropped.
+    pub fn reopen(&mut self) -> Result<()> {
+        self.inner.reopen()
+    }
+
+    /// Returns `true` if a restore from a checkpoint was notified since the
+    /// descriptor was opened, see [`restore`](crate::restore).
+    pub fn needs_reopen(&self) -> bool {
+        self.inner.needs_reopen()
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        if let Some(info) = self.inner.pop() {
+            return Ok(Some(info));
+        }
+        if !wait(self.inner.as_raw_fd(), 0)? {
+            return Ok(None);
+        }
+        self.inner.read_one()
+    }
+
+    /// Waits for the next signal.
+    pub fn recv(&mut self) -> Result<SignalInfo> {
+        loop {
+            if let Some(info) = self.recv_timeout(None)? {
+                return Ok(info);
+            }
+        }
+    }
+
+    /// Waits for the next signal for at most `timeout`, returning `None` if
+    /// none arrived in time.
+    pub fn recv_timeout<T: Into<Option<Duration>>>(
+        &mut self,
+        timeout: T,
+    ) -> Result<Option<SignalInfo>> {
+        let deadline = timeout.into().map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(info) = self.inner.pop() {
+                return Ok(Some(info));
+            }
+            let timeout = match deadline {
+                Some(deadline) => {
+                    // Rounded up, so as not to spin until the deadline.
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    let millis = left.as_nanos().div_ceil(1_000_000);
+                    millis.min(libc::c_int::MAX as u128) as libc::c_int
+                }
+                None => -1,
+            };
+            if wait(self.inner.as_raw_fd(), timeout)? {
+                if let Some(info) = self.inner.read_one()? {
+                    return Ok(Some(info));
+                }
+            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Waits for `fd` to become readable, for at most `timeout` milliseconds,
+/// returning whether it did.
+///
+/// Interruptions return `false` as timeouts do, leaving the caller to check
+/// the time.
+fn wait(fd: RawFd, timeout: libc::c_int) -> Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+        rv if rv < 0 => {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+            Ok(false)
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+impl Iterator for SignalFd {
+    type Item = Result<SignalInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.recv())
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for SignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recv_and_time_out() {
+        let mut signals = SignalFd::new(&[libc::SIGUSR1]).unwrap();
+        let started = Instant::now();
+        let none = signals.recv_timeout(Duration::from_millis(50)).unwrap();
+        assert!(none.is_none());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let thread = unsafe { libc::pthread_self() };
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            unsafe { libc::pthread_kill(thread, libc::SIGUSR1) };
+        });
+        assert_eq!(signals.recv().unwrap().signo(), libc::SIGUSR1);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn blocking_descriptor() {
+        let mut signals = SignalFd::builder()
+            .signal(libc::SIGUSR1)
+            .nonblocking(false)
+            .cloexec(false)
+            .build()
+            .unwrap();
+        let fd = signals.as_raw_fd();
+        assert_eq!(
+            unsafe { libc::fcntl(fd, libc::F_GETFL) } & libc::O_NONBLOCK,
+            0
+        );
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, 0);
+        assert!(signals.try_recv().unwrap().is_none());
+        let timeout = Duration::from_millis(10);
+        assert!(signals.recv_timeout(timeout).unwrap().is_none());
+        unsafe { libc::raise(libc::SIGUSR1) };
+        assert_eq!(signals.try_recv().unwrap().unwrap().signo(), libc::SIGUSR1);
+    }
+
+    #[test]
+    fn iterates() {
+        let mut signals = SignalFd::new(&[libc::SIGUSR2]).unwrap();
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+        }
+        let signos = signals
+            .by_ref()
+            .take(1)
+            .map(|info| info.unwrap().signo())
+            .collect::<Vec<_>>();
+        assert_eq!(signos, [libc::SIGUSR2]);
+    }
+}
@@ -0,0 +1,359 @@
+use std::io::{self, Result};
+use std::sync::Arc;
+
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// How creating a signalfd changes the calling thread's signal mask.
+///
+/// Signals have to be blocked in every thread for them to be reliably
+/// delivered through a signalfd instead of their regular disposition.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskStrategy {
+    /// Add the signals to the current mask (`SIG_BLOCK`). This is the default.
+    #[default]
+    Block,
+    /// Replace the current mask with exactly the signals (`SIG_SETMASK`).
+    SetMask,
+    /// Leave the mask untouched, for callers that block the signals themselves
+    /// (e.g. before spawning any threads).
+    Keep,
+}
+
+/// What to do about signals that already have a handler installed, e.g. by
+/// another library.
+///
+/// A handler is anything other than the default action or `SIG_IGN`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExistingHandlers {
+    /// Take the signals over anyway. Blocking them keeps the handlers from
+    /// running for as long as the signalfd exists. This is the default.
+    #[default]
+    Ignore,
+    /// Fail with `AlreadyExists`, naming the first handled signal.
+    Error,
+    /// If any of the signals is handled, receive all of them through
+    /// [`Backend::SignalHook`] so that the existing handlers keep running.
+    #[cfg(feature = "signal-hook")]
+    Bridge,
+}
+
+/// What happens to a record read while the buffer is at its
+/// [`queue_capacity`](Builder::queue_capacity).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Discard the oldest buffered record to make room. This is the default.
+    #[default]
+    DropOldest,
+    /// Discard the record just read.
+    DropNewest,
+    /// Discard the record just read, and fail the read with
+    /// [`SignalFdError::Overflow`](crate::SignalFdError::Overflow). The
+    /// records already buffered are still returned afterwards.
+    Error,
+}
+
+/// A predicate deciding whether a received record is delivered.
+pub(crate) type Filter = Arc<dyn Fn(&SignalInfo) -> bool + Send + Sync>;
+
+/// Settings applied when creating the descriptor.
+pub(crate) struct Options {
+    pub(crate) mask: MaskStrategy,
+    pub(crate) restore_mask: bool,
+    pub(crate) backend: Option<Backend>,
+    pub(crate) existing_handlers: ExistingHandlers,
+    pub(crate) filters: Vec<Filter>,
+    pub(crate) cloexec: bool,
+    pub(crate) nonblocking: bool,
+    pub(crate) priority: Vec<i32>,
+    pub(crate) capacity: Option<usize>,
+    pub(crate) overflow: Overflow,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mask: MaskStrategy::default(),
+            restore_mask: true,
+            backend: None,
+            existing_handlers: ExistingHandlers::default(),
+            filters: Vec::new(),
+            cloexec: true,
+            nonblocking: true,
+            priority: Vec::new(),
+            capacity: None,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+/// Builder for signalfd streams, created with `SignalFd::builder`.
+pub struct Builder<B> {
+    signals: Vec<i32>,
+    options: Options,
+    make: fn(RawSignalFd) -> Result<B>,
+    allow_blocking: bool,
+}
+
+impl<B> Builder<B> {
+    pub(crate) fn new(make: fn(RawSignalFd) -> Result<B>) -> Self {
+        Builder {
+            signals: Vec::new(),
+            options: Options::default(),
+            make,
+            allow_blocking: false,
+        }
+    }
+
+    /// Lets [`nonblocking`](Builder::nonblocking) be turned off, for types
+    /// that don't wait through a reactor.
+    pub(crate) fn allow_blocking(mut self) -> Self {
+        self.allow_blocking = true;
+        self
+    }
+
+    /// Adds `signal` to the set of received signals.
+    pub fn signal<S: Into<i32>>(mut self, signal: S) -> Self {
+        self.signals.push(signal.into());
+        self
+    }
+
+    /// Adds `signals` to the set of received signals.
+    pub fn signals<S: Copy + Into<i32>>(mut self, signals: &[S]) -> Self {
+        self.signals
+            .extend(signals.iter().map(|&signal| signal.into()));
+        self
+    }
+
+    /// Adds every signal in `set` to the set of received signals.
+    pub fn set(mut self, set: &SigSet) -> Self {
+        self.signals.extend(set.iter().map(i32::from));
+        self
+    }
+
+    /// Chooses how the calling thread's signal mask is updated.
+    ///
+    /// The mask in effect before the update is available afterwards through
+    /// `SignalFd::previous_mask`.
+    pub fn mask(mut self, strategy: MaskStrategy) -> Self {
+        self.options.mask = strategy;
+        self
+    }
+
+    /// Chooses whether the mask change is undone when the signalfd is dropped.
+    ///
+    /// Enabled by default. With [`MaskStrategy::Block`] only the signals that
+    /// weren't blocked already are unblocked again, with
+    /// [`MaskStrategy::SetMask`] the whole previous mask is reinstated. Since
+    /// masks are per thread, nothing is restored when the signalfd is dropped
+    /// on a thread other than the one that created it.
+    ///
+    /// Signals still pending when they are unblocked are delivered to their
+    /// regular disposition.
+    pub fn restore_mask(mut self, restore: bool) -> Self {
+        self.options.restore_mask = restore;
+        self
+    }
+
+    /// Chooses the mechanism signals are received through.
+    ///
+    /// By default the platform's native backend is used, falling back to the
+    /// sigwait thread (with the `sigwait-thread` feature) when the kernel
+    /// refuses it, e.g. because a seccomp filter blocks the syscall. Building
+    /// fails with `Unsupported` if `backend` isn't compiled in.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.options.backend = Some(backend);
+        self
+    }
+
+    /// Chooses what happens when some of the signals already have a handler.
+    ///
+    /// The check is made once, when building; handlers installed afterwards
+    /// go unnoticed.
+    pub fn existing_handlers(mut self, policy: ExistingHandlers) -> Self {
+        self.options.existing_handlers = policy;
+        self
+    }
+
+    /// Chooses whether the descriptor is closed on `exec`.
+    ///
+    /// Enabled by default. Disable it to hand the descriptor down to a
+    /// program started with `exec`, which then also inherits the signal
+    /// mask. Spawned processes inherit the descriptor too, unless their
+    /// spawning code closes it.
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.options.cloexec = cloexec;
+        self
+    }
+
+    /// Chooses whether reads of the descriptor fail with `WouldBlock` rather
+    /// than wait for a signal.
+    ///
+    /// Enabled by default, and required by every type but
+    /// [`blocking::SignalFd`](crate::blocking::SignalFd): building anything
+    /// else with it disabled fails with `InvalidInput`. A blocking descriptor
+    /// suits code also reading it directly, through `as_raw_fd`. Only
+    /// signalfd descriptors can be made blocking, the other backends ignore
+    /// this.
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.options.nonblocking = nonblocking;
+        self
+    }
+
+    /// Only delivers the signals for which `filter` returns `true`.
+    ///
+    /// Filters run on every record as it is read, before it is queued, and a
+    /// record has to pass all of them. For instance, to only accept `SIGUSR1`
+    /// from root and ignore kernel-generated `SIGIO`:
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "tokio-01")]
+    /// # fn example() -> std::io::Result<()> {
+    /// use tokio_signalfd::{SignalFd, SIGIO, SIGUSR1};
+    ///
+    /// let signals = SignalFd::builder()
+    ///     .signals(&[SIGUSR1, SIGIO])
+    ///     .filter(|info| info.signo() != SIGUSR1 || info.uid() == 0)
+    ///     .filter(|info| info.signo() != SIGIO || info.code() != libc::SI_KERNEL)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Rejected signals are consumed all the same; they don't reach their
+    /// regular disposition either.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&SignalInfo) -> bool + Send + Sync + 'static,
+    {
+        self.options.filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Delivers pending signals by urgency, from the first of `order` to the
+    /// last, rather than in the order they were read in.
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "tokio-1")]
+    /// # fn run() -> std::io::Result<()> {
+    /// use tokio_signalfd::tokio::SignalFd;
+    /// use tokio_signalfd::{SIGHUP, SIGTERM, SIGUSR1};
+    ///
+    /// let signals = SignalFd::builder()
+    ///     .signals(&[SIGHUP, SIGUSR1, SIGTERM])
+    ///     .priority(&[SIGTERM, SIGHUP])
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Here a shutdown preempts a reload that is pending as well. Signals
+    /// missing from `order` come last, and instances of equally urgent
+    /// signals keep their order. Before each signal is returned, whatever
+    /// else is pending by then is read in, at the cost of a `poll(2)`.
+    pub fn priority<S: Copy + Into<i32>>(mut self, order: &[S]) -> Self {
+        self.options.priority = order.iter().map(|&signal| signal.into()).collect();
+        self
+    }
+
+    /// Bounds the number of records buffered between reads from the
+    /// descriptor and their delivery, handling the excess as `overflow`
+    /// says.
+    ///
+    /// Records are read in batches, and whatever is pending is read in one go
+    /// where a [`priority`](Builder::priority) order or waiting for a signal
+    /// from a future calls for it, so the buffer can grow under a flood of
+    /// realtime signals. Unbounded by default; the capacity is at least one.
+    pub fn queue_capacity(mut self, capacity: usize, overflow: Overflow) -> Self {
+        self.options.capacity = Some(capacity.max(1));
+        self.options.overflow = overflow;
+        self
+    }
+
+    /// Creates the signalfd.
+    ///
+    /// Fails if any of the signals is invalid, or if the mask or the
+    /// descriptor can't be set up.
+    pub fn build(self) -> Result<B> {
+        if !self.options.nonblocking && !self.allow_blocking {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only blocking::SignalFd can use a blocking descriptor",
+            ));
+        }
+        let set = SigSet::from_signals(&self.signals)?;
+        let inner = RawSignalFd::open(&set, self.options)?;
+        (self.make)(inner)
+    }
+}
+
+#[cfg(all(test, feature = "tokio-01"))]
+mod tests {
+    use super::*;
+    use crate::{Signal, SignalFd};
+
+    fn blocked() -> SigSet {
+        let mut set = SigSet::empty();
+        let rv =
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), set.as_raw_mut()) };
+        assert_eq!(rv, 0);
+        set
+    }
+
+    #[test]
+    fn mask_strategies() {
+        let signals = SignalFd::builder()
+            .signal(Signal::Usr1)
+            .mask(MaskStrategy::Keep)
+            .build()
+            .unwrap();
+        assert!(signals.previous_mask().is_none());
+        assert!(!blocked().contains(Signal::Usr1));
+
+        let signals = SignalFd::builder().signal(Signal::Usr2).build().unwrap();
+        assert!(!signals.previous_mask().unwrap().contains(Signal::Usr2));
+        assert!(blocked().contains(Signal::Usr2));
+
+        let signals = SignalFd::builder()
+            .signal(Signal::Hup)
+            .mask(MaskStrategy::SetMask)
+            .build()
+            .unwrap();
+        assert!(signals.previous_mask().unwrap().contains(Signal::Usr2));
+        assert!(blocked().contains(Signal::Hup));
+        assert!(!blocked().contains(Signal::Usr2));
+    }
+
+    #[test]
+    fn restore_mask() {
+        let signals = SignalFd::builder()
+            .signals(&[Signal::Usr1, Signal::Usr2])
+            .build()
+            .unwrap();
+        assert!(blocked().contains(Signal::Usr1));
+        drop(signals);
+        assert!(!blocked().contains(Signal::Usr1));
+        assert!(!blocked().contains(Signal::Usr2));
+
+        let signals = SignalFd::builder()
+            .signal(Signal::Usr1)
+            .restore_mask(false)
+            .build()
+            .unwrap();
+        drop(signals);
+        assert!(blocked().contains(Signal::Usr1));
+    }
+
+    #[test]
+    fn blocking_needs_blocking_type() {
+        let err = SignalFd::builder()
+            .signal(Signal::Usr1)
+            .nonblocking(false)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
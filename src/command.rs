@@ -0,0 +1,81 @@
+//! Giving spawned programs a clean signal state.
+
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::signal::sigrtmax;
+use crate::sigset::SigSet;
+
+/// Resets the signal state of spawned programs.
+///
+/// Children inherit the signal mask of the thread spawning them, and the
+/// signals the parent ignores. While a signalfd has `SIGINT` and `SIGTERM`
+/// blocked, a child spawned as is would have them blocked too, and neither
+/// Ctrl-C nor a plain `kill` would stop it.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use tokio_signalfd::blocking::SignalFd;
+/// use tokio_signalfd::{SignalCommandExt, SIGINT, SIGTERM};
+///
+/// let _signals = SignalFd::new(&[SIGINT, SIGTERM])?;
+/// let child = Command::new("my-worker").reset_signals().spawn()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// For `tokio::process::Command`, go through its `as_std_mut`.
+pub trait SignalCommandExt {
+    /// Unblocks every signal and restores the default disposition of all of
+    /// them in the child, right before it runs the program.
+    ///
+    /// Handlers are reset by `exec` anyway; this matters for the signals
+    /// that are blocked or ignored.
+    fn reset_signals(&mut self) -> &mut Self;
+}
+
+impl SignalCommandExt for Command {
+    fn reset_signals(&mut self) -> &mut Self {
+        let empty = SigSet::empty();
+        let max = sigrtmax();
+        // Only async-signal-safe calls between fork and exec.
+        let reset = move || {
+            for signal in 1..=max {
+                if signal != libc::SIGKILL && signal != libc::SIGSTOP {
+                    // Numbers that aren't signals, or are reserved by libc,
+                    // just fail.
+                    unsafe { libc::signal(signal, libc::SIG_DFL) };
+                }
+            }
+            unsafe {
+                libc::pthread_sigmask(libc::SIG_SETMASK, empty.as_raw(), std::ptr::null_mut())
+            };
+            Ok(())
+        };
+        unsafe { self.pre_exec(reset) }
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use super::*;
+
+    fn status_field(command: &mut Command, field: &str) -> String {
+        let output = command.arg("/proc/self/status").output().unwrap();
+        let status = String::from_utf8(output.stdout).unwrap();
+        let line = status.lines().find(|line| line.starts_with(field)).unwrap();
+        line[field.len()..].trim().to_string()
+    }
+
+    #[test]
+    fn resets_mask() {
+        let set = SigSet::from_signals(&[libc::SIGPROF]).unwrap();
+        let previous = set.block().unwrap();
+        let blocked = status_field(&mut Command::new("cat"), "SigBlk:");
+        let reset = status_field(Command::new("cat").reset_signals(), "SigBlk:");
+        unsafe {
+            libc::pthread_sigmask(libc::SIG_SETMASK, previous.as_raw(), std::ptr::null_mut())
+        };
+        assert_ne!(u64::from_str_radix(&blocked, 16).unwrap(), 0);
+        assert_eq!(u64::from_str_radix(&reset, 16).unwrap(), 0);
+    }
+}
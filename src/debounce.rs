@@ -0,0 +1,188 @@
+//! Collapsing bursts of signals.
+
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio1::time::Sleep;
+
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::source::SignalSource;
+
+/// A signal source delivering a burst of the same signal as a single event,
+/// once the signal has stopped arriving for a quiet period.
+///
+/// Meant for reload signals: a deployment rewriting a configuration
+/// directory file by file may send a `SIGHUP` for every file, and reloading
+/// once it's done is both cheaper and avoids loading a half-written state.
+/// The event delivered is the last of the burst. Each signal is debounced on
+/// its own, and errors are passed through right away.
+///
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use tokio_signalfd::{tokio::SignalFd, SignalSource, SIGHUP};
+///
+/// let quiet = Duration::from_millis(500);
+/// let mut signals = SignalFd::new(&[SIGHUP])?.debounce(quiet);
+/// loop {
+///     signals.recv().await?;
+///     println!("reloading");
+/// }
+/// # }
+/// ```
+///
+/// Waiting for the quiet period needs the runtime's time driver to be
+/// enabled.
+pub struct Debounced<S> {
+    source: S,
+    quiet: Duration,
+    debounced: Option<SigSet>,
+    /// The last instance of each burst and when it can be delivered, in the
+    /// order the bursts started in.
+    bursts: Vec<(SignalInfo, Instant)>,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> Debounced<S> {
+    /// Debounces every signal from `source`, delivering each burst once
+    /// there was no instance of the signal for `quiet`.
+    pub fn new(source: S, quiet: Duration) -> Self {
+        Debounced {
+            source,
+            quiet,
+            debounced: None,
+            bursts: Vec::new(),
+            timer: None,
+        }
+    }
+
+    /// Debounces only the signals from `source` that are in `signals`, and
+    /// passes the others through as they arrive.
+    ///
+    /// Leaving the terminating signals out this way keeps shutdowns prompt.
+    pub fn only(source: S, quiet: Duration, signals: &SigSet) -> Self {
+        Debounced {
+            debounced: Some(*signals),
+            ..Debounced::new(source, quiet)
+        }
+    }
+
+    /// Returns the quiet period ending a burst.
+    pub fn quiet(&self) -> Duration {
+        self.quiet
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the underlying source.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Returns the underlying source. Bursts that haven't ended yet are lost.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Records `info`, returning it if it isn't debounced.
+    fn observe(&mut self, info: SignalInfo, now: Instant) -> Option<SignalInfo> {
+        let signo = info.signo();
+        if let Some(debounced) = &self.debounced {
+            if !debounced.contains(signo) {
+                return Some(info);
+            }
+        }
+        let deadline = now + self.quiet;
+        match self
+            .bursts
+            .iter_mut()
+            .find(|(held, _)| held.signo() == signo)
+        {
+            Some(burst) => *burst = (info, deadline),
+            None => self.bursts.push((info, deadline)),
+        }
+        None
+    }
+}
+
+impl<S: SignalSource> SignalSource for Debounced<S> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        loop {
+            let now = Instant::now();
+            while let Poll::Ready(result) = self.source.poll_recv(cx) {
+                if let Some(info) = self.observe(result?, now) {
+                    return Poll::Ready(Ok(info));
+                }
+            }
+            if let Some(i) = self.bursts.iter().position(|&(_, end)| end <= now) {
+                return Poll::Ready(Ok(self.bursts.remove(i).0));
+            }
+            let wake = match self.bursts.iter().map(|&(_, end)| end).min() {
+                Some(wake) => wake,
+                None => {
+                    self.timer = None;
+                    return Poll::Pending;
+                }
+            };
+            let timer = match &mut self.timer {
+                Some(timer) => {
+                    timer.as_mut().reset(wake.into());
+                    timer
+                }
+                None => self
+                    .timer
+                    .insert(Box::pin(tokio1::time::sleep_until(wake.into()))),
+            };
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Never ends, only the source can fail.
+impl<S: SignalSource + Unpin> futures_core::Stream for Debounced<S> {
+    type Item = Result<SignalInfo>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSignalSource;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn collapses_bursts() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        rt.block_on(async {
+            let mock = MockSignalSource::new();
+            let sender = mock.sender();
+            let quiet = Duration::from_millis(50);
+            let hup = SigSet::from_signals(&[libc::SIGHUP]).unwrap();
+            let mut signals = Debounced::only(mock, quiet, &hup);
+            let start = Instant::now();
+            for _ in 0..3 {
+                sender.send(libc::SIGHUP);
+                tokio1::time::sleep(Duration::from_millis(10)).await;
+            }
+            sender.send(libc::SIGTERM);
+
+            assert_eq!(signals.recv().await.unwrap().signo(), libc::SIGTERM);
+            assert_eq!(signals.recv().await.unwrap().signo(), libc::SIGHUP);
+            assert!(start.elapsed() >= Duration::from_millis(20) + quiet);
+            let more = tokio1::time::timeout(quiet * 2, signals.recv()).await;
+            assert!(more.is_err());
+        });
+    }
+}
@@ -0,0 +1,327 @@
+//! Distributing received signals to several consumers.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Wake, Waker};
+
+use futures_core::Stream;
+
+use crate::signal::Signal;
+
+/// Splits one signal stream into a separate stream per signal.
+///
+/// A signalfd delivers all of its signals through a single descriptor, so a
+/// program reacting to `SIGHUP` in one place and `SIGTERM` in another would
+/// otherwise have to demultiplex them by hand. The dispatcher takes over the
+/// stream, and [`subscribe`](SignalDispatcher::subscribe) returns a
+/// [`Subscription`] yielding only the given signal.
+///
+/// There is no driver task: whichever subscription is polled reads from the
+/// underlying stream and queues what it reads for the others, waking them.
+/// Every subscriber to a signal gets its own copy of it; signals nobody has
+/// subscribed to are discarded. Subscribing doesn't change the set of signals
+/// received, so the stream has to be watching the signal already.
+pub struct SignalDispatcher<T = i32> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    wakers: Arc<Wakers>,
+}
+
+struct State<T> {
+    signals: Pin<Box<dyn Stream<Item = Result<T>> + Send>>,
+    subscribers: HashMap<u64, Subscriber<T>>,
+    next_id: u64,
+    done: bool,
+}
+
+struct Subscriber<T> {
+    signal: Option<Signal>,
+    queue: VecDeque<T>,
+}
+
+/// The wakers of subscriptions waiting for the underlying stream.
+///
+/// The stream is polled with a waker for the whole set, so that a signal
+/// wakes every waiting subscription rather than only the one that happened to
+/// poll last.
+#[derive(Default)]
+struct Wakers(Mutex<HashMap<u64, Waker>>);
+
+impl Wakers {
+    fn register(&self, id: u64, waker: &Waker) {
+        self.0.lock().unwrap().insert(id, waker.clone());
+    }
+
+    fn wake_one(&self, id: u64) {
+        let waker = self.0.lock().unwrap().remove(&id);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Wake for Wakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers = std::mem::take(&mut *self.0.lock().unwrap());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    fn new<S>(signals: S) -> Arc<Self>
+    where
+        S: Stream<Item = Result<T>> + Send + 'static,
+    {
+        let state = State {
+            signals: Box::pin(signals),
+            subscribers: HashMap::new(),
+            next_id: 0,
+            done: false,
+        };
+        Arc::new(Shared {
+            state: Mutex::new(state),
+            wakers: Arc::default(),
+        })
+    }
+
+    /// Adds a subscriber to `signal`, or to every signal if it's `None`.
+    fn tap(self: &Arc<Self>, signal: Option<Signal>, queue: VecDeque<T>) -> Tap<T> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.insert(id, Subscriber { signal, queue });
+        Tap {
+            id,
+            shared: self.clone(),
+        }
+    }
+}
+
+impl<T> SignalDispatcher<T> {
+    /// Creates a dispatcher distributing the signals from `signals`.
+    pub fn new<S>(signals: S) -> Self
+    where
+        S: Stream<Item = Result<T>> + Send + 'static,
+    {
+        SignalDispatcher {
+            shared: Shared::new(signals),
+        }
+    }
+
+    /// Returns a stream of the occurrences of `signal` from now on.
+    pub fn subscribe<S: Into<i32>>(&self, signal: S) -> Subscription<T> {
+        let signal = Signal::from_raw(signal.into());
+        Subscription {
+            tap: self.shared.tap(Some(signal), VecDeque::new()),
+            signal,
+        }
+    }
+}
+
+/// A stream of one signal, returned by [`SignalDispatcher::subscribe`].
+///
+/// Errors reading the underlying stream are yielded by whichever subscription
+/// was reading it at the time. Once the underlying stream ends, every
+/// subscription ends after yielding what was already queued for it.
+pub struct Subscription<T = i32> {
+    tap: Tap<T>,
+    signal: Signal,
+}
+
+impl<T> Subscription<T> {
+    /// Returns the signal this subscription yields.
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+}
+
+impl<T: Clone + Into<Signal>> Stream for Subscription<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().tap.poll_next(cx)
+    }
+}
+
+/// Fans every received signal out to any number of receivers.
+///
+/// Like [`SignalDispatcher`], but each [`Receiver`] sees every signal, so that
+/// several subsystems can all react to `SIGTERM` without competing for the
+/// one stream. Receivers can be cloned; a clone starts with the signals its
+/// original hasn't yielded yet.
+///
+/// Signals are queued until each receiver takes them, so a receiver that is
+/// never polled keeps its signals around; drop receivers that are no longer
+/// needed.
+pub struct SignalBroadcaster<T = i32> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> SignalBroadcaster<T> {
+    /// Creates a broadcaster distributing the signals from `signals`.
+    pub fn new<S>(signals: S) -> Self
+    where
+        S: Stream<Item = Result<T>> + Send + 'static,
+    {
+        SignalBroadcaster {
+            shared: Shared::new(signals),
+        }
+    }
+
+    /// Returns a receiver for the signals received from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver {
+            tap: self.shared.tap(None, VecDeque::new()),
+        }
+    }
+}
+
+/// A stream of every signal, returned by [`SignalBroadcaster::subscribe`].
+///
+/// Errors and the end of the underlying stream are handled as for a
+/// [`Subscription`].
+pub struct Receiver<T = i32> {
+    tap: Tap<T>,
+}
+
+impl<T: Clone> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let queue = {
+            let state = self.tap.shared.state.lock().unwrap();
+            state.subscribers[&self.tap.id].queue.clone()
+        };
+        Receiver {
+            tap: self.tap.shared.tap(None, queue),
+        }
+    }
+}
+
+impl<T: Clone + Into<Signal>> Stream for Receiver<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().tap.poll_next(cx)
+    }
+}
+
+impl<T: Clone + Into<Signal>> State<T> {
+    /// Queues `item` for its subscribers, returning their IDs.
+    fn route(&mut self, item: T) -> Vec<u64> {
+        let signal = item.clone().into();
+        let mut routed = Vec::new();
+        for (&id, subscriber) in &mut self.subscribers {
+            if subscriber.signal.is_none_or(|s| s == signal) {
+                subscriber.queue.push_back(item.clone());
+                routed.push(id);
+            }
+        }
+        routed
+    }
+}
+
+/// One subscriber's end of the shared stream.
+struct Tap<T> {
+    id: u64,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Clone + Into<Signal>> Tap<T> {
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        let shared = &*self.shared;
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            let queued = state.subscribers.get_mut(&self.id);
+            if let Some(item) = queued.and_then(|subscriber| subscriber.queue.pop_front()) {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if state.done {
+                return Poll::Ready(None);
+            }
+            shared.wakers.register(self.id, cx.waker());
+            let waker = Waker::from(shared.wakers.clone());
+            let mut hub = Context::from_waker(&waker);
+            match ready!(state.signals.as_mut().poll_next(&mut hub)) {
+                Some(Ok(item)) => {
+                    for id in state.route(item) {
+                        if id != self.id {
+                            shared.wakers.wake_one(id);
+                        }
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => {
+                    state.done = true;
+                    shared.wakers.wake_by_ref();
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for Tap<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.subscribers.remove(&self.id);
+        }
+        if let Ok(mut wakers) = self.shared.wakers.0.lock() {
+            wakers.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use futures_util::StreamExt;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn routes_by_signal() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+            let dispatcher = SignalDispatcher::new(signals);
+            let mut usr1 = dispatcher.subscribe(libc::SIGUSR1);
+            let mut usr2 = dispatcher.subscribe(libc::SIGUSR2);
+            let mut also_usr2 = dispatcher.subscribe(libc::SIGUSR2);
+
+            unsafe { libc::raise(libc::SIGUSR2) };
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(usr1.next().await.unwrap().unwrap(), libc::SIGUSR1);
+            assert_eq!(usr2.next().await.unwrap().unwrap(), libc::SIGUSR2);
+            assert_eq!(also_usr2.next().await.unwrap().unwrap(), libc::SIGUSR2);
+        });
+    }
+
+    #[test]
+    fn broadcasts_to_every_receiver() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+            let broadcaster = SignalBroadcaster::new(signals);
+            let mut first = broadcaster.subscribe();
+
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(first.next().await.unwrap().unwrap(), libc::SIGUSR1);
+            let mut second = broadcaster.subscribe();
+            unsafe { libc::raise(libc::SIGUSR2) };
+            assert_eq!(first.next().await.unwrap().unwrap(), libc::SIGUSR2);
+
+            let mut clone = second.clone();
+            assert_eq!(second.next().await.unwrap().unwrap(), libc::SIGUSR2);
+            assert_eq!(clone.next().await.unwrap().unwrap(), libc::SIGUSR2);
+        });
+    }
+}
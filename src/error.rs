@@ -0,0 +1,84 @@
+//! Errors specific to receiving signals.
+
+use std::io;
+
+use crate::signal::InvalidSignal;
+
+/// What went wrong, for errors originating in this crate.
+///
+/// Functions keep returning `io::Error`s, for compatibility, with a
+/// `SignalFdError` inside for the failures listed here. Errors passed on
+/// unchanged from elsewhere, e.g. reading the descriptor, carry none.
+///
+/// ```
+/// use tokio_signalfd::{SignalFdError, SigSet};
+///
+/// let err = SigSet::from_str_list("TERM,9000").unwrap_err();
+/// match SignalFdError::from_io(&err) {
+///     Some(SignalFdError::InvalidSignal(s)) => assert_eq!(s.signo(), 9000),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SignalFdError {
+    /// Changing the calling thread's signal mask failed.
+    #[error("failed to change the signal mask: {0}")]
+    Mask(#[source] io::Error),
+    /// Creating the signalfd, or its equivalent for the backend, failed.
+    #[error("failed to create the signal descriptor: {0}")]
+    Create(#[source] io::Error),
+    /// A signal was out of range, or can't be caught.
+    #[error(transparent)]
+    InvalidSignal(#[from] InvalidSignal),
+    /// A read from the descriptor ended with a partial record.
+    #[error("short read from signalfd: {trailing} trailing bytes of a {size} byte record")]
+    ShortRead {
+        /// The number of bytes past the last complete record.
+        trailing: usize,
+        /// The size of a record.
+        size: usize,
+    },
+    /// Records were discarded because the buffer was full, see
+    /// [`Overflow::Error`](crate::Overflow::Error).
+    #[error("signal buffer overflowed: {dropped} records discarded")]
+    Overflow {
+        /// The number of records discarded by the failing read.
+        dropped: usize,
+    },
+    /// Registering the descriptor with the reactor failed.
+    #[error("failed to register the signal descriptor with the reactor: {0}")]
+    Register(#[source] io::Error),
+    /// The receiver's limit of queued signals was reached, see
+    /// [`send_value`](crate::signal::send_value).
+    #[error("too many signals queued for the receiver: {0}")]
+    QueueFull(#[source] io::Error),
+}
+
+impl SignalFdError {
+    /// Returns the `SignalFdError` inside `err`, if any.
+    pub fn from_io(err: &io::Error) -> Option<&SignalFdError> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    /// Returns the kind of the `io::Error` the error is reported as: that of
+    /// the underlying error if there is one, `InvalidInput` for bad signals,
+    /// `InvalidData` for short reads and `Other` for overflows.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            SignalFdError::Mask(err)
+            | SignalFdError::Create(err)
+            | SignalFdError::Register(err)
+            | SignalFdError::QueueFull(err) => err.kind(),
+            SignalFdError::InvalidSignal(_) => io::ErrorKind::InvalidInput,
+            SignalFdError::ShortRead { .. } => io::ErrorKind::InvalidData,
+            SignalFdError::Overflow { .. } => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl From<SignalFdError> for io::Error {
+    fn from(err: SignalFdError) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
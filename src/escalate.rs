@@ -0,0 +1,359 @@
+//! Shutting down child processes, escalating to `SIGKILL`.
+
+use std::collections::HashSet;
+use std::future::{poll_fn, Future};
+use std::io::{self, Result};
+use std::pin::{pin, Pin};
+use std::process::ExitStatus;
+use std::task::Poll;
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio1::time;
+
+use crate::reaper::ChildReaper;
+use crate::signal::{self, Signal};
+
+/// Stops a set of child processes, killing the ones that don't exit in time.
+///
+/// [`run`](ChildShutdown::run) sends every child `SIGTERM` (or the signal
+/// set with [`signal`](ChildShutdown::signal)), and collects them through a
+/// [`ChildReaper`] as they exit. Children still running once the grace period
+/// is over are sent `SIGKILL`, and waited for up to a kill timeout. The
+/// returned [`ShutdownSummary`] tells which children exited on their own,
+/// which had to be killed, and which still hadn't been collected by then.
+///
+/// ```no_run
+/// # async fn example(pids: Vec<libc::pid_t>) -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use tokio_signalfd::{tokio::SignalFd, ChildReaper, ChildShutdown, Signal};
+///
+/// let mut reaper = ChildReaper::new(SignalFd::new(&[Signal::Chld])?);
+/// let mut shutdown = ChildShutdown::new(Duration::from_secs(10));
+/// for pid in pids {
+///     shutdown.add(pid);
+/// }
+/// let summary = shutdown.run_on_terminate(&mut reaper).await?;
+/// for (pid, _) in summary.killed() {
+///     eprintln!("child {} had to be killed", pid);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ChildShutdown {
+    children: Vec<libc::pid_t>,
+    grace: Duration,
+    kill_timeout: Duration,
+    signal: Signal,
+}
+
+/// The outcome of a [`ChildShutdown`].
+#[derive(Debug, Default)]
+pub struct ShutdownSummary {
+    exited: Vec<(libc::pid_t, ExitStatus)>,
+    killed: Vec<(libc::pid_t, ExitStatus)>,
+    others: Vec<(libc::pid_t, ExitStatus)>,
+    missing: Vec<libc::pid_t>,
+    stragglers: Vec<libc::pid_t>,
+}
+
+impl ShutdownSummary {
+    /// Returns the children that exited within the grace period.
+    pub fn exited(&self) -> &[(libc::pid_t, ExitStatus)] {
+        &self.exited
+    }
+
+    /// Returns the children that were sent `SIGKILL`.
+    ///
+    /// A child may have exited on its own just before being killed, so check
+    /// the status to tell.
+    pub fn killed(&self) -> &[(libc::pid_t, ExitStatus)] {
+        &self.killed
+    }
+
+    /// Returns the other children the reaper collected in the meantime.
+    pub fn others(&self) -> &[(libc::pid_t, ExitStatus)] {
+        &self.others
+    }
+
+    /// Returns the children that were gone before they could be signalled,
+    /// having been collected elsewhere already.
+    pub fn missing(&self) -> &[libc::pid_t] {
+        &self.missing
+    }
+
+    /// Returns the children that weren't collected within the kill timeout
+    /// after `SIGKILL`, e.g. stuck in uninterruptible sleep or collected by
+    /// other code in the meantime.
+    pub fn stragglers(&self) -> &[libc::pid_t] {
+        &self.stragglers
+    }
+}
+
+impl ChildShutdown {
+    /// Creates a shutdown giving children `grace` to exit, and those killed
+    /// afterwards five seconds to be collected.
+    pub fn new(grace: Duration) -> Self {
+        ChildShutdown {
+            children: Vec::new(),
+            grace,
+            kill_timeout: Duration::from_secs(5),
+            signal: Signal::Term,
+        }
+    }
+
+    /// Adds the child `pid` to the ones to stop.
+    pub fn add(&mut self, pid: libc::pid_t) {
+        if !self.children.contains(&pid) {
+            self.children.push(pid);
+        }
+    }
+
+    /// Adds the child `pid` to the ones to stop.
+    pub fn child(mut self, pid: libc::pid_t) -> Self {
+        self.add(pid);
+        self
+    }
+
+    /// Chooses how long to wait for the children sent `SIGKILL`, before
+    /// giving up on them as [stragglers](ShutdownSummary::stragglers).
+    pub fn kill_timeout(mut self, timeout: Duration) -> Self {
+        self.kill_timeout = timeout;
+        self
+    }
+
+    /// Chooses the signal asking children to exit, `SIGTERM` by default.
+    pub fn signal(mut self, signal: Signal) -> Self {
+        self.signal = signal;
+        self
+    }
+
+    /// Stops the children now, collecting them through `reaper`.
+    ///
+    /// Fails with `InvalidInput` if one of the children is watched through
+    /// [`ChildReaper::watch`], as the reaper then doesn't yield its exit.
+    pub async fn run<S, T>(&self, reaper: &mut ChildReaper<S>) -> Result<ShutdownSummary>
+    where
+        S: Stream<Item = Result<T>> + Unpin,
+    {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        if let Some(pid) = self.children.iter().find(|&&pid| reaper.is_watched(pid)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("child {} is watched, so the reaper won't yield it", pid),
+            ));
+        }
+        let mut summary = ShutdownSummary::default();
+        let mut running: HashSet<_> = self.children.iter().copied().collect();
+        for &pid in &self.children {
+            send(pid, self.signal, &mut running, &mut summary)?;
+        }
+
+        let mut deadline = pin!(time::sleep(self.grace));
+        while !running.is_empty() {
+            let (pid, status) = match next_child(reaper, deadline.as_mut()).await? {
+                Some(child) => child,
+                None => break,
+            };
+            if running.remove(&pid) {
+                summary.exited.push((pid, status));
+            } else {
+                summary.others.push((pid, status));
+            }
+        }
+
+        for pid in running.clone() {
+            send(pid, Signal::Kill, &mut running, &mut summary)?;
+        }
+        deadline
+            .as_mut()
+            .reset(time::Instant::now() + self.kill_timeout);
+        while !running.is_empty() {
+            let (pid, status) = match next_child(reaper, deadline.as_mut()).await? {
+                Some(child) => child,
+                None => break,
+            };
+            if running.remove(&pid) {
+                summary.killed.push((pid, status));
+            } else {
+                summary.others.push((pid, status));
+            }
+        }
+        summary.stragglers = running.into_iter().collect();
+        summary.stragglers.sort_unstable();
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("signalfd_shutdown_duration_seconds").record(started.elapsed());
+        Ok(summary)
+    }
+
+    /// Waits for `SIGTERM`, then stops the children.
+    ///
+    /// See [`terminate`](crate::tokio::terminate) for how the signal mask is
+    /// affected.
+    pub async fn run_on_terminate<S, T>(
+        &self,
+        reaper: &mut ChildReaper<S>,
+    ) -> Result<ShutdownSummary>
+    where
+        S: Stream<Item = Result<T>> + Unpin,
+    {
+        crate::tokio::terminate().await?;
+        self.run(reaper).await
+    }
+}
+
+/// Sends `signal` to `pid`, moving it from `running` to the missing children
+/// if it is gone.
+///
+/// A child that exited is only gone once collected, and one collected by the
+/// reaper was already taken out of `running`, so a missing one was collected
+/// elsewhere and won't be yielded.
+fn send(
+    pid: libc::pid_t,
+    signal: Signal,
+    running: &mut HashSet<libc::pid_t>,
+    summary: &mut ShutdownSummary,
+) -> Result<()> {
+    match signal::send(pid, signal) {
+        Err(err) if err.raw_os_error() == Some(libc::ESRCH) => {
+            running.remove(&pid);
+            summary.missing.push(pid);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Returns the next child collected by `reaper`, or `None` once `deadline`
+/// has passed.
+async fn next_child<S, T>(
+    reaper: &mut ChildReaper<S>,
+    mut deadline: Pin<&mut time::Sleep>,
+) -> Result<Option<(libc::pid_t, ExitStatus)>>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    poll_fn(|cx| {
+        if deadline.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Ok(None));
+        }
+        match Pin::new(&mut *reaper).poll_next(cx) {
+            Poll::Ready(Some(child)) => Poll::Ready(child.map(Some)),
+            Poll::Ready(None) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "SIGCHLD stream ended before the children exited",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sigset::SigSet;
+    use crate::tokio::SignalFd;
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::Command;
+    use tokio1::runtime::Builder;
+
+    fn command(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        unsafe {
+            command.pre_exec(|| {
+                let empty = SigSet::empty();
+                libc::pthread_sigmask(libc::SIG_SETMASK, empty.as_raw(), std::ptr::null_mut());
+                Ok(())
+            });
+        }
+        command
+    }
+
+    fn spawn(script: &str) -> libc::pid_t {
+        command(script).spawn().unwrap().id() as libc::pid_t
+    }
+
+    #[test]
+    fn kills_stragglers() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+
+        let nudger = crate::SigchldNudger::start();
+        let summary = rt.block_on(async {
+            let mut reaper = ChildReaper::new(SignalFd::new(&[Signal::Chld]).unwrap());
+            let polite = spawn("exec sleep 10");
+            let stubborn = spawn("trap '' TERM; while :; do sleep 0.05; done");
+            // Let the shell install its trap.
+            time::sleep(Duration::from_millis(100)).await;
+            let shutdown = ChildShutdown::new(Duration::from_millis(300))
+                .child(polite)
+                .child(stubborn);
+            let summary = shutdown.run(&mut reaper).await.unwrap();
+            assert_eq!(summary.exited().len(), 1);
+            assert_eq!(summary.exited()[0].0, polite);
+            assert_eq!(summary.killed().len(), 1);
+            assert_eq!(summary.killed()[0].0, stubborn);
+            summary
+        });
+        drop(nudger);
+        assert_eq!(summary.exited()[0].1.signal(), Some(libc::SIGTERM));
+        assert_eq!(summary.killed()[0].1.signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn skips_collected_children() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let mut reaper = ChildReaper::new(SignalFd::new(&[Signal::Chld]).unwrap());
+            let mut child = Command::new("true").spawn().unwrap();
+            let dead = child.id() as libc::pid_t;
+            child.wait().unwrap();
+
+            let shutdown = ChildShutdown::new(Duration::from_millis(100)).child(dead);
+            let run = time::timeout(Duration::from_secs(5), shutdown.run(&mut reaper));
+            let summary = run.await.expect("hung on a collected child").unwrap();
+            assert_eq!(summary.missing(), [dead]);
+            assert!(summary.exited().is_empty() && summary.killed().is_empty());
+
+            // A grandchild is collected by its parent, never by the reaper.
+            // It reports its pid only once it ignores `SIGTERM`.
+            let script = "(trap '' TERM; exec sh -c 'echo $$; exec sleep 10') & wait";
+            let mut parent = command(script)
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            let mut line = String::new();
+            let stdout = parent.stdout.take().unwrap();
+            std::io::BufRead::read_line(&mut std::io::BufReader::new(stdout), &mut line).unwrap();
+            let grandchild: libc::pid_t = line.trim().parse().unwrap();
+            let shutdown = ChildShutdown::new(Duration::from_millis(100))
+                .kill_timeout(Duration::from_millis(200))
+                .child(grandchild);
+            let run = time::timeout(Duration::from_secs(5), shutdown.run(&mut reaper));
+            let summary = run.await.expect("hung after SIGKILL").unwrap();
+            assert_eq!(summary.stragglers(), [grandchild]);
+            // Unless the reaper collected it already.
+            let _ = parent.wait();
+
+            let watched = spawn("exec sleep 10");
+            let _exit = reaper.watch(watched).unwrap();
+            let err = ChildShutdown::new(Duration::from_millis(100))
+                .child(watched)
+                .run(&mut reaper)
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            signal::send(watched, Signal::Kill).unwrap();
+            let mut status = 0;
+            assert_eq!(unsafe { libc::waitpid(watched, &mut status, 0) }, watched);
+        });
+    }
+}
@@ -0,0 +1,217 @@
+//! Relaying received signals to other processes.
+
+use std::io::{self, Result};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+
+/// Where forwarded signals are sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Targets {
+    Pids(Vec<libc::pid_t>),
+    Group(libc::pid_t),
+}
+
+/// A stream that relays every signal it receives to a set of processes.
+///
+/// This is what wrapper and launcher programs need: the wrapper blocks the
+/// signals, and whatever reaches it is passed on to the child (or its process
+/// group). Each received signal is yielded after it has been forwarded.
+///
+/// The job control stop signals `SIGTSTP`, `SIGTTIN` and `SIGTTOU` are
+/// blocked too, so they would never stop the wrapper itself. After forwarding
+/// one of them the forwarder therefore stops its own process with `SIGSTOP`,
+/// so that the shell sees the whole job stop. Include `SIGCONT` in the watched
+/// signals so that it gets forwarded when the job is resumed.
+pub struct Forwarder<S> {
+    signals: S,
+    targets: Targets,
+    include: Option<SigSet>,
+    exclude: SigSet,
+    stop_self: bool,
+}
+
+impl<S> Forwarder<S> {
+    /// Creates a forwarder relaying `signals` to no process yet.
+    pub fn new(signals: S) -> Self {
+        Forwarder {
+            signals,
+            targets: Targets::Pids(Vec::new()),
+            include: None,
+            exclude: SigSet::empty(),
+            stop_self: true,
+        }
+    }
+
+    /// Forwards signals to `pid`, in addition to any other processes.
+    ///
+    /// Replaces a process group set with [`group`](Forwarder::group).
+    pub fn add_pid(&mut self, pid: libc::pid_t) {
+        match &mut self.targets {
+            Targets::Pids(pids) if !pids.contains(&pid) => pids.push(pid),
+            Targets::Pids(_) => {}
+            Targets::Group(_) => self.targets = Targets::Pids(vec![pid]),
+        }
+    }
+
+    /// Stops forwarding signals to `pid`.
+    pub fn remove_pid(&mut self, pid: libc::pid_t) {
+        if let Targets::Pids(pids) = &mut self.targets {
+            pids.retain(|&p| p != pid);
+        }
+    }
+
+    /// Forwards signals to `pid`, in addition to any other processes.
+    pub fn pid(mut self, pid: libc::pid_t) -> Self {
+        self.add_pid(pid);
+        self
+    }
+
+    /// Forwards signals to the process group `pgid` instead of individual
+    /// processes.
+    pub fn group(mut self, pgid: libc::pid_t) -> Self {
+        self.targets = Targets::Group(pgid);
+        self
+    }
+
+    /// Only forwards `signal` and other explicitly included signals.
+    ///
+    /// By default every received signal is forwarded.
+    pub fn include<T: Into<i32>>(mut self, signal: T) -> Result<Self> {
+        self.include.get_or_insert_with(SigSet::empty).add(signal)?;
+        Ok(self)
+    }
+
+    /// Never forwards `signal`; it is still yielded by the stream.
+    pub fn exclude<T: Into<i32>>(mut self, signal: T) -> Result<Self> {
+        self.exclude.add(signal)?;
+        Ok(self)
+    }
+
+    /// Chooses whether the forwarder stops its own process after forwarding a
+    /// job control stop signal. Enabled by default.
+    pub fn stop_self(mut self, stop_self: bool) -> Self {
+        self.stop_self = stop_self;
+        self
+    }
+
+    /// Returns `true` if `signal` passes the include and exclude lists.
+    pub fn forwards(&self, signal: Signal) -> bool {
+        let included = self.include.as_ref().is_none_or(|set| set.contains(signal));
+        included && !self.exclude.contains(signal)
+    }
+
+    /// Sends `signal` to the target processes.
+    ///
+    /// Processes that no longer exist are skipped.
+    pub fn forward(&self, signal: Signal) -> Result<()> {
+        let targets = match &self.targets {
+            Targets::Pids(pids) => pids.clone(),
+            Targets::Group(pgid) => vec![-pgid],
+        };
+        for pid in targets {
+            if unsafe { libc::kill(pid, signal.as_raw()) } < 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(libc::ESRCH) {
+                    return Err(err);
+                }
+                continue;
+            }
+            #[cfg(feature = "metrics")]
+            metrics::counter!(
+                "signalfd_signals_forwarded_total",
+                "signal" => signal.label()
+            )
+            .increment(1);
+        }
+        Ok(())
+    }
+
+    fn relay(&self, signal: Signal) -> Result<()> {
+        if !self.forwards(signal) {
+            return Ok(());
+        }
+        self.forward(signal)?;
+        let job_stop = matches!(signal, Signal::Tstp | Signal::Ttin | Signal::Ttou);
+        if job_stop && self.stop_self {
+            // SIGSTOP can't be blocked; execution resumes here on SIGCONT.
+            if unsafe { libc::kill(libc::getpid(), libc::SIGSTOP) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+}
+
+impl<S, T> Stream for Forwarder<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Into<Signal>,
+{
+    type Item = Result<Signal>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let signal = match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+            Some(Ok(signal)) => signal.into(),
+            Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(this.relay(signal).map(|()| signal)))
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use futures_util::StreamExt;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn forwards_to_child() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[Signal::Usr1, Signal::Usr2]).unwrap();
+            let mut command = Command::new("sleep");
+            command.arg("10");
+            unsafe {
+                command.pre_exec(|| {
+                    let empty = SigSet::empty();
+                    libc::pthread_sigmask(libc::SIG_SETMASK, empty.as_raw(), std::ptr::null_mut());
+                    Ok(())
+                });
+            }
+            let mut child = command.spawn().unwrap();
+            let pid = child.id() as libc::pid_t;
+            let mut forwarder = Forwarder::new(signals)
+                .pid(pid)
+                .exclude(Signal::Usr2)
+                .unwrap();
+
+            unsafe { libc::raise(libc::SIGUSR2) };
+            assert_eq!(forwarder.next().await.unwrap().unwrap(), Signal::Usr2);
+            assert!(child.try_wait().unwrap().is_none());
+
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(forwarder.next().await.unwrap().unwrap(), Signal::Usr1);
+            let status = child.wait().unwrap();
+            use std::os::unix::process::ExitStatusExt;
+            assert_eq!(status.signal(), Some(libc::SIGUSR1));
+        });
+    }
+}
@@ -0,0 +1,148 @@
+//! Shutting down within an orchestrator's termination grace period.
+
+use std::io::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio1::time;
+
+use crate::signal::Signal;
+
+/// Shutdown timing for services run by Kubernetes and similar orchestrators.
+///
+/// When a pod is deleted, `SIGTERM` is sent at the same time as the pod is
+/// removed from its service's endpoints, and `SIGKILL` follows once the
+/// termination grace period (30 seconds by default) has passed. A service
+/// therefore wants to:
+///
+/// 1. report that it is draining right away, e.g. by failing its readiness
+///    probe, see [`drain_flag`](GracePeriod::drain_flag);
+/// 2. keep serving for a little while, until load balancers have stopped
+///    sending new requests, see
+///    [`deregistration_delay`](GracePeriod::deregistration_delay);
+/// 3. then stop accepting, finish what is in flight and exit before the
+///    grace period is over.
+///
+/// [`wait`](GracePeriod::wait) returns when it is time for the third step.
+/// Should the shutdown take too long, the process exits on its own shortly
+/// before the grace period ends, from a separate thread so that even a
+/// stuck runtime doesn't keep it from happening.
+pub struct GracePeriod {
+    grace: Duration,
+    margin: Duration,
+    delay: Duration,
+    exit_code: i32,
+    draining: DrainFlag,
+}
+
+/// Tells whether the service is shutting down, see
+/// [`GracePeriod::drain_flag`].
+#[derive(Clone, Debug, Default)]
+pub struct DrainFlag(Arc<AtomicBool>);
+
+impl DrainFlag {
+    /// Returns `true` once `SIGTERM` has been received.
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+impl GracePeriod {
+    /// Creates a grace period of 30 seconds, with no deregistration delay.
+    pub fn new() -> Self {
+        GracePeriod {
+            grace: Duration::from_secs(30),
+            margin: Duration::from_secs(1),
+            delay: Duration::ZERO,
+            exit_code: 128 + libc::SIGTERM,
+            draining: DrainFlag::default(),
+        }
+    }
+
+    /// Sets the grace period, to match `terminationGracePeriodSeconds`.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Sets how long before the end of the grace period the process exits
+    /// by itself, one second by default.
+    pub fn margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Sets how long to keep serving after `SIGTERM` before
+    /// [`wait`](GracePeriod::wait) returns.
+    pub fn deregistration_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the status the process exits with when the grace period runs
+    /// out, 143 (as if killed by `SIGTERM`) by default.
+    pub fn exit_code(mut self, code: i32) -> Self {
+        self.exit_code = code;
+        self
+    }
+
+    /// Returns a flag that is raised once `SIGTERM` has been received.
+    pub fn drain_flag(&self) -> DrainFlag {
+        self.draining.clone()
+    }
+
+    /// Waits for `SIGTERM`, plus the deregistration delay.
+    ///
+    /// As soon as the signal arrives the drain flag is raised and the
+    /// countdown to the forced exit starts. See
+    /// [`terminate`](crate::tokio::terminate) for how the signal mask is
+    /// affected.
+    pub async fn wait(&self) -> Result<()> {
+        crate::tokio::any_of(&[Signal::Term]).await?;
+        self.draining.0.store(true, Ordering::Release);
+        let deadline = self.grace.saturating_sub(self.margin);
+        let code = self.exit_code;
+        std::thread::Builder::new()
+            .name("signalfd-grace".into())
+            .spawn(move || {
+                std::thread::sleep(deadline);
+                std::process::exit(code);
+            })?;
+        time::sleep(self.delay).await;
+        Ok(())
+    }
+}
+
+impl Default for GracePeriod {
+    fn default() -> Self {
+        GracePeriod::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future;
+    use std::time::Instant;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn drains_after_sigterm() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let grace = GracePeriod::new()
+                .grace(Duration::from_secs(3600))
+                .deregistration_delay(Duration::from_millis(50));
+            let draining = grace.drain_flag();
+            assert!(!draining.is_draining());
+
+            let start = Instant::now();
+            let raise = async { unsafe { libc::raise(libc::SIGTERM) } };
+            let (waited, _) = future::join(grace.wait(), raise).await;
+            waited.unwrap();
+            assert!(draining.is_draining());
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        });
+    }
+}
@@ -0,0 +1,230 @@
+//! Running callbacks for received signals.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use crate::signal::Signal;
+
+type Handler<T> = Arc<dyn Fn(T) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A registry of async handlers run for received signals.
+///
+/// This is for programs that think in handlers rather than streams:
+/// [`on_signal`](SignalHandlers::on_signal) registers a closure returning a
+/// future, which is spawned onto the [`HandlerDriver`] every time the signal
+/// arrives. The driver is a future that has to be spawned on the runtime, or
+/// awaited, for anything to happen:
+///
+/// ```no_run
+/// # #[cfg(feature = "tokio-1")]
+/// # async fn example() -> std::io::Result<()> {
+/// use tokio_signalfd::{tokio::SignalFd, SignalHandlers, SIGHUP, SIGUSR1};
+///
+/// let signals = SignalFd::new(&[SIGHUP, SIGUSR1])?;
+/// let (handlers, driver) = SignalHandlers::new(signals);
+/// handlers.on_signal(SIGHUP, |_| async move { println!("reloading") });
+/// handlers.on_signal(SIGUSR1, |_| async move { println!("dumping stats") });
+/// driver.await
+/// # }
+/// ```
+///
+/// Handlers for the same or different signals run concurrently with each
+/// other, all within the driver's task.
+pub struct SignalHandlers<T = i32> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    handlers: HashMap<u64, (Signal, Handler<T>)>,
+    next_id: u64,
+    shutdown: bool,
+    waker: Option<Waker>,
+}
+
+/// Identifies a handler registered with [`SignalHandlers::on_signal`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+impl<T> SignalHandlers<T> {
+    /// Creates a registry for the signals from `signals`, along with the
+    /// driver running its handlers.
+    pub fn new<S>(signals: S) -> (Self, HandlerDriver<S, T>) {
+        let shared = Arc::new(Mutex::new(Shared {
+            handlers: HashMap::new(),
+            next_id: 0,
+            shutdown: false,
+            waker: None,
+        }));
+        let driver = HandlerDriver {
+            signals: Some(signals),
+            shared: shared.clone(),
+            running: Vec::new(),
+        };
+        (SignalHandlers { shared }, driver)
+    }
+
+    /// Runs `handler` every time `signal` is received.
+    ///
+    /// Registration doesn't change the set of signals received, so the
+    /// stream has to be watching the signal already.
+    pub fn on_signal<S, F, Fut>(&self, signal: S, handler: F) -> HandlerId
+    where
+        S: Into<i32>,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let signal = Signal::from_raw(signal.into());
+        let handler: Handler<T> = Arc::new(move |item| Box::pin(handler(item)));
+        let mut shared = self.shared.lock().unwrap();
+        let id = shared.next_id;
+        shared.next_id += 1;
+        shared.handlers.insert(id, (signal, handler));
+        HandlerId(id)
+    }
+
+    /// Unregisters a handler, returning `false` if it wasn't registered.
+    ///
+    /// Runs of the handler that have already started are left to finish.
+    pub fn remove(&self, id: HandlerId) -> bool {
+        self.shared.lock().unwrap().handlers.remove(&id.0).is_some()
+    }
+
+    /// Stops the driver from receiving further signals.
+    ///
+    /// The driver resolves once the handlers that are already running have
+    /// finished.
+    pub fn shutdown(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.shutdown = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Clone for SignalHandlers<T> {
+    fn clone(&self) -> Self {
+        SignalHandlers {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Future reading signals and running their handlers, returned by
+/// [`SignalHandlers::new`].
+///
+/// It resolves after [`SignalHandlers::shutdown`] or the end of the signal
+/// stream, once the running handlers have finished. An error reading the
+/// stream resolves it immediately, dropping the running handlers.
+pub struct HandlerDriver<S, T = i32> {
+    signals: Option<S>,
+    shared: Arc<Mutex<Shared<T>>>,
+    running: Vec<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<S, T> HandlerDriver<S, T>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Clone + Into<Signal>,
+{
+    /// Starts the handlers for every signal that is ready, returning `false`
+    /// once no further signals will be received.
+    fn receive(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        let signals = match &mut self.signals {
+            Some(signals) => signals,
+            None => return Ok(false),
+        };
+        loop {
+            {
+                let mut shared = self.shared.lock().unwrap();
+                if shared.shutdown {
+                    break;
+                }
+                shared.waker = Some(cx.waker().clone());
+            }
+            let item = match Pin::new(&mut *signals).poll_next(cx) {
+                Poll::Ready(Some(item)) => item?,
+                Poll::Ready(None) => break,
+                Poll::Pending => return Ok(true),
+            };
+            let signal = item.clone().into();
+            // Handlers may use the registry themselves.
+            let matching: Vec<_> = {
+                let shared = self.shared.lock().unwrap();
+                let handlers = shared.handlers.values();
+                handlers
+                    .filter(|(s, _)| *s == signal)
+                    .map(|(_, handler)| handler.clone())
+                    .collect()
+            };
+            let started = matching.iter().map(|handler| handler(item.clone()));
+            self.running.extend(started);
+        }
+        self.signals = None;
+        Ok(false)
+    }
+}
+
+impl<S, T> Future for HandlerDriver<S, T>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Clone + Into<Signal>,
+{
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let receiving = this.receive(cx)?;
+        this.running
+            .retain_mut(|handler| handler.as_mut().poll(cx).is_pending());
+        if receiving || !this.running.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn runs_handlers_until_shutdown() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+            let (handlers, driver) = SignalHandlers::new(signals);
+            let calls = Arc::new(AtomicUsize::new(0));
+
+            let counted = calls.clone();
+            let removed = handlers.on_signal(libc::SIGUSR2, move |_| {
+                counted.fetch_add(10, Ordering::SeqCst);
+                async {}
+            });
+            assert!(handlers.remove(removed));
+            let counted = calls.clone();
+            let stopping = handlers.clone();
+            handlers.on_signal(libc::SIGUSR1, move |signal| {
+                assert_eq!(signal, libc::SIGUSR1);
+                counted.fetch_add(1, Ordering::SeqCst);
+                stopping.shutdown();
+                async {}
+            });
+
+            unsafe { libc::raise(libc::SIGUSR2) };
+            unsafe { libc::raise(libc::SIGUSR1) };
+            driver.await.unwrap();
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        });
+    }
+}
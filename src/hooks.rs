@@ -0,0 +1,169 @@
+//! Running shutdown hooks when the process is asked to stop.
+
+use std::future::Future;
+use std::io::{self, Result};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio1::time;
+
+use crate::signal::Signal;
+
+type Hook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// A registry of async hooks run in order when shutting down.
+///
+/// Subsystems [`register`](ShutdownHooks::register) a hook with a priority,
+/// for instance to stop accepting connections, drain in-flight requests and
+/// flush buffers. [`run_on_signal`](ShutdownHooks::run_on_signal) then waits
+/// for `SIGTERM` or `SIGINT` and runs the hooks one after another, lowest
+/// priority first and in registration order among equal priorities.
+///
+/// All hooks together have to finish within the deadline. Once it passes the
+/// running hook is dropped and the remaining ones are skipped, so that the
+/// process still exits in time for whoever asked it to stop.
+///
+/// The registry can be cloned and shared across subsystems; hooks registered
+/// after the hooks started running are ignored.
+///
+/// With the `systemd` feature, [`notify_systemd`](ShutdownHooks::notify_systemd)
+/// makes running the hooks report the shutdown to systemd first.
+#[derive(Clone)]
+pub struct ShutdownHooks {
+    shared: Arc<Mutex<Shared>>,
+}
+
+struct Shared {
+    hooks: Vec<(i32, Hook)>,
+    deadline: Duration,
+    started: bool,
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    notify: bool,
+}
+
+impl ShutdownHooks {
+    /// Creates an empty registry whose hooks have `deadline` to finish.
+    pub fn new(deadline: Duration) -> Self {
+        ShutdownHooks {
+            shared: Arc::new(Mutex::new(Shared {
+                hooks: Vec::new(),
+                deadline,
+                started: false,
+                #[cfg(all(feature = "systemd", target_os = "linux"))]
+                notify: false,
+            })),
+        }
+    }
+
+    /// Chooses whether running the hooks first sends `STOPPING=1` to
+    /// systemd, extending its stop timeout to the deadline. Disabled by
+    /// default.
+    ///
+    /// See [`notify_stopping`](crate::systemd::notify_stopping).
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    pub fn notify_systemd(self, notify: bool) -> Self {
+        self.shared.lock().unwrap().notify = notify;
+        self
+    }
+
+    /// Adds `hook`, to be run with the given priority.
+    pub fn register<F, Fut>(&self, priority: i32, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.started {
+            let hook: Hook = Box::new(move || Box::pin(hook()));
+            shared.hooks.push((priority, hook));
+        }
+    }
+
+    /// Runs the hooks now.
+    ///
+    /// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if they didn't all
+    /// finish before the deadline.
+    pub async fn run(&self) -> Result<()> {
+        let (mut hooks, deadline) = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.started = true;
+            #[cfg(all(feature = "systemd", target_os = "linux"))]
+            if shared.notify {
+                // Failing to tell systemd is no reason not to shut down.
+                let _ = crate::systemd::notify_stopping(Some(shared.deadline));
+            }
+            (std::mem::take(&mut shared.hooks), shared.deadline)
+        };
+        hooks.sort_by_key(|&(priority, _)| priority);
+        let total = hooks.len();
+        let mut finished = 0;
+        let run = async {
+            for (_, hook) in hooks {
+                hook().await;
+                finished += 1;
+            }
+        };
+        match time::timeout(deadline, run).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "shutdown hooks exceeded their {:?} deadline, {} of {} finished",
+                    deadline, finished, total
+                ),
+            )),
+        }
+    }
+
+    /// Waits for `SIGTERM` or `SIGINT`, then runs the hooks, returning the
+    /// signal received.
+    ///
+    /// See [`any_of`](crate::tokio::any_of) for how the signal mask is
+    /// affected.
+    pub async fn run_on_signal(&self) -> Result<Signal> {
+        let signal = crate::tokio::any_of(&[Signal::Term, Signal::Int]).await?;
+        self.run().await?;
+        Ok(signal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn runs_in_priority_order() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let hooks = ShutdownHooks::new(Duration::from_secs(5));
+            let order = Arc::new(Mutex::new(Vec::new()));
+            for (priority, name) in [(10, "flush"), (0, "stop"), (10, "close")] {
+                let order = order.clone();
+                hooks.register(priority, move || async move {
+                    order.lock().unwrap().push(name);
+                });
+            }
+
+            let raise = async { unsafe { libc::raise(libc::SIGTERM) } };
+            let (signal, _) = future::join(hooks.run_on_signal(), raise).await;
+            assert_eq!(signal.unwrap(), Signal::Term);
+            assert_eq!(*order.lock().unwrap(), ["stop", "flush", "close"]);
+        });
+    }
+
+    #[test]
+    fn enforces_deadline() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let hooks = ShutdownHooks::new(Duration::from_millis(50));
+            hooks.register(0, || async {});
+            hooks.register(1, || time::sleep(Duration::from_secs(10)));
+            let err = hooks.run().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(err.to_string().contains("1 of 2 finished"));
+        });
+    }
+}
@@ -0,0 +1,94 @@
+//! Taking over signalfds passed in by a supervisor.
+
+use std::env;
+use std::fs;
+use std::io::{self, Result};
+use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+
+use crate::sigset::SigSet;
+
+/// The first descriptor passed with the `LISTEN_FDS` protocol.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Returns the signals a signalfd receives, as the kernel reports them in
+/// `/proc/self/fdinfo`, or `None` if `fd` isn't a signalfd.
+pub(crate) fn signal_mask(fd: RawFd) -> Result<Option<SigSet>> {
+    let info = fs::read_to_string(format!("/proc/self/fdinfo/{}", fd))?;
+    let mask = match info.lines().find_map(|line| line.strip_prefix("sigmask:")) {
+        Some(mask) => mask.trim(),
+        None => return Ok(None),
+    };
+    let bits = u64::from_str_radix(mask, 16).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed sigmask {:?}: {}", mask, err),
+        )
+    })?;
+    let mut set = SigSet::empty();
+    for signal in (1..=64).filter(|signal| bits & 1 << (signal - 1) != 0) {
+        // Bits past the highest signal are never set.
+        let _ = set.add(signal);
+    }
+    Ok(Some(set))
+}
+
+/// Returns a duplicate of the first signalfd passed to this process with
+/// the `LISTEN_FDS` protocol, among those named `name` if given.
+///
+/// The passed descriptor itself is left open, since other code might look
+/// for it too.
+pub(crate) fn listen_fd(name: Option<&str>) -> Result<Option<OwnedFd>> {
+    let ours = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<libc::pid_t>().ok())
+        == Some(unsafe { libc::getpid() });
+    let count = match env::var("LISTEN_FDS").ok().and_then(|fds| fds.parse().ok()) {
+        Some(count) if ours => count,
+        _ => return Ok(None),
+    };
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    let mut names = names.split(':');
+    for fd in (LISTEN_FDS_START..).take(count) {
+        let fd_name = names.next();
+        if name.is_some() && fd_name != name {
+            continue;
+        }
+        if signal_mask(fd)?.is_some() {
+            let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+            if dup < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(Some(unsafe { OwnedFd::from_raw_fd(dup) }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::RawSignalFd;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn adopts_signalfd() {
+        let set = SigSet::from_signals(&[libc::SIGUSR1, libc::SIGHUP]).unwrap();
+        let fd = unsafe { libc::signalfd(-1, set.as_raw(), 0) };
+        assert!(fd >= 0);
+        let mut inner = RawSignalFd::adopt(unsafe { OwnedFd::from_raw_fd(fd) }).unwrap();
+        assert!(inner.signals().contains(libc::SIGUSR1));
+        assert!(inner.signals().contains(libc::SIGHUP));
+        assert!(!inner.signals().contains(libc::SIGUSR2));
+        let flags = unsafe { libc::fcntl(inner.as_raw_fd(), libc::F_GETFL) };
+        assert_ne!(flags & libc::O_NONBLOCK, 0);
+
+        assert!(inner.read_one().unwrap().is_none());
+        unsafe { libc::raise(libc::SIGUSR1) };
+        assert_eq!(inner.read_one().unwrap().unwrap().signo(), libc::SIGUSR1);
+
+        let err = RawSignalFd::adopt(fs::File::open("/dev/null").unwrap().into())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,158 @@
+//! A minimal init process for containers.
+
+use std::future::poll_fn;
+use std::io::Result;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+
+use crate::forward::Forwarder;
+use crate::reaper::reap;
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::tokio::SignalFd;
+
+/// Signals that are never forwarded: `SIGKILL` and `SIGSTOP` can't be caught,
+/// and the synchronous ones are only meaningful to the faulting process.
+const UNFORWARDABLE: [i32; 11] = [
+    libc::SIGKILL,
+    libc::SIGSTOP,
+    libc::SIGILL,
+    libc::SIGTRAP,
+    libc::SIGABRT,
+    libc::SIGBUS,
+    libc::SIGFPE,
+    libc::SIGSEGV,
+    libc::SIGSYS,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+/// Runs a command the way an init process (PID 1) has to, in the spirit of
+/// tini.
+///
+/// All forwardable signals are received through a signalfd and passed on to
+/// the child, and every zombie that gets reparented to this process is reaped.
+/// [`run`](InitMode::run) resolves to the child's exit status once it exits.
+/// The child starts with the signal mask the calling thread had before.
+///
+/// Signals sent to the process, `SIGCHLD` in particular, are only received if
+/// no other thread leaves them unblocked, so this is best run on a
+/// current-thread runtime before any other threads are spawned.
+///
+/// ```no_run
+/// # async fn init() -> std::io::Result<()> {
+/// use std::process::Command;
+/// use tokio_signalfd::InitMode;
+///
+/// let status = InitMode::new(Command::new("my-server")).run().await?;
+/// std::process::exit(InitMode::exit_code(status));
+/// # }
+/// ```
+pub struct InitMode {
+    command: Command,
+    signals: SigSet,
+    subreaper: bool,
+}
+
+impl InitMode {
+    /// Creates an init process for `command`.
+    pub fn new(command: Command) -> Self {
+        let mut signals = SigSet::full();
+        for &signal in &UNFORWARDABLE {
+            let _ = signals.remove(signal);
+        }
+        InitMode {
+            command,
+            signals,
+            subreaper: false,
+        }
+    }
+
+    /// Leaves `signal` alone instead of forwarding it. `SIGCHLD` can't be
+    /// excluded.
+    pub fn exclude<S: Into<i32>>(mut self, signal: S) -> Result<Self> {
+        let signal = signal.into();
+        if signal != libc::SIGCHLD {
+            self.signals.remove(signal)?;
+        }
+        Ok(self)
+    }
+
+    /// Registers the process as a child subreaper, so that orphaned
+    /// descendants are reparented to it even when it isn't PID 1.
+    pub fn subreaper(mut self, subreaper: bool) -> Self {
+        self.subreaper = subreaper;
+        self
+    }
+
+    /// Spawns the command and supervises it until it exits.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime with IO enabled.
+    pub async fn run(mut self) -> Result<ExitStatus> {
+        if self.subreaper && unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut signals = SignalFd::from_set(&self.signals)?.signal_stream();
+        let mask = signals.previous_mask().copied().unwrap_or_default();
+        unsafe {
+            self.command.pre_exec(move || {
+                libc::pthread_sigmask(libc::SIG_SETMASK, mask.as_raw(), std::ptr::null_mut());
+                Ok(())
+            });
+        }
+        let child = self.command.spawn()?;
+        let pid = child.id() as libc::pid_t;
+        let forwarder = Forwarder::new(()).pid(pid).stop_self(false);
+
+        loop {
+            let signal: Signal = poll_fn(|cx| signals.poll_recv(cx)).await?.into();
+            if signal != Signal::Chld {
+                forwarder.forward(signal)?;
+                continue;
+            }
+            let mut status = None;
+            while let Some((reaped, exit)) = reap()? {
+                if reaped == pid {
+                    status = Some(exit);
+                }
+            }
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Returns the exit code an init process should exit with for `status`,
+    /// following the shell convention of `128 + signal` for children killed
+    /// by a signal.
+    pub fn exit_code(status: ExitStatus) -> i32 {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn exits_with_child_status() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 0.1 & exit 3"]);
+
+        let nudger = crate::SigchldNudger::start();
+        let status = rt.block_on(InitMode::new(command).run()).unwrap();
+        drop(nudger);
+        assert_eq!(InitMode::exit_code(status), 3);
+    }
+}
@@ -0,0 +1,157 @@
+//! Escalating repeated interrupts to a forced exit.
+
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+
+use crate::signal::{raise_default, Signal};
+
+/// What an interrupt asks for, as yielded by [`InterruptPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Shut down gracefully.
+    Graceful,
+    /// Stop right away, the user is out of patience.
+    Force,
+}
+
+/// A stream applying the usual double Ctrl-C policy.
+///
+/// The first interrupt asks for a graceful shutdown; another one arriving
+/// within the window asks to quit right away. An interrupt after the window
+/// has passed starts over with a graceful shutdown. Every signal received
+/// from the wrapped stream counts as an interrupt, so it would typically
+/// watch just `SIGINT`.
+///
+/// With [`abort`](InterruptPolicy::abort) enabled the forced exit happens
+/// in the stream itself: the signal is raised again with its default
+/// disposition and unblocked, so that the process is killed by it and the
+/// parent (a shell, say) sees it terminated by that signal rather than
+/// exiting normally.
+pub struct InterruptPolicy<S> {
+    signals: S,
+    window: Duration,
+    abort: bool,
+    last: Option<Instant>,
+}
+
+impl<S> InterruptPolicy<S> {
+    /// Creates a policy for the signals from `signals`, with a window of two
+    /// seconds.
+    pub fn new(signals: S) -> Self {
+        InterruptPolicy {
+            signals,
+            window: Duration::from_secs(2),
+            abort: false,
+            last: None,
+        }
+    }
+
+    /// Sets how soon a second interrupt has to follow the first one to force
+    /// an exit.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Chooses whether a forced exit kills the process instead of being
+    /// yielded. Disabled by default.
+    pub fn abort(mut self, abort: bool) -> Self {
+        self.abort = abort;
+        self
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+
+    fn classify(&mut self, now: Instant) -> Interrupt {
+        match self.last {
+            Some(last) if now.duration_since(last) <= self.window => {
+                self.last = None;
+                Interrupt::Force
+            }
+            _ => {
+                self.last = Some(now);
+                Interrupt::Graceful
+            }
+        }
+    }
+}
+
+impl<S, T> Stream for InterruptPolicy<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Into<Signal>,
+{
+    type Item = Result<Interrupt>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let signal: Signal = match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+            Some(Ok(signal)) => signal.into(),
+            Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            None => return Poll::Ready(None),
+        };
+        let interrupt = this.classify(Instant::now());
+        if interrupt == Interrupt::Force && this.abort {
+            // Only returns if the signal doesn't terminate the process.
+            if let Err(err) = raise_default(signal) {
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+        Poll::Ready(Some(Ok(interrupt)))
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::sigset::SigSet;
+    use crate::tokio::SignalFd;
+    use futures_util::StreamExt;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn second_interrupt_forces() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGINT]).unwrap();
+            let mut policy = InterruptPolicy::new(signals).window(Duration::from_secs(60));
+            unsafe { libc::raise(libc::SIGINT) };
+            assert_eq!(policy.next().await.unwrap().unwrap(), Interrupt::Graceful);
+            unsafe { libc::raise(libc::SIGINT) };
+            assert_eq!(policy.next().await.unwrap().unwrap(), Interrupt::Force);
+        });
+
+        let mut policy = InterruptPolicy::new(()).window(Duration::from_millis(10));
+        let start = Instant::now();
+        assert_eq!(policy.classify(start), Interrupt::Graceful);
+        let late = start + Duration::from_millis(20);
+        assert_eq!(policy.classify(late), Interrupt::Graceful);
+    }
+
+    #[test]
+    fn abort_kills_with_signal() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            let int = SigSet::from_signals(&[Signal::Int]).unwrap();
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, int.as_raw(), std::ptr::null_mut()) };
+            let _ = raise_default(Signal::Int);
+            unsafe { libc::_exit(0) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::WTERMSIG(status), libc::SIGINT);
+    }
+}
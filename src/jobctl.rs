@@ -0,0 +1,133 @@
+//! Suspending and resuming the process on `SIGTSTP`.
+
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+
+use crate::signal::{raise_default, Signal};
+
+type Callback = Box<dyn FnMut() + Send>;
+
+/// A stream that suspends the process when it receives `SIGTSTP`.
+///
+/// With `SIGTSTP` blocked, Ctrl-Z no longer stops the process, which is what
+/// full-screen terminal programs want: they have to restore the terminal
+/// before stopping, and set it up again once resumed. On every `SIGTSTP` this
+/// stream runs the [`on_suspend`](JobControl::on_suspend) callback, then
+/// stops the process by raising `SIGTSTP` again with its default disposition
+/// and briefly unblocking it. Once the process is continued by `SIGCONT`, the
+/// [`on_resume`](JobControl::on_resume) callback runs and the `SIGTSTP` is
+/// yielded. Every other signal is yielded as is.
+///
+/// `SIGTSTP` has to be blocked in every thread, so that the terminal's stop
+/// request reaches the signalfd rather than stopping the process directly.
+pub struct JobControl<S> {
+    signals: S,
+    on_suspend: Option<Callback>,
+    on_resume: Option<Callback>,
+}
+
+impl<S> JobControl<S> {
+    /// Creates a stream handling the `SIGTSTP`s received by `signals`.
+    pub fn new(signals: S) -> Self {
+        JobControl {
+            signals,
+            on_suspend: None,
+            on_resume: None,
+        }
+    }
+
+    /// Runs `callback` right before the process stops.
+    pub fn on_suspend<F: FnMut() + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_suspend = Some(Box::new(callback));
+        self
+    }
+
+    /// Runs `callback` once the process has been continued.
+    pub fn on_resume<F: FnMut() + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_resume = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+
+    fn suspend(&mut self) -> Result<()> {
+        if let Some(callback) = &mut self.on_suspend {
+            callback();
+        }
+        // Returns once the process has been continued.
+        raise_default(Signal::Tstp)?;
+        if let Some(callback) = &mut self.on_resume {
+            callback();
+        }
+        Ok(())
+    }
+}
+
+impl<S, T> Stream for JobControl<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Into<Signal>,
+{
+    type Item = Result<Signal>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let signal = match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+            Some(Ok(signal)) => signal.into(),
+            Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            None => return Poll::Ready(None),
+        };
+        if signal == Signal::Tstp {
+            if let Err(err) = this.suspend() {
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+        Poll::Ready(Some(Ok(signal)))
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::sigset::SigSet;
+
+    #[test]
+    fn stops_until_continued() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            // Stop signals are discarded in an orphaned process group, which
+            // the test runner's may be; the parent keeps this one attached.
+            unsafe { libc::setpgid(0, 0) };
+            let tstp = SigSet::from_signals(&[Signal::Tstp]).unwrap();
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, tstp.as_raw(), std::ptr::null_mut()) };
+            let code = if raise_default(Signal::Tstp).is_ok() {
+                0
+            } else {
+                1
+            };
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status = 0;
+        assert_eq!(
+            unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) },
+            pid
+        );
+        assert!(libc::WIFSTOPPED(status));
+        assert_eq!(libc::WSTOPSIG(status), libc::SIGTSTP);
+        assert_eq!(unsafe { libc::kill(pid, libc::SIGCONT) }, 0);
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 0);
+    }
+}
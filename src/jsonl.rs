@@ -0,0 +1,82 @@
+//! Writing received signals out as newline-delimited JSON.
+
+use std::future::poll_fn;
+use std::io::{self, Result};
+use std::pin::Pin;
+
+use futures_core::Stream;
+use serde::Serialize;
+use tokio1::io::{AsyncWrite, AsyncWriteExt};
+
+/// Writes every item of a signal stream as a line of JSON, for auditing
+/// signals from a sidecar or shipping them to a log pipeline.
+///
+/// Works with any stream of serializable items, e.g. the streams'
+/// [`info_stream`](crate::tokio::SignalFd::info_stream) for full
+/// [`SignalInfo`](crate::SignalInfo) records:
+///
+/// ```no_run
+/// use tokio_signalfd::tokio::SignalFd;
+/// use tokio_signalfd::{JsonLines, Signal};
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let signals = SignalFd::new(&[Signal::Hup, Signal::Usr1])?.info_stream();
+/// let mut socket = tokio1::net::UnixStream::connect("/run/audit.sock").await?;
+/// JsonLines::new(signals).write_to(&mut socket).await
+/// # }
+/// ```
+pub struct JsonLines<S> {
+    signals: S,
+}
+
+impl<S> JsonLines<S> {
+    /// Creates an adapter writing out `signals`.
+    pub fn new(signals: S) -> Self {
+        JsonLines { signals }
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+}
+
+impl<S, T> JsonLines<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Serialize,
+{
+    /// Writes the stream's items to `writer` until it ends, flushing after
+    /// every line.
+    ///
+    /// Stops at the first error, from the stream or the writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&mut self, writer: &mut W) -> Result<()> {
+        let mut line = Vec::new();
+        while let Some(item) = poll_fn(|cx| Pin::new(&mut self.signals).poll_next(cx)).await {
+            line.clear();
+            serde_json::to_writer(&mut line, &item?).map_err(io::Error::from)?;
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Signal;
+
+    #[test]
+    fn writes_lines() {
+        let rt = tokio1::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let signals = futures_util::stream::iter(vec![Ok(Signal::Hup), Ok(Signal::Realtime(1))]);
+        let mut out = Vec::new();
+        rt.block_on(JsonLines::new(signals).write_to(&mut out))
+            .unwrap();
+        assert_eq!(out, b"\"SIGHUP\"\n\"SIGRTMIN+1\"\n");
+    }
+}
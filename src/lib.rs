@@ -1,167 +1,240 @@
-use std::io::{self, Result};
-use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
-
-use libc;
-
-use mio::unix::EventedFd;
-use mio::{self, Evented, PollOpt, Ready, Token};
-
-use futures::{try_ready, Async, Poll, Stream};
-use tokio_io::AsyncRead;
-use tokio_reactor::PollEvented;
-
-pub use libc::{SIGINT, SIGTERM};
-
-#[repr(C)]
-struct signalfd_siginfo {
-    ssi_signo: u32,
-    _dont_care: [u8; 124],
+#![cfg_attr(
+    not(any(feature = "tokio-01", feature = "tokio-1", feature = "async-io")),
+    allow(dead_code)
+)]
+
+#[cfg(all(unix, feature = "async-std"))]
+pub mod async_std;
+#[cfg(all(unix, feature = "async-io"))]
+mod asyncio;
+#[cfg(unix)]
+pub mod blocking;
+#[cfg(unix)]
+mod builder;
+#[cfg(unix)]
+mod command;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod debounce;
+#[cfg(all(unix, feature = "futures-03"))]
+mod dispatch;
+#[cfg(unix)]
+mod error;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod escalate;
+#[cfg(all(unix, feature = "futures-03"))]
+mod forward;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod grace;
+#[cfg(all(unix, feature = "futures-03"))]
+mod handlers;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod hooks;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod inherit;
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+mod init;
+#[cfg(all(unix, feature = "futures-03"))]
+mod interrupt;
+#[cfg(all(unix, feature = "futures-03"))]
+mod jobctl;
+#[cfg(all(unix, feature = "json-lines"))]
+mod jsonl;
+#[cfg(unix)]
+mod local;
+#[cfg(all(unix, feature = "mio-1"))]
+mod mio;
+#[cfg(all(unix, feature = "tokio-blocking"))]
+mod offload;
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+mod pidfd;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod ratelimit;
+#[cfg(unix)]
+mod raw;
+#[cfg(all(unix, feature = "futures-03"))]
+mod reaper;
+#[cfg(all(unix, feature = "futures-03"))]
+mod reload;
+#[cfg(all(unix, feature = "futures-03"))]
+mod resize;
+#[cfg(unix)]
+pub mod restore;
+#[cfg(all(unix, feature = "tokio-1"))]
+mod shutdown;
+#[cfg(unix)]
+mod siginfo;
+#[cfg(unix)]
+pub mod signal;
+#[cfg(unix)]
+mod sigset;
+#[cfg(unix)]
+mod source;
+#[cfg(unix)]
+mod sys;
+#[cfg(all(feature = "systemd", target_os = "linux"))]
+pub mod systemd;
+#[cfg(unix)]
+mod terminal;
+#[cfg(unix)]
+pub mod testing;
+#[cfg(all(unix, feature = "futures-03"))]
+pub mod upgrade;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+
+#[cfg(all(unix, feature = "tokio-01"))]
+mod tokio01;
+#[cfg(all(unix, feature = "tokio-01"))]
+pub use crate::tokio01::{any_of, ctrl_c, terminate, SignalFd};
+
+#[cfg(all(unix, feature = "tokio-1"))]
+pub mod tokio;
+
+#[cfg(all(unix, feature = "macros"))]
+pub use tokio_signalfd_macros::main;
+
+// Paths the macros expand to, so that callers don't need the crates
+// themselves.
+#[cfg(all(unix, feature = "macros"))]
+#[doc(hidden)]
+pub mod __private {
+    pub use tokio1;
 }
 
-struct Inner(RawFd);
+#[cfg(all(feature = "tokio-uring", target_os = "linux"))]
+pub mod tokio_uring;
+
+// Without Unix signals only the shutdown helpers are provided, so that
+// portable programs can keep a single code path.
+#[cfg(not(unix))]
+mod nonunix;
+#[cfg(all(not(unix), feature = "tokio-1"))]
+pub use crate::nonunix::tokio;
+#[cfg(all(not(unix), feature = "tokio-01"))]
+pub use crate::nonunix::{ctrl_c, terminate};
+
+#[cfg(all(unix, feature = "async-io"))]
+pub use crate::asyncio::AsyncSignalFd;
+#[cfg(unix)]
+pub use crate::builder::{Builder, ExistingHandlers, MaskStrategy, Overflow};
+#[cfg(unix)]
+pub use crate::command::SignalCommandExt;
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::debounce::Debounced;
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::dispatch::{Receiver, SignalBroadcaster, SignalDispatcher, Subscription};
+#[cfg(unix)]
+pub use crate::error::SignalFdError;
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::escalate::{ChildShutdown, ShutdownSummary};
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::forward::Forwarder;
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::grace::{DrainFlag, GracePeriod};
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::handlers::{HandlerDriver, HandlerId, SignalHandlers};
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::hooks::ShutdownHooks;
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+pub use crate::init::InitMode;
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::interrupt::{Interrupt, InterruptPolicy};
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::jobctl::JobControl;
+#[cfg(all(unix, feature = "json-lines"))]
+pub use crate::jsonl::JsonLines;
+#[cfg(unix)]
+pub use crate::local::LocalSignalFd;
+#[cfg(all(unix, feature = "mio-1"))]
+pub use crate::mio::MioSignalFd;
+#[cfg(all(unix, feature = "tokio-blocking"))]
+pub use crate::offload::SpawnBlockingSignalFd;
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+pub use crate::pidfd::PidFd;
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::ratelimit::{Excess, RateLimit, RateLimited};
+#[cfg(unix)]
+pub use crate::raw::RawSignalFd;
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::reaper::{ChildExit, ChildReaper};
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::reload::Reloader;
+#[cfg(all(unix, feature = "futures-03"))]
+pub use crate::resize::{window_size, ResizeEvents};
+#[cfg(all(unix, feature = "tokio-1"))]
+pub use crate::shutdown::ShutdownToken;
+#[cfg(unix)]
+pub use crate::siginfo::{BusCode, ChildCode, PollCode, SegvCode, SignalCode, SignalInfo};
+#[cfg(unix)]
+pub use crate::signal::{InvalidSignal, Signal};
+#[cfg(unix)]
+pub use crate::sigset::SigSet;
+#[cfg(unix)]
+pub use crate::source::{MockSender, MockSignalSource, Recv, SignalSource};
+#[cfg(unix)]
+pub use crate::sys::Backend;
+#[cfg(unix)]
+pub use crate::terminal::TakeUntilTerminal;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use crate::uring::UringSignalFd;
+/// Standard signal numbers, re-exported so that callers don't need to depend on
+/// `libc` directly.
+#[cfg(unix)]
+pub use libc::{
+    SIGABRT, SIGALRM, SIGBUS, SIGCHLD, SIGCONT, SIGFPE, SIGHUP, SIGILL, SIGINT, SIGIO, SIGIOT,
+    SIGKILL, SIGPIPE, SIGPROF, SIGQUIT, SIGSEGV, SIGSTOP, SIGSYS, SIGTERM, SIGTRAP, SIGTSTP,
+    SIGTTIN, SIGTTOU, SIGURG, SIGUSR1, SIGUSR2, SIGVTALRM, SIGWINCH, SIGXCPU, SIGXFSZ,
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use libc::{SIGPOLL, SIGPWR, SIGSTKFLT};
+/// Bounds of the realtime signal range, see [`Signal::rt`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use libc::{SIGRTMAX, SIGRTMIN};
+
+/// Serializes tests that spawn child processes, since collecting children with
+/// `waitpid(-1)` in one test would steal them from the others.
+#[cfg(all(test, feature = "tokio-1"))]
+pub(crate) static CHILDREN: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Keeps sending `SIGCHLD` to the thread that started it, until dropped.
+///
+/// A child's `SIGCHLD` may go to any other test thread, which discards it, so
+/// tests waiting for one on their own thread have it nudged along.
+#[cfg(all(test, feature = "tokio-1"))]
+pub(crate) struct SigchldNudger {
+    done: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
 
-impl Inner {
-    fn new(signals: &[libc::c_int]) -> Result<Self> {
-        unsafe {
-            let mut sig_set = std::mem::MaybeUninit::<libc::sigset_t>::uninit();
-            if libc::sigemptyset(sig_set.as_mut_ptr()) < 0 {
-                return Err(io::Error::last_os_error());
-            }
-            let mut sig_set = sig_set.assume_init();
-            for signal in signals {
-                if libc::sigaddset(&mut sig_set, *signal) < 0 {
-                    return Err(io::Error::last_os_error());
+#[cfg(all(test, feature = "tokio-1"))]
+impl SigchldNudger {
+    pub(crate) fn start() -> Self {
+        use std::sync::atomic::Ordering;
+
+        let target = unsafe { libc::pthread_self() } as usize;
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let done = done.clone();
+            move || {
+                while !done.load(Ordering::Acquire) {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    unsafe { libc::pthread_kill(target as libc::pthread_t, libc::SIGCHLD) };
                 }
             }
-            if libc::pthread_sigmask(libc::SIG_BLOCK, &sig_set, std::ptr::null_mut()) < 0 {
-                return Err(io::Error::last_os_error());
-            }
-            let fd = libc::signalfd(-1, &sig_set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
-            if fd < 0 {
-                return Err(io::Error::last_os_error());
-            }
-            Ok(Inner(fd))
+        });
+        SigchldNudger {
+            done,
+            thread: Some(thread),
         }
     }
 }
 
-impl Drop for Inner {
+#[cfg(all(test, feature = "tokio-1"))]
+impl Drop for SigchldNudger {
     fn drop(&mut self) {
-        unsafe { libc::close(self.0) };
-    }
-}
-
-impl io::Read for Inner {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let rv =
-            unsafe { libc::read(self.0, buf.as_mut_ptr() as *mut std::ffi::c_void, buf.len()) };
-        if rv < 0 {
-            return Err(io::Error::last_os_error());
-        }
-        Ok(rv as usize)
-    }
-}
-
-impl Evented for Inner {
-    fn register(
-        &self,
-        poll: &mio::Poll,
-        token: Token,
-        interest: Ready,
-        opts: PollOpt,
-    ) -> Result<()> {
-        poll.register(&EventedFd(&self.0), token, interest, opts)
-    }
-
-    fn reregister(
-        &self,
-        poll: &mio::Poll,
-        token: Token,
-        interest: Ready,
-        opts: PollOpt,
-    ) -> Result<()> {
-        poll.reregister(&EventedFd(&self.0), token, interest, opts)
-    }
-
-    fn deregister(&self, poll: &mio::Poll) -> Result<()> {
-        poll.deregister(&EventedFd(&self.0))
-    }
-}
-
-pub struct SignalFd(PollEvented<Inner>);
-
-impl SignalFd {
-    pub fn new(signals: &[i32]) -> Result<Self> {
-        let inner = Inner::new(signals)?;
-        Ok(SignalFd(PollEvented::new(inner)))
-    }
-}
-
-impl AsRawFd for SignalFd {
-    fn as_raw_fd(&self) -> RawFd {
-        self.0.get_ref().0
-    }
-}
-
-impl FromRawFd for SignalFd {
-    unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        SignalFd(PollEvented::new(Inner(fd)))
-    }
-}
-
-impl io::Read for SignalFd {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.0.read(buf)
-    }
-}
-
-impl AsyncRead for SignalFd {
-    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, io::Error> {
-        self.0.poll_read(buf)
-    }
-}
-
-impl Stream for SignalFd {
-    type Item = i32;
-    type Error = io::Error;
-
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        let mut buf = [0; std::mem::size_of::<signalfd_siginfo>()];
-
-        try_ready!(self.0.poll_read_ready(Ready::readable()));
-
-        match self.poll_read(&mut buf)? {
-            Async::NotReady => Ok(Async::NotReady),
-            Async::Ready(count) => {
-                assert_eq!(count, std::mem::size_of::<signalfd_siginfo>());
-                let mut signum = [0; 4];
-                signum.copy_from_slice(&buf[0..4]);
-                let signum = i32::from_ne_bytes(signum);
-                Ok(Async::Ready(Some(signum)))
-            }
+        self.done.store(true, std::sync::atomic::Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
     }
 }
-
-mod tests {
-    #[test]
-    fn it_works() {
-        use super::*;
-        use tokio::prelude::*;
-
-        let signals = SignalFd::new(&[SIGINT, SIGTERM]).unwrap();
-        let fut = future::lazy(move || {
-            unsafe {
-                libc::raise(SIGINT);
-            }
-            signals
-                .map_err(|err| panic!(err))
-                .for_each(|signal| {
-                    assert_eq!(signal, SIGINT);
-                    Err("ok")
-                })
-                .map_err(|err| assert_eq!(err, "ok"))
-        });
-        tokio::run(fut);
-    }
-}
@@ -0,0 +1,271 @@
+//! Signals for thread-per-core runtimes.
+
+use std::future::Future;
+use std::io::Result;
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+
+use crate::builder::Builder;
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// A signalfd that stays on the thread that created it.
+///
+/// Thread-per-core runtimes such as glommio and monoio run one executor per
+/// thread and never move tasks between them. Signal masks and the signals a
+/// signalfd sees are per thread as well, so this type is neither `Send` nor
+/// `Sync`: the mask is changed on the thread that creates it, and its signals
+/// can only be consumed there. That includes signals directed at that thread
+/// with `pthread_kill(3)`, which makes it possible to signal a particular core.
+///
+/// The type isn't tied to a runtime. Use [`try_recv`](LocalSignalFd::try_recv)
+/// once the descriptor is readable, or hand the runtime's way of waiting for
+/// readability to [`recv_with`](LocalSignalFd::recv_with).
+pub struct LocalSignalFd {
+    inner: RawSignalFd,
+    _local: PhantomData<*const ()>,
+}
+
+impl LocalSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        LocalSignalFd::from_inner(RawSignalFd::new(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        LocalSignalFd::from_inner(RawSignalFd::from_set(set)?)
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(LocalSignalFd::from_inner)
+    }
+
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        Ok(LocalSignalFd {
+            inner,
+            _local: PhantomData,
+        })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the thread's signal mask from before it was changed to block
+    /// the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Gives up the descriptor without closing it, e.g. to hand it to another
+    /// component.
+    ///
+    /// With `restore_mask` unset the signals stay blocked, so that they keep
+    /// arriving through the descriptor; this is what `IntoRawFd` does.
+    /// Otherwise the mask change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals read
+    /// from the descriptor but not yet returned are lost.
+    ///
+    /// The descriptor is close-on-exec; clear `FD_CLOEXEC` to pass it to a
+    /// program started with `exec`, which inherits the mask as well.
+    pub fn into_raw_fd_with(self, restore_mask: bool) -> RawFd {
+        self.inner.into_fd(restore_mask).into_raw_fd()
+    }
+
+    /// Stops receiving the signals, handing them back to their regular
+    /// disposition.
+    ///
+    /// The descriptor is closed, and the mask change is undone as
+    /// it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals that
+    /// were pending on the descriptor are returned rather than delivered to
+    /// their disposition once unblocked.
+    pub fn close(self) -> Result<Vec<SignalInfo>> {
+        self.inner.close()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it again.
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.inner.pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.inner.resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.inner.read_one()
+    }
+
+    /// Returns every signal that can be received without waiting, e.g. to
+    /// deal with the ones that arrived between creating the signalfd and
+    /// starting to poll it.
+    pub fn drain_pending(&mut self) -> Result<Vec<SignalInfo>> {
+        self.inner.drain()
+    }
+
+    /// Waits for the next signal, using `readable` to wait for the descriptor
+    /// to become readable.
+    ///
+    /// `readable` is called with the descriptor each time no signal is
+    /// pending, and should resolve once the runtime's reactor reports it as
+    /// readable.
+    pub async fn recv_with<F, Fut>(&mut self, mut readable: F) -> Result<SignalInfo>
+    where
+        F: FnMut(RawFd) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        loop {
+            if let Some(info) = self.try_recv()? {
+                return Ok(info);
+            }
+            readable(self.inner.as_raw_fd()).await?;
+        }
+    }
+}
+
+impl AsRawFd for LocalSignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for LocalSignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// The signal mask is left as is, see `into_raw_fd_with`.
+impl IntoRawFd for LocalSignalFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.into_raw_fd_with(false)
+    }
+}
+
+/// Adopts an existing signalfd whose signal set is unknown.
+impl From<OwnedFd> for LocalSignalFd {
+    fn from(fd: OwnedFd) -> Self {
+        LocalSignalFd {
+            inner: RawSignalFd::from_fd(fd),
+            _local: PhantomData,
+        }
+    }
+}
+
+/// The signal mask is left as is, so that the signals keep arriving through
+/// the descriptor.
+impl From<LocalSignalFd> for OwnedFd {
+    fn from(signals: LocalSignalFd) -> OwnedFd {
+        signals.inner.into_fd(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::os::unix::io::FromRawFd;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    #[test]
+    fn recv_with_readiness() {
+        let mut signals = LocalSignalFd::new(&[libc::SIGUSR1]).unwrap();
+        assert!(signals.try_recv().unwrap().is_none());
+
+        let mut waits = 0;
+        let recv = signals.recv_with(|_| {
+            waits += 1;
+            unsafe { libc::raise(libc::SIGUSR1) };
+            std::future::ready(Ok(()))
+        });
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(recv).poll(&mut cx) {
+            Poll::Ready(info) => assert_eq!(info.unwrap().signo(), libc::SIGUSR1),
+            Poll::Pending => panic!("signal was pending"),
+        }
+        assert_eq!(waits, 1);
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut signals = LocalSignalFd::new(&[libc::SIGUSR1]).unwrap();
+        signals.pause(false).unwrap();
+        unsafe { libc::raise(libc::SIGUSR1) };
+        assert!(signals.try_recv().unwrap().is_none());
+        signals.resume().unwrap();
+        #[cfg(not(kqueue))]
+        assert_eq!(signals.try_recv().unwrap().unwrap().signo(), libc::SIGUSR1);
+
+        let blocked = || {
+            let mut set = SigSet::empty();
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), set.as_raw_mut()) };
+            set.contains(libc::SIGUSR1)
+        };
+        signals.pause(true).unwrap();
+        assert!(signals.is_paused());
+        assert!(!blocked());
+        signals.resume().unwrap();
+        assert!(blocked());
+    }
+
+    #[test]
+    fn into_raw_fd_mask() {
+        let blocked = || {
+            let mut set = SigSet::empty();
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), set.as_raw_mut()) };
+            set.contains(libc::SIGUSR2)
+        };
+        let fd = LocalSignalFd::new(&[libc::SIGUSR2])
+            .unwrap()
+            .into_raw_fd_with(true);
+        assert!(!blocked());
+        unsafe { libc::close(fd) };
+
+        let fd = LocalSignalFd::new(&[libc::SIGUSR2]).unwrap().into_raw_fd();
+        assert!(blocked());
+        unsafe { libc::raise(libc::SIGUSR2) };
+        let mut signals = LocalSignalFd::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        assert_eq!(signals.try_recv().unwrap().unwrap().signo(), libc::SIGUSR2);
+    }
+}
@@ -0,0 +1,171 @@
+//! mio 1.x integration, for event loops built on mio directly.
+
+use std::io::Result;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
+use mio1::event::Source;
+use mio1::unix::SourceFd;
+use mio1::{Interest, Registry, Token};
+
+use crate::builder::Builder;
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// A signalfd to register with a mio `Poll`.
+///
+/// Setting up the mask and parsing records work as with the async types, but
+/// waiting is left to the caller's event loop: register the descriptor for
+/// readable events, and on each one call [`try_recv`](MioSignalFd::try_recv)
+/// until it returns `None`, since mio only reports new readiness.
+///
+/// ```no_run
+/// use mio1::{Events, Interest, Poll, Token};
+/// use tokio_signalfd::{MioSignalFd, Signal};
+///
+/// let mut poll = Poll::new()?;
+/// let mut signals = MioSignalFd::new(&[Signal::Hup, Signal::Term])?;
+/// poll.registry()
+///     .register(&mut signals, Token(0), Interest::READABLE)?;
+/// let mut events = Events::with_capacity(8);
+/// loop {
+///     poll.poll(&mut events, None)?;
+///     while let Some(info) = signals.try_recv()? {
+///         println!("received signal {}", info.signo());
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MioSignalFd {
+    inner: RawSignalFd,
+}
+
+impl MioSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        MioSignalFd::from_inner(RawSignalFd::new(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        MioSignalFd::from_inner(RawSignalFd::from_set(set)?)
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(MioSignalFd::from_inner)
+    }
+
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        Ok(MioSignalFd { inner })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.remove(signal.into())
+    }
+
+    /// Replaces the descriptor with a new one for the same signals, e.g.
+    /// once the process was restored from a checkpoint.
+    ///
+    /// The new descriptor has to be registered again, closing the old one
+    /// already took it out of the `Poll`. Filters and the mask change are
+    /// kept; signals read but not yet returned are dropped.
+    pub fn reopen(&mut self) -> Result<()> {
+        self.inner.reopen()
+    }
+
+    /// Returns `true` if a restore from a checkpoint was notified since the
+    /// descriptor was opened, see [`restore`](crate::restore).
+    pub fn needs_reopen(&self) -> bool {
+        self.inner.needs_reopen()
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.inner.read_one()
+    }
+}
+
+impl Source for MioSignalFd {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl AsRawFd for MioSignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for MioSignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mio1::{Events, Poll};
+    use std::time::Duration;
+
+    #[test]
+    fn polls() {
+        let mut poll = Poll::new().unwrap();
+        let mut signals = MioSignalFd::new(&[libc::SIGUSR1]).unwrap();
+        poll.registry()
+            .register(&mut signals, Token(7), Interest::READABLE)
+            .unwrap();
+        assert!(signals.try_recv().unwrap().is_none());
+
+        unsafe { libc::raise(libc::SIGUSR1) };
+        let mut events = Events::with_capacity(4);
+        poll.poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(events.iter().next().unwrap().token(), Token(7));
+        let info = signals.try_recv().unwrap().unwrap();
+        assert_eq!(info.signo(), libc::SIGUSR1);
+        assert!(signals.try_recv().unwrap().is_none());
+    }
+}
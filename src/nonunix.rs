@@ -0,0 +1,92 @@
+//! Shutdown helpers for platforms without Unix signals.
+//!
+//! On Windows the tokio 1.x helpers map to console control events, which is
+//! what `SIGINT` and `SIGTERM` correspond to there. Everywhere else, and for
+//! the tokio 0.1 helpers, they fail with `Unsupported`.
+
+use std::io;
+
+#[allow(dead_code)]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "signals are not supported on this platform",
+    )
+}
+
+/// Returns a future failing with `Unsupported`, as there is no `SIGINT` on
+/// this platform.
+#[cfg(feature = "tokio-01")]
+pub fn ctrl_c() -> impl futures::Future<Item = (), Error = io::Error> {
+    futures::future::err(unsupported())
+}
+
+/// Returns a future failing with `Unsupported`, as there is no `SIGTERM` on
+/// this platform.
+#[cfg(feature = "tokio-01")]
+pub fn terminate() -> impl futures::Future<Item = (), Error = io::Error> {
+    futures::future::err(unsupported())
+}
+
+/// Shutdown helpers for tokio 1.x runtimes.
+#[cfg(feature = "tokio-1")]
+pub mod tokio {
+    use std::io::Result;
+
+    /// Waits for Ctrl-C or Ctrl-Break in the console.
+    ///
+    /// Fails with `Unsupported` on platforms other than Windows.
+    #[cfg(windows)]
+    pub async fn ctrl_c() -> Result<()> {
+        use std::future::poll_fn;
+        use tokio1::signal::windows;
+
+        let mut ctrl_c = windows::ctrl_c()?;
+        let mut ctrl_break = windows::ctrl_break()?;
+        poll_fn(|cx| {
+            if ctrl_c.poll_recv(cx).is_ready() || ctrl_break.poll_recv(cx).is_ready() {
+                return std::task::Poll::Ready(());
+            }
+            std::task::Poll::Pending
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Waits for the console window to be closed, or for the user to log off
+    /// or the system to shut down.
+    ///
+    /// Fails with `Unsupported` on platforms other than Windows.
+    #[cfg(windows)]
+    pub async fn terminate() -> Result<()> {
+        use std::future::poll_fn;
+        use tokio1::signal::windows;
+
+        let mut close = windows::ctrl_close()?;
+        let mut logoff = windows::ctrl_logoff()?;
+        let mut shutdown = windows::ctrl_shutdown()?;
+        poll_fn(|cx| {
+            if close.poll_recv(cx).is_ready()
+                || logoff.poll_recv(cx).is_ready()
+                || shutdown.poll_recv(cx).is_ready()
+            {
+                return std::task::Poll::Ready(());
+            }
+            std::task::Poll::Pending
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Fails with `Unsupported`, as there is no `SIGINT` on this platform.
+    #[cfg(not(windows))]
+    pub async fn ctrl_c() -> Result<()> {
+        Err(super::unsupported())
+    }
+
+    /// Fails with `Unsupported`, as there is no `SIGTERM` on this platform.
+    #[cfg(not(windows))]
+    pub async fn terminate() -> Result<()> {
+        Err(super::unsupported())
+    }
+}
@@ -0,0 +1,186 @@
+//! Reading signals on tokio's blocking threads instead of the reactor.
+
+use std::future::poll_fn;
+use std::io::{self, Result};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio1::sync::mpsc;
+
+use crate::blocking;
+use crate::builder::{Builder, Options};
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+use crate::source::SignalSource;
+use crate::sys::Backend;
+
+/// How many signals the reading thread gets ahead of the consumer.
+const CAPACITY: usize = 16;
+
+/// How often the reading thread checks whether the stream was dropped.
+const CHECK_CLOSED: Duration = Duration::from_millis(100);
+
+/// A stream of signals read by a task on tokio's blocking thread pool.
+///
+/// The descriptor is created blocking and is never registered with the
+/// reactor, for processes whose seccomp policy restricts `epoll_ctl`, or
+/// runtimes built without IO. A task started with `spawn_blocking` reads
+/// it and hands the signals over through a channel; it holds on to one of
+/// the pool's threads for as long as the stream exists, and gives it back
+/// shortly after the stream is dropped.
+///
+/// Only the signalfd and kqueue backends are supported. The signals have to
+/// be blocked in every thread as usual, the blocking pool's included. A
+/// thread inherits its mask from whichever thread started it, so block them
+/// before building the runtime, e.g. with `pthread_sigmask(3)`; the mask
+/// change made when creating the stream only reaches pool threads started
+/// afterwards from the same thread.
+pub struct SpawnBlockingSignalFd {
+    inner: RawSignalFd,
+    signals: mpsc::Receiver<Result<SignalInfo>>,
+}
+
+impl SpawnBlockingSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        SpawnBlockingSignalFd::from_set(&SigSet::from_signals(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        let options = Options {
+            nonblocking: false,
+            ..Options::default()
+        };
+        SpawnBlockingSignalFd::from_inner(RawSignalFd::open(set, options)?)
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(SpawnBlockingSignalFd::from_inner)
+            .allow_blocking()
+            .nonblocking(false)
+    }
+
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        // The duplicate is read on the blocking thread, while the original
+        // stays here so that dropping it restores this thread's mask.
+        let reader = blocking::SignalFd::from_inner(inner.try_clone()?)?;
+        let (sender, signals) = mpsc::channel(CAPACITY);
+        tokio1::task::spawn_blocking(move || read(reader, sender));
+        Ok(SpawnBlockingSignalFd { inner, signals })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Polls for the next signal.
+    ///
+    /// Fails with `BrokenPipe` once the reading task has stopped, after
+    /// returning the error that stopped it.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        match ready!(self.signals.poll_recv(cx)) {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the task reading signals has stopped",
+            ))),
+        }
+    }
+
+    /// Waits for the next signal.
+    pub async fn recv(&mut self) -> Result<SignalInfo> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+/// Forwards the signals `reader` receives until the stream is dropped or
+/// reading fails.
+fn read(mut reader: blocking::SignalFd, sender: mpsc::Sender<Result<SignalInfo>>) {
+    loop {
+        let result = match reader.recv_timeout(CHECK_CLOSED) {
+            Ok(Some(info)) => Ok(info),
+            Ok(None) if sender.is_closed() => return,
+            Ok(None) => continue,
+            Err(err) => Err(err),
+        };
+        let failed = result.is_err();
+        if sender.blocking_send(result).is_err() || failed {
+            return;
+        }
+    }
+}
+
+impl SignalSource for SpawnBlockingSignalFd {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        SpawnBlockingSignalFd::poll_recv(self, cx)
+    }
+}
+
+/// Ends after the error that stopped the reading task, if any.
+impl Stream for SpawnBlockingSignalFd {
+    type Item = Result<SignalInfo>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.signals.poll_recv(cx)
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_without_reactor() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        // The signal is sent to the whole process, so it has to be blocked
+        // in all of its threads: fork one with a single thread.
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            let rt = tokio1::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let received = rt.block_on(async {
+                let mut signals = SpawnBlockingSignalFd::new(&[libc::SIGUSR1])?;
+                unsafe { libc::kill(libc::getpid(), libc::SIGUSR1) };
+                signals.recv().await
+            });
+            let code = match received {
+                Ok(info) if info.signo() == libc::SIGUSR1 => 0,
+                _ => 1,
+            };
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 0);
+    }
+}
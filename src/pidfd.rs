@@ -0,0 +1,136 @@
+//! Per-process exit notification through pidfds.
+
+use std::future::poll_fn;
+use std::io::{self, Result};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+use std::task::{ready, Context, Poll};
+
+use tokio1::io::unix::AsyncFd;
+
+/// A handle to a child process that becomes readable once it exits.
+///
+/// Unlike waiting for `SIGCHLD`, a pidfd refers to exactly one process, so
+/// awaiting its exit doesn't race with other code collecting children. Requires
+/// Linux 5.4 or newer, for `waitid(P_PIDFD)`, and a tokio 1.x runtime.
+///
+/// [`wait`](PidFd::wait) collects the child, so don't use it together with a
+/// [`ChildReaper`](crate::ChildReaper); use
+/// [`ChildReaper::watch`](crate::ChildReaper::watch) instead.
+pub struct PidFd {
+    io: AsyncFd<OwnedFd>,
+    pid: libc::pid_t,
+    status: Option<ExitStatus>,
+}
+
+impl PidFd {
+    /// Opens a pidfd for the child process `pid`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime with IO enabled.
+    pub fn open(pid: libc::pid_t) -> Result<Self> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        Ok(PidFd {
+            io: AsyncFd::new(fd)?,
+            pid,
+            status: None,
+        })
+    }
+
+    /// Opens a pidfd for `child`.
+    pub fn from_child(child: &Child) -> Result<Self> {
+        PidFd::open(child.id() as libc::pid_t)
+    }
+
+    /// Returns the process ID this pidfd refers to.
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Polls for the exit of the process, collecting it once it has exited.
+    pub fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<Result<ExitStatus>> {
+        if let Some(status) = self.status {
+            return Poll::Ready(Ok(status));
+        }
+        loop {
+            let mut guard = ready!(self.io.poll_read_ready(cx))?;
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let rv = unsafe {
+                libc::waitid(
+                    libc::P_PIDFD,
+                    self.io.as_raw_fd() as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOHANG,
+                )
+            };
+            if rv < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Poll::Ready(Err(err));
+            }
+            if unsafe { info.si_pid() } == 0 {
+                guard.clear_ready();
+                continue;
+            }
+            let status = exit_status(&info);
+            self.status = Some(status);
+            return Poll::Ready(Ok(status));
+        }
+    }
+
+    /// Waits for the process to exit and collects it.
+    pub async fn wait(&mut self) -> Result<ExitStatus> {
+        poll_fn(|cx| self.poll_wait(cx)).await
+    }
+}
+
+impl AsRawFd for PidFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.as_raw_fd()
+    }
+}
+
+/// Converts the `siginfo_t` filled in by `waitid(2)` into a wait status.
+fn exit_status(info: &libc::siginfo_t) -> ExitStatus {
+    let status = unsafe { info.si_status() };
+    let raw = match info.si_code {
+        libc::CLD_EXITED => (status & 0xff) << 8,
+        libc::CLD_DUMPED => status | 0x80,
+        _ => status,
+    };
+    ExitStatus::from_raw(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    #[allow(clippy::zombie_processes)] // collected through the pidfd
+    fn wait_for_exit() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let child = Command::new("sh").args(["-c", "exit 5"]).spawn().unwrap();
+            let mut pidfd = PidFd::from_child(&child).unwrap();
+            assert_eq!(pidfd.wait().await.unwrap().code(), Some(5));
+
+            let child = Command::new("sleep").arg("10").spawn().unwrap();
+            let mut pidfd = PidFd::from_child(&child).unwrap();
+            unsafe { libc::kill(pidfd.pid(), libc::SIGKILL) };
+            assert_eq!(pidfd.wait().await.unwrap().signal(), Some(libc::SIGKILL));
+        });
+    }
+}
@@ -0,0 +1,344 @@
+//! Protecting services from signal storms.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio1::time::Sleep;
+
+use crate::siginfo::SignalInfo;
+use crate::source::SignalSource;
+
+/// How many instances of a signal get through, as a token bucket: up to
+/// `burst` at once, then one every `period / burst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    burst: u32,
+    interval: Duration,
+}
+
+impl RateLimit {
+    /// Lets `count` signals through per `period`, all of them at once if
+    /// they arrive together.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero.
+    pub fn per(count: u32, period: Duration) -> Self {
+        assert!(count > 0, "a rate limit must let some signals through");
+        RateLimit {
+            burst: count,
+            interval: period / count,
+        }
+    }
+}
+
+/// What happens to signals over their [`RateLimit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Excess {
+    /// They are discarded, and counted in [`RateLimited::dropped`].
+    Drop,
+    /// All instances over the limit are merged into the last of them, which
+    /// is delivered once the limit allows.
+    Coalesce,
+    /// Every one of them is delivered, as fast as the limit allows. They are
+    /// kept in memory until then, however many arrive.
+    Delay,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    tokens: u32,
+    refilled_at: Instant,
+}
+
+/// A signal source letting signals through at a limited rate, each signal
+/// with a bucket of its own.
+///
+/// A peer spamming `SIGHUP` then can't have the service reload continuously,
+/// and doesn't delay other signals. Errors are passed through right away.
+///
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use tokio_signalfd::tokio::SignalFd;
+/// use tokio_signalfd::{Excess, RateLimit, RateLimited, SignalSource};
+/// use tokio_signalfd::{SIGHUP, SIGTERM, SIGUSR1};
+///
+/// let signals = SignalFd::new(&[SIGHUP, SIGUSR1, SIGTERM])?;
+/// let hup = RateLimit::per(1, Duration::from_secs(5));
+/// let mut signals = RateLimited::new(signals, hup, Excess::Coalesce)
+///     .limit(SIGUSR1, RateLimit::per(10, Duration::from_secs(1)));
+/// loop {
+///     let info = signals.recv().await?;
+///     println!("received {}", info.signo());
+/// }
+/// # }
+/// ```
+///
+/// Limited signals wait on a tokio timer, so this has to be polled within a
+/// runtime with the time driver enabled.
+pub struct RateLimited<S> {
+    source: S,
+    default: Option<RateLimit>,
+    limits: HashMap<i32, RateLimit>,
+    excess: Excess,
+    buckets: HashMap<i32, Bucket>,
+    pending: VecDeque<SignalInfo>,
+    timer: Option<Pin<Box<Sleep>>>,
+    dropped: u64,
+}
+
+impl<S> RateLimited<S> {
+    /// Limits every signal from `source` to `limit`, handling the excess as
+    /// `excess` says.
+    pub fn new(source: S, limit: RateLimit, excess: Excess) -> Self {
+        RateLimited::with_default(source, Some(limit), excess)
+    }
+
+    /// Lets signals from `source` through unlimited, except for those given
+    /// a limit with [`limit`](RateLimited::limit).
+    pub fn unlimited(source: S, excess: Excess) -> Self {
+        RateLimited::with_default(source, None, excess)
+    }
+
+    fn with_default(source: S, default: Option<RateLimit>, excess: Excess) -> Self {
+        RateLimited {
+            source,
+            default,
+            limits: HashMap::new(),
+            excess,
+            buckets: HashMap::new(),
+            pending: VecDeque::new(),
+            timer: None,
+            dropped: 0,
+        }
+    }
+
+    /// Gives `signal` a limit of its own.
+    pub fn limit<T: Into<i32>>(mut self, signal: T, limit: RateLimit) -> Self {
+        let signo = signal.into();
+        self.limits.insert(signo, limit);
+        self.buckets.remove(&signo);
+        self
+    }
+
+    /// Returns how many signals were discarded with [`Excess::Drop`].
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns how many signals are held back until the limit allows them.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the underlying source.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Returns the underlying source. Signals held back are lost.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    fn limit_of(&self, signo: i32) -> Option<RateLimit> {
+        self.limits.get(&signo).copied().or(self.default)
+    }
+
+    /// Refills the bucket of `signo` and returns it, or `None` if the signal
+    /// isn't limited.
+    fn bucket(&mut self, signo: i32, now: Instant) -> Option<(&mut Bucket, RateLimit)> {
+        let limit = self.limit_of(signo)?;
+        let bucket = self.buckets.entry(signo).or_insert(Bucket {
+            tokens: limit.burst,
+            refilled_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        let refills = elapsed.as_nanos() / limit.interval.as_nanos().max(1);
+        if refills >= u128::from(limit.burst - bucket.tokens) {
+            bucket.tokens = limit.burst;
+            bucket.refilled_at = now;
+        } else {
+            // Fewer than `burst` refills, which fit in a `u32`.
+            bucket.tokens += refills as u32;
+            bucket.refilled_at += limit.interval * refills as u32;
+        }
+        Some((bucket, limit))
+    }
+
+    /// Takes a token for `signo`, returning whether there was one.
+    fn take(&mut self, signo: i32, now: Instant) -> bool {
+        match self.bucket(signo, now) {
+            Some((bucket, _)) if bucket.tokens == 0 => false,
+            Some((bucket, _)) => {
+                bucket.tokens -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Returns when `signo` gets its next token.
+    fn next_token(&mut self, signo: i32, now: Instant) -> Instant {
+        match self.bucket(signo, now) {
+            Some((bucket, limit)) => bucket.refilled_at + limit.interval,
+            None => now,
+        }
+    }
+
+    /// Returns the first held back signal the limit lets through by now.
+    fn release(&mut self, now: Instant) -> Option<SignalInfo> {
+        for i in 0..self.pending.len() {
+            let signo = self.pending[i].signo();
+            if self
+                .pending
+                .iter()
+                .take(i)
+                .any(|info| info.signo() == signo)
+            {
+                // Instances of a signal leave in order.
+                continue;
+            }
+            if self.take(signo, now) {
+                return self.pending.remove(i);
+            }
+        }
+        None
+    }
+
+    /// Lets `info` through, or holds it back or drops it as configured.
+    fn admit(&mut self, info: SignalInfo, now: Instant) -> Option<SignalInfo> {
+        let signo = info.signo();
+        let queued = self.pending.iter().position(|held| held.signo() == signo);
+        if queued.is_none() && self.take(signo, now) {
+            return Some(info);
+        }
+        match (self.excess, queued) {
+            (Excess::Drop, _) => self.dropped += 1,
+            (Excess::Coalesce, Some(i)) => self.pending[i] = info,
+            (Excess::Coalesce, None) | (Excess::Delay, _) => self.pending.push_back(info),
+        }
+        None
+    }
+}
+
+impl<S: SignalSource> SignalSource for RateLimited<S> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        loop {
+            let now = Instant::now();
+            if let Some(info) = self.release(now) {
+                return Poll::Ready(Ok(info));
+            }
+            while let Poll::Ready(result) = self.source.poll_recv(cx) {
+                match result {
+                    Ok(info) => {
+                        if let Some(info) = self.admit(info, now) {
+                            return Poll::Ready(Ok(info));
+                        }
+                    }
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+            }
+            let signals: Vec<i32> = self.pending.iter().map(SignalInfo::signo).collect();
+            let wake = match signals
+                .into_iter()
+                .map(|signo| self.next_token(signo, now))
+                .min()
+            {
+                Some(wake) => wake,
+                None => {
+                    self.timer = None;
+                    return Poll::Pending;
+                }
+            };
+            let timer = match &mut self.timer {
+                Some(timer) => {
+                    timer.as_mut().reset(wake.into());
+                    timer
+                }
+                None => self
+                    .timer
+                    .insert(Box::pin(tokio1::time::sleep_until(wake.into()))),
+            };
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Never ends, only the source can fail.
+impl<S: SignalSource + Unpin> futures_core::Stream for RateLimited<S> {
+    type Item = Result<SignalInfo>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSignalSource;
+    use std::future::poll_fn;
+    use tokio1::runtime::Builder;
+
+    fn is_pending<S: SignalSource>(source: &mut S) -> bool {
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        source.poll_recv(&mut cx).is_pending()
+    }
+
+    #[test]
+    fn coalesces_excess() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        rt.block_on(async {
+            let mock = MockSignalSource::new();
+            let sender = mock.sender();
+            let period = Duration::from_millis(50);
+            let mut limited = RateLimited::new(mock, RateLimit::per(1, period), Excess::Coalesce);
+            for _ in 0..3 {
+                sender.send(libc::SIGHUP);
+            }
+            sender.send(libc::SIGTERM);
+
+            let start = Instant::now();
+            assert_eq!(limited.recv().await.unwrap().signo(), libc::SIGHUP);
+            assert_eq!(limited.recv().await.unwrap().signo(), libc::SIGTERM);
+            assert_eq!(limited.pending(), 1);
+            assert_eq!(limited.recv().await.unwrap().signo(), libc::SIGHUP);
+            assert!(start.elapsed() >= period);
+            assert!(is_pending(&mut limited));
+        });
+    }
+
+    #[test]
+    fn drops_excess() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        rt.block_on(async {
+            let mock = MockSignalSource::new();
+            let sender = mock.sender();
+            let mut limited = RateLimited::unlimited(mock, Excess::Drop)
+                .limit(libc::SIGUSR1, RateLimit::per(2, Duration::from_secs(60)));
+            for _ in 0..5 {
+                sender.send(libc::SIGUSR1);
+            }
+            sender.send(libc::SIGUSR2);
+            let mut received = Vec::new();
+            while let Poll::Ready(info) = poll_fn(|cx| Poll::Ready(limited.poll_recv(cx))).await {
+                received.push(info.unwrap().signo());
+            }
+            assert_eq!(received, [libc::SIGUSR1, libc::SIGUSR1, libc::SIGUSR2]);
+            assert_eq!(limited.dropped(), 3);
+        });
+    }
+}
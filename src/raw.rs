@@ -0,0 +1,1009 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{self, Result};
+use std::mem;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+use crate::builder::{Builder, ExistingHandlers, Filter, MaskStrategy, Options, Overflow};
+use crate::error::SignalFdError;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::inherit;
+use crate::restore;
+use crate::siginfo::{Received, SignalInfo, SIGINFO_SIZE};
+use crate::signal::InvalidSignal;
+use crate::sigset::SigSet;
+use crate::sys::{self, Backend};
+
+/// Maximum number of records consumed by a single `read(2)`.
+pub(crate) const BATCH: usize = 16;
+
+/// Applies `set` to the calling thread's signal mask, returning the previous
+/// mask.
+fn sigmask(how: libc::c_int, set: &SigSet) -> Result<SigSet> {
+    let mut old = SigSet::empty();
+    let rv = unsafe { libc::pthread_sigmask(how, set.as_raw(), old.as_raw_mut()) };
+    if rv != 0 {
+        let err = io::Error::from_raw_os_error(rv);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(how, ?set, error = %err, "failed to change signal mask");
+        return Err(SignalFdError::Mask(err).into());
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(how, ?set, previous = ?old, "changed signal mask");
+    Ok(old)
+}
+
+/// Clears the close-on-exec and non-blocking flags the engines set, as
+/// configured.
+fn clear_flags(fd: RawFd, backend: Backend, options: &Options) -> Result<()> {
+    if !options.cloexec && unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if !options.nonblocking && backend == Backend::Signalfd {
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Fails if `set` holds `SIGKILL` or `SIGSTOP`, which would never be
+/// received.
+fn check_catchable(set: &SigSet) -> Result<()> {
+    for signal in [libc::SIGKILL, libc::SIGSTOP] {
+        if set.contains(signal) {
+            return Err(InvalidSignal::new(signal).into());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the first signal in `set` with a handler installed.
+fn first_handled(set: &SigSet) -> Result<Option<i32>> {
+    for signal in set.iter().map(i32::from) {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        if unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if action.sa_sigaction != libc::SIG_DFL && action.sa_sigaction != libc::SIG_IGN {
+            return Ok(Some(signal));
+        }
+    }
+    Ok(None)
+}
+
+/// Undoes a signal mask change when the descriptor is dropped.
+struct Restore {
+    thread: libc::pthread_t,
+    how: libc::c_int,
+    set: SigSet,
+}
+
+/// The descriptor underneath the stream types, together with the signal mask
+/// change made for it.
+///
+/// It is reachable through the streams' `get_ref`, `get_mut` and
+/// `into_inner`, e.g. to change its reactor registration, and can be created
+/// on its own for runtimes none of them fit: the descriptor is non-blocking,
+/// so wait for it to become readable through [`AsRawFd`], then call
+/// [`read_one`](RawSignalFd::read_one) or
+/// [`read_many`](RawSignalFd::read_many) until they come up empty.
+///
+/// Reading it with `io::Read` yields raw `signalfd_siginfo` records instead,
+/// bypassing any filters. Dropping it closes the descriptor and restores the
+/// signal mask as configured with
+/// [`Builder::restore_mask`](crate::Builder::restore_mask).
+pub struct RawSignalFd {
+    /// `None` only once given up by `into_fd`.
+    fd: Option<OwnedFd>,
+    backend: Backend,
+    set: SigSet,
+    previous_mask: Option<SigSet>,
+    restore: Option<Restore>,
+    filters: Vec<Filter>,
+    /// Set while paused, to whether the signals were unblocked.
+    paused: Option<bool>,
+    queue: VecDeque<SignalInfo>,
+    /// Room for a batch of records, reused by every read so that it is only
+    /// zeroed once; empty until the first read.
+    buf: Vec<u8>,
+    /// An error that came along with signals, left for the next read to
+    /// report once those were returned.
+    error: Option<io::Error>,
+    /// Signals returned ahead of buffered ones, from the most urgent.
+    priority: Vec<i32>,
+    /// The bound of `queue`, if any, and what to do when it is reached.
+    capacity: Option<usize>,
+    overflow: Overflow,
+    /// The restore generation the descriptor was opened in.
+    generation: u64,
+}
+
+impl RawSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        RawSignalFd::from_set(&SigSet::from_signals(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        RawSignalFd::open(set, Options::default())
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(Ok)
+    }
+
+    pub(crate) fn open(set: &SigSet, options: Options) -> Result<Self> {
+        check_catchable(set)?;
+        let backend = match options.existing_handlers {
+            ExistingHandlers::Ignore => options.backend,
+            ExistingHandlers::Error => match first_handled(set)? {
+                Some(signal) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("signal {} already has a handler installed", signal),
+                    ))
+                }
+                None => options.backend,
+            },
+            #[cfg(feature = "signal-hook")]
+            ExistingHandlers::Bridge => match first_handled(set)? {
+                Some(_) => Some(Backend::SignalHook),
+                None => options.backend,
+            },
+        };
+        // Handlers chained through signal-hook only run while the signals
+        // are unblocked.
+        let mask = match backend {
+            Some(Backend::SignalHook) => MaskStrategy::Keep,
+            _ => options.mask,
+        };
+        let previous_mask = match mask {
+            MaskStrategy::Block => Some(sigmask(libc::SIG_BLOCK, set)?),
+            MaskStrategy::SetMask => Some(sigmask(libc::SIG_SETMASK, set)?),
+            MaskStrategy::Keep => None,
+        };
+        let (fd, backend) = match sys::open(set, backend) {
+            Ok(opened) => opened,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(signals = ?set, error = %err, "failed to open signal descriptor");
+                if let Some(previous_mask) = previous_mask {
+                    let _ = sigmask(libc::SIG_SETMASK, &previous_mask);
+                }
+                return Err(SignalFdError::Create(err).into());
+            }
+        };
+        if let Err(err) = clear_flags(fd.as_raw_fd(), backend, &options) {
+            sys::release(backend, fd.as_raw_fd());
+            if let Some(previous_mask) = previous_mask {
+                let _ = sigmask(libc::SIG_SETMASK, &previous_mask);
+            }
+            return Err(SignalFdError::Create(err).into());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            fd = fd.as_raw_fd(),
+            ?backend,
+            signals = ?set,
+            "opened signal descriptor"
+        );
+        let restore = match previous_mask {
+            Some(previous_mask) if options.restore_mask => {
+                let (how, set) = match mask {
+                    MaskStrategy::SetMask => (libc::SIG_SETMASK, previous_mask),
+                    _ => (libc::SIG_UNBLOCK, difference(set, &previous_mask)),
+                };
+                Some(Restore {
+                    thread: unsafe { libc::pthread_self() },
+                    how,
+                    set,
+                })
+            }
+            _ => None,
+        };
+        Ok(RawSignalFd {
+            fd: Some(fd),
+            backend,
+            set: *set,
+            previous_mask,
+            restore,
+            filters: options.filters,
+            paused: None,
+            queue: VecDeque::new(),
+            buf: Vec::new(),
+            error: None,
+            priority: options.priority,
+            capacity: options.capacity,
+            overflow: options.overflow,
+            generation: restore::generation(),
+        })
+    }
+
+    /// Adopts an existing signalfd whose signal set is unknown.
+    pub(crate) fn from_fd(fd: OwnedFd) -> Self {
+        RawSignalFd {
+            fd: Some(fd),
+            backend: Backend::native().unwrap_or(Backend::SigwaitThread),
+            set: SigSet::empty(),
+            previous_mask: None,
+            restore: None,
+            filters: Vec::new(),
+            paused: None,
+            queue: VecDeque::new(),
+            buf: Vec::new(),
+            error: None,
+            priority: Vec::new(),
+            capacity: None,
+            overflow: Overflow::default(),
+            generation: restore::generation(),
+        }
+    }
+
+    /// Takes over a signalfd created by another process, such as a supervisor
+    /// or the process this one replaces, along with the signals queued on it.
+    ///
+    /// The signals it receives are recovered from the kernel and blocked on
+    /// the calling thread, and the descriptor is made non-blocking and
+    /// close-on-exec. Fails with `InvalidInput` if `fd` isn't a signalfd, and
+    /// with `Unsupported` where there is no signalfd.
+    pub fn adopt(fd: OwnedFd) -> Result<Self> {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            drop(fd);
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "signalfd isn't available on this platform",
+            ))
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let raw = fd.as_raw_fd();
+            let set = inherit::signal_mask(raw)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("descriptor {} isn't a signalfd", raw),
+                )
+            })?;
+            let flags = unsafe { libc::fcntl(raw, libc::F_GETFL) };
+            if flags < 0
+                || unsafe { libc::fcntl(raw, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0
+                || unsafe { libc::fcntl(raw, libc::F_SETFD, libc::FD_CLOEXEC) } < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            let previous_mask = sigmask(libc::SIG_BLOCK, &set)?;
+            Ok(RawSignalFd {
+                fd: Some(fd),
+                backend: Backend::Signalfd,
+                set,
+                previous_mask: Some(previous_mask),
+                restore: Some(Restore {
+                    thread: unsafe { libc::pthread_self() },
+                    how: libc::SIG_UNBLOCK,
+                    set: difference(&set, &previous_mask),
+                }),
+                filters: Vec::new(),
+                paused: None,
+                queue: VecDeque::new(),
+                buf: Vec::new(),
+                error: None,
+                priority: Vec::new(),
+                capacity: None,
+                overflow: Overflow::default(),
+                generation: restore::generation(),
+            })
+        }
+    }
+
+    /// Adopts the first signalfd passed to this process with systemd's
+    /// `LISTEN_FDS` protocol, e.g. from its file descriptor store, as
+    /// [`adopt`](RawSignalFd::adopt) does.
+    ///
+    /// With `name` given, only descriptors of that name in `LISTEN_FDNAMES`
+    /// are considered. The passed descriptor is duplicated rather than taken
+    /// over, so that other code can still look for it. Returns `None` if no
+    /// signalfd was passed, always so where there is no signalfd.
+    pub fn from_listen_fds(name: Option<&str>) -> Result<Option<Self>> {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = name;
+            Ok(None)
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        match inherit::listen_fd(name)? {
+            Some(fd) => RawSignalFd::adopt(fd).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Gives up the descriptor, restoring the signal mask as on drop only if
+    /// `restore_mask` is set.
+    ///
+    /// The sigwait thread and signal-hook engines stop writing to it, and
+    /// buffered records are dropped.
+    pub(crate) fn into_fd(mut self, restore_mask: bool) -> OwnedFd {
+        if restore_mask {
+            self.restore_mask();
+        }
+        sys::release(self.backend, self.as_raw_fd());
+        self.fd.take().expect("descriptor given up already")
+    }
+
+    fn fd(&self) -> &OwnedFd {
+        self.fd.as_ref().expect("descriptor given up already")
+    }
+
+    /// Duplicates the descriptor, for a second reader of the same signals.
+    ///
+    /// The duplicate has the same filters but no buffered records, and
+    /// leaves restoring the mask to `self`. Only signalfd and kqueue
+    /// descriptors can be duplicated.
+    pub(crate) fn try_clone(&self) -> Result<Self> {
+        if !matches!(self.backend, Backend::Signalfd | Backend::Kqueue) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "descriptors of the {:?} backend can't be duplicated",
+                    self.backend
+                ),
+            ));
+        }
+        Ok(RawSignalFd {
+            fd: Some(self.fd().try_clone()?),
+            backend: self.backend,
+            set: self.set,
+            previous_mask: self.previous_mask,
+            restore: None,
+            filters: self.filters.clone(),
+            paused: self.paused,
+            queue: VecDeque::new(),
+            buf: Vec::new(),
+            error: None,
+            priority: self.priority.clone(),
+            capacity: self.capacity,
+            overflow: self.overflow,
+            generation: restore::generation(),
+        })
+    }
+
+    /// Replaces the descriptor with a new one receiving the same signals, in
+    /// a child process after `fork`, blocking the signals on the calling
+    /// thread again first if `block` is set.
+    ///
+    /// The sigwait thread doesn't survive the fork and the signal-hook
+    /// handlers would write to a pipe shared with the parent, so every
+    /// backend gets a fresh descriptor. Records buffered before the fork
+    /// stay with the parent.
+    pub(crate) fn reopen_with(&mut self, block: bool) -> Result<()> {
+        if block && self.backend != Backend::SignalHook {
+            sigmask(libc::SIG_BLOCK, &self.set)?;
+            if let Some(restore) = &mut self.restore {
+                restore.thread = unsafe { libc::pthread_self() };
+            }
+        }
+        let set = match self.paused {
+            Some(_) => SigSet::empty(),
+            None => self.set,
+        };
+        let (fd, backend) = sys::open(&set, Some(self.backend)).map_err(SignalFdError::Create)?;
+        let old = self.as_raw_fd();
+        let options = Options {
+            cloexec: unsafe { libc::fcntl(old, libc::F_GETFD) } & libc::FD_CLOEXEC != 0,
+            nonblocking: unsafe { libc::fcntl(old, libc::F_GETFL) } & libc::O_NONBLOCK != 0,
+            ..Options::default()
+        };
+        if let Err(err) = clear_flags(fd.as_raw_fd(), backend, &options) {
+            sys::release(backend, fd.as_raw_fd());
+            return Err(SignalFdError::Create(err).into());
+        }
+        // The old descriptor is closed only now, so that the new one gets
+        // another number. A reactor inherited from the parent then has
+        // nothing registered under it, and deregistering doesn't take the
+        // parent's registration away.
+        sys::release(self.backend, old);
+        self.fd = Some(fd);
+        self.backend = backend;
+        self.queue.clear();
+        self.generation = restore::generation();
+        Ok(())
+    }
+
+    /// Replaces the descriptor with a new one receiving the same signals,
+    /// with the same filters and flags.
+    ///
+    /// The new descriptor has another number, so it has to be registered
+    /// wherever the old one was. Signals read but not yet returned are
+    /// dropped. See [`notify_restored`](crate::restore::notify_restored).
+    pub fn reopen(&mut self) -> Result<()> {
+        self.reopen_with(false)
+    }
+
+    /// Returns `true` if a restore from a checkpoint was notified since the
+    /// descriptor was opened, see
+    /// [`notify_restored`](crate::restore::notify_restored).
+    pub fn needs_reopen(&self) -> bool {
+        self.generation != restore::generation()
+    }
+
+    fn restore_mask(&self) {
+        if let Some(restore) = &self.restore {
+            // Signal masks are per thread, only the creating thread's mask is
+            // ours to restore.
+            if unsafe { libc::pthread_equal(restore.thread, libc::pthread_self()) } != 0 {
+                let _ = sigmask(restore.how, &restore.set);
+            }
+        }
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        &self.set
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.previous_mask.as_ref()
+    }
+
+    /// Blocks `signal` and starts receiving it on the existing descriptor.
+    pub fn add(&mut self, signal: i32) -> Result<()> {
+        let added = SigSet::from_signals(&[signal])?;
+        check_catchable(&added)?;
+        let mut set = self.set;
+        set.add(signal)?;
+        if self.backend == Backend::SignalHook {
+            return self.update(set);
+        }
+        let old = sigmask(libc::SIG_BLOCK, &added)?;
+        if let Some(restore) = &mut self.restore {
+            if restore.how == libc::SIG_UNBLOCK && !old.contains(signal) {
+                restore.set.add(signal)?;
+            }
+        }
+        self.update(set)
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    pub fn remove(&mut self, signal: i32) -> Result<()> {
+        let removed = SigSet::from_signals(&[signal])?;
+        let mut set = self.set;
+        set.remove(signal)?;
+        self.update(set)?;
+        if self.backend == Backend::SignalHook {
+            return Ok(());
+        }
+        sigmask(libc::SIG_UNBLOCK, &removed)?;
+        if let Some(restore) = &mut self.restore {
+            if restore.how == libc::SIG_UNBLOCK {
+                restore.set.remove(signal)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, set: SigSet) -> Result<()> {
+        // While paused the descriptor receives nothing, `resume` applies the
+        // new set.
+        if self.paused.is_none() {
+            sys::update(self.backend, self.as_raw_fd(), &self.set, &set)?;
+        }
+        self.set = set;
+        Ok(())
+    }
+
+    /// Stops receiving the signals, unblocking them on the calling thread
+    /// too if `unblock` is set.
+    pub(crate) fn pause(&mut self, unblock: bool) -> Result<()> {
+        if self.paused.is_some() {
+            return Ok(());
+        }
+        sys::update(self.backend, self.as_raw_fd(), &self.set, &SigSet::empty())?;
+        let unblock = unblock && self.backend != Backend::SignalHook;
+        if unblock {
+            sigmask(libc::SIG_UNBLOCK, &self.set)?;
+        }
+        self.paused = Some(unblock);
+        Ok(())
+    }
+
+    /// Undoes `pause`.
+    pub(crate) fn resume(&mut self) -> Result<()> {
+        let unblocked = match self.paused {
+            Some(unblocked) => unblocked,
+            None => return Ok(()),
+        };
+        if unblocked {
+            sigmask(libc::SIG_BLOCK, &self.set)?;
+        }
+        sys::update(self.backend, self.as_raw_fd(), &SigSet::empty(), &self.set)?;
+        self.paused = None;
+        Ok(())
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Closes the descriptor, returning the signals that were pending on it.
+    ///
+    /// Draining them first keeps them from reaching their regular disposition
+    /// once the mask is restored.
+    pub(crate) fn close(mut self) -> Result<Vec<SignalInfo>> {
+        self.drain()
+    }
+
+    /// Returns every signal that can be received without waiting.
+    pub(crate) fn drain(&mut self) -> Result<Vec<SignalInfo>> {
+        let mut pending = Vec::new();
+        while let Some(info) = self.read_one()? {
+            pending.push(info);
+        }
+        Ok(pending)
+    }
+
+    /// Returns a record buffered by a previous read, if any.
+    ///
+    /// With a priority order, whatever else is pending is read first, so
+    /// that an urgent signal isn't held up by less urgent ones already
+    /// buffered. Errors reading are left for the next read to report.
+    pub(crate) fn pop(&mut self) -> Option<SignalInfo> {
+        if self.priority.is_empty() {
+            return self.queue.pop_front();
+        }
+        if !self.queue.is_empty() {
+            let _ = self.read_pending();
+        }
+        let rank = |info: &SignalInfo| {
+            let signo = info.signo();
+            let rank = self.priority.iter().position(|&urgent| urgent == signo);
+            rank.unwrap_or(self.priority.len())
+        };
+        // The earliest of the most urgent ones.
+        let (next, _) = self
+            .queue
+            .iter()
+            .enumerate()
+            .min_by_key(|&(i, info)| (rank(info), i))?;
+        self.queue.remove(next)
+    }
+
+    /// Keeps `err` for the next read to report, as signals received before
+    /// it are returned first.
+    pub(crate) fn defer_error(&mut self, err: io::Error) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Fails with the error kept by [`defer_error`](Self::defer_error), if
+    /// any.
+    pub(crate) fn take_error(&mut self) -> Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Returns the next record, reading batches of them from the descriptor
+    /// when none are buffered.
+    pub(crate) fn read_info(&mut self) -> Result<SignalInfo> {
+        self.take_error()?;
+        loop {
+            if let Some(info) = self.pop() {
+                return Ok(info);
+            }
+            self.read_batch()?;
+        }
+    }
+
+    /// Reads a batch of records from the descriptor into the queue.
+    fn read_batch(&mut self) -> Result<()> {
+        // Moved out for the duration of the read, which borrows `self`.
+        let mut buf = mem::take(&mut self.buf);
+        if buf.is_empty() {
+            buf = vec![0; SIGINFO_SIZE * BATCH];
+        }
+        let result =
+            io::Read::read(self, &mut buf).and_then(|count| self.push_records(&buf[..count]));
+        self.buf = buf;
+        result
+    }
+
+    /// Buffers every record that can be read without waiting, whether or not
+    /// the descriptor is blocking.
+    fn read_pending(&mut self) -> Result<()> {
+        let mut ready = libc::pollfd {
+            fd: self.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while unsafe { libc::poll(&mut ready, 1, 0) } > 0 && ready.revents & libc::POLLIN != 0 {
+            self.read_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    ///
+    /// Signals are read from the descriptor in batches, the ones not
+    /// returned yet stay buffered. The descriptor is only read when it is
+    /// readable, so this doesn't wait even if it was adopted in blocking
+    /// mode.
+    pub fn read_one(&mut self) -> Result<Option<SignalInfo>> {
+        self.take_error()?;
+        if let Some(info) = self.pop() {
+            return Ok(Some(info));
+        }
+        match self.read_pending() {
+            Ok(()) => Ok(self.pop()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(self.pop()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns up to `max` pending signals, without waiting.
+    pub fn read_many(&mut self, max: usize) -> Result<Vec<SignalInfo>> {
+        let mut signals = Vec::new();
+        while signals.len() < max {
+            match self.read_one()? {
+                Some(info) => signals.push(info),
+                None => break,
+            }
+        }
+        Ok(signals)
+    }
+
+    /// Reads every record pending on the descriptor into the buffer, until
+    /// the read fails with `WouldBlock`.
+    ///
+    /// Other errors are kept for the next read to report, and reading goes on
+    /// past them; only a second one is returned instead, so that a descriptor
+    /// that keeps failing isn't read in a loop.
+    #[cfg_attr(not(any(feature = "tokio-1", feature = "async-io")), allow(dead_code))]
+    pub(crate) fn fill(&mut self) -> Result<()> {
+        loop {
+            match self.read_batch() {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock || self.error.is_some() => {
+                    return Err(err)
+                }
+                Err(err) => self.defer_error(err),
+            }
+        }
+    }
+
+    /// Queues the records in `buf`, as read from the descriptor, that pass
+    /// the filters and fit.
+    ///
+    /// Fails if `buf` is empty or ends with a partial record; the complete
+    /// records stay queued, so the error doesn't lose them. Also fails if
+    /// records were discarded with [`Overflow::Error`].
+    pub(crate) fn push_records(&mut self, buf: &[u8]) -> Result<()> {
+        let records = buf.chunks_exact(SIGINFO_SIZE);
+        let partial = records.remainder().len();
+        let received = Received::now();
+        let mut dropped = 0;
+        for record in records {
+            let record = record.try_into().expect("chunk of SIGINFO_SIZE bytes");
+            let info = SignalInfo::from_bytes(record).received(received);
+            let accepted = self.filters.iter().all(|filter| filter(&info));
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                fd = self.as_raw_fd(),
+                signo = info.signo(),
+                code = info.code(),
+                pid = info.pid(),
+                uid = info.uid(),
+                status = info.status(),
+                value = info.value(),
+                filtered = !accepted,
+                "received signal"
+            );
+            #[cfg(feature = "metrics")]
+            metrics::counter!(
+                "signalfd_signals_received_total",
+                "signal" => crate::Signal::from_raw(info.signo()).label()
+            )
+            .increment(1);
+            if !accepted {
+                continue;
+            }
+            match self.capacity {
+                Some(capacity) if self.queue.len() >= capacity => match self.overflow {
+                    Overflow::DropOldest => {
+                        self.queue.pop_front();
+                        self.queue.push_back(info);
+                    }
+                    Overflow::DropNewest => {}
+                    Overflow::Error => dropped += 1,
+                },
+                _ => self.queue.push_back(info),
+            }
+        }
+        if partial != 0 || buf.is_empty() {
+            let err = io::Error::from(SignalFdError::ShortRead {
+                trailing: partial,
+                size: SIGINFO_SIZE,
+            });
+            #[cfg(feature = "tracing")]
+            tracing::warn!(fd = self.as_raw_fd(), error = %err, "failed to read signals");
+            return Err(err);
+        }
+        if dropped != 0 {
+            return Err(SignalFdError::Overflow { dropped }.into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the signals in `a` that aren't in `b`.
+fn difference(a: &SigSet, b: &SigSet) -> SigSet {
+    let mut set = SigSet::empty();
+    for signal in a.iter().filter(|&signal| !b.contains(signal)) {
+        let _ = set.add(signal);
+    }
+    set
+}
+
+impl Drop for RawSignalFd {
+    fn drop(&mut self) {
+        // Given up by `into_fd`, which took care of the mask.
+        if self.fd.is_none() {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(fd = self.as_raw_fd(), "closing signal descriptor");
+        sys::release(self.backend, self.as_raw_fd());
+        self.restore_mask();
+    }
+}
+
+impl AsRawFd for RawSignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd().as_raw_fd()
+    }
+}
+
+impl AsFd for RawSignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd().as_fd()
+    }
+}
+
+impl io::Read for RawSignalFd {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let result = sys::read(self.backend, self.as_raw_fd(), buf);
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &result {
+            if err.kind() != io::ErrorKind::WouldBlock && err.kind() != io::ErrorKind::Interrupted {
+                tracing::warn!(fd = self.as_raw_fd(), error = %err, "failed to read signals");
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batched_read() {
+        let signals = [libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2];
+        let mut inner = RawSignalFd::new(&signals).unwrap();
+        for &signal in &signals {
+            unsafe { libc::raise(signal) };
+        }
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGHUP);
+        assert_eq!(inner.queue.len(), 2);
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR1);
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR2);
+        let err = inner.read_info().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn read_many() {
+        let signals = [libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2];
+        let mut inner = RawSignalFd::new(&signals).unwrap();
+        assert!(inner.read_one().unwrap().is_none());
+        for &signal in &signals {
+            unsafe { libc::raise(signal) };
+        }
+        let read: Vec<_> = inner
+            .read_many(2)
+            .unwrap()
+            .iter()
+            .map(SignalInfo::signo)
+            .collect();
+        assert_eq!(read, [libc::SIGHUP, libc::SIGUSR1]);
+        assert_eq!(inner.read_many(2).unwrap().len(), 1);
+        assert!(inner.read_many(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn priority_order() {
+        let set =
+            SigSet::from_signals(&[libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2, libc::SIGTERM])
+                .unwrap();
+        let options = Options {
+            priority: vec![libc::SIGTERM, libc::SIGHUP],
+            ..Options::default()
+        };
+        let mut inner = RawSignalFd::open(&set, options).unwrap();
+        for signal in [libc::SIGUSR1, libc::SIGHUP, libc::SIGTERM] {
+            unsafe { libc::raise(signal) };
+        }
+        let read: Vec<_> = inner
+            .read_many(3)
+            .unwrap()
+            .iter()
+            .map(SignalInfo::signo)
+            .collect();
+        assert_eq!(read, [libc::SIGTERM, libc::SIGHUP, libc::SIGUSR1]);
+
+        // Signals arriving later still overtake the buffered ones.
+        unsafe { libc::raise(libc::SIGUSR1) };
+        unsafe { libc::raise(libc::SIGUSR2) };
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR1);
+        unsafe { libc::raise(libc::SIGTERM) };
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGTERM);
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR2);
+    }
+
+    #[test]
+    fn bounded_queue() {
+        let signals = [libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2];
+        let set = SigSet::from_signals(&signals).unwrap();
+        let read = |overflow| {
+            let options = Options {
+                capacity: Some(2),
+                overflow,
+                ..Options::default()
+            };
+            let mut inner = RawSignalFd::open(&set, options).unwrap();
+            for &signal in &signals {
+                unsafe { libc::raise(signal) };
+            }
+            let first = inner.read_info();
+            let rest: Vec<_> = inner
+                .drain()
+                .unwrap()
+                .iter()
+                .map(SignalInfo::signo)
+                .collect();
+            (first, rest)
+        };
+
+        let (first, rest) = read(Overflow::DropOldest);
+        assert_eq!(first.unwrap().signo(), libc::SIGUSR1);
+        assert_eq!(rest, [libc::SIGUSR2]);
+
+        let (first, rest) = read(Overflow::Error);
+        match SignalFdError::from_io(&first.unwrap_err()) {
+            Some(SignalFdError::Overflow { dropped }) => assert_eq!(*dropped, 1),
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(rest, [libc::SIGHUP, libc::SIGUSR1]);
+    }
+
+    #[test]
+    fn rejects_uncatchable() {
+        let invalid = |err: io::Error| {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            match SignalFdError::from_io(&err) {
+                Some(SignalFdError::InvalidSignal(invalid)) => *invalid,
+                _ => panic!("unexpected error {:?}", err),
+            }
+        };
+        let err = RawSignalFd::new(&[libc::SIGTERM, libc::SIGKILL])
+            .err()
+            .unwrap();
+        assert!(invalid(err).is_uncatchable());
+        assert_eq!(
+            invalid(RawSignalFd::new(&[999]).err().unwrap()).signo(),
+            999
+        );
+
+        let mut inner = RawSignalFd::new(&[libc::SIGUSR1]).unwrap();
+        let err = inner.add(libc::SIGSTOP).unwrap_err();
+        assert_eq!(err.to_string(), "SIGSTOP can't be caught or blocked");
+        assert!(!inner.signals().contains(libc::SIGSTOP));
+    }
+
+    #[test]
+    fn filtered_read() {
+        let set = SigSet::from_signals(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+        let mut options = Options::default();
+        options
+            .filters
+            .push(std::sync::Arc::new(|info| info.signo() != libc::SIGUSR1));
+        let mut inner = RawSignalFd::open(&set, options).unwrap();
+        unsafe { libc::raise(libc::SIGUSR1) };
+        let err = inner.read_info().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        unsafe { libc::raise(libc::SIGUSR1) };
+        unsafe { libc::raise(libc::SIGUSR2) };
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR2);
+    }
+
+    #[test]
+    fn existing_handlers() {
+        extern "C" fn handler(_: libc::c_int) {}
+
+        let set = SigSet::from_signals(&[libc::SIGXFSZ]).unwrap();
+        let options = || Options {
+            existing_handlers: ExistingHandlers::Error,
+            ..Options::default()
+        };
+        drop(RawSignalFd::open(&set, options()).unwrap());
+        unsafe {
+            libc::signal(
+                libc::SIGXFSZ,
+                handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        };
+        let err = RawSignalFd::open(&set, options()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        #[cfg(feature = "signal-hook")]
+        {
+            let options = Options {
+                existing_handlers: ExistingHandlers::Bridge,
+                ..Options::default()
+            };
+            let mut inner = RawSignalFd::open(&set, options).unwrap();
+            assert_eq!(inner.backend(), Backend::SignalHook);
+            assert!(inner.previous_mask().is_none());
+            unsafe { libc::raise(libc::SIGXFSZ) };
+            assert_eq!(inner.read_info().unwrap().signo(), libc::SIGXFSZ);
+        }
+    }
+
+    #[test]
+    #[cfg(not(kqueue))]
+    fn short_read() {
+        let mut fds = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK) },
+            0
+        );
+        let fd = unsafe { std::os::unix::io::FromRawFd::from_raw_fd(fds[0]) };
+        let mut inner = RawSignalFd::from_fd(fd);
+        let mut record = [0u8; SIGINFO_SIZE + 8];
+        record[0] = libc::SIGHUP as u8;
+        let written = unsafe { libc::write(fds[1], record.as_ptr() as *const _, record.len()) };
+        assert_eq!(written as usize, record.len());
+        unsafe { libc::close(fds[1]) };
+
+        let err = inner.read_info().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGHUP);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn drain_blocking_fd() {
+        let set = SigSet::from_signals(&[libc::SIGUSR2]).unwrap();
+        let previous = sigmask(libc::SIG_BLOCK, &set).unwrap();
+        let fd = unsafe { libc::signalfd(-1, set.as_raw(), libc::SFD_CLOEXEC) };
+        assert!(fd >= 0);
+        let fd = unsafe { std::os::unix::io::FromRawFd::from_raw_fd(fd) };
+        let mut inner = RawSignalFd::from_fd(fd);
+        crate::signal::send_to_pthread(unsafe { libc::pthread_self() }, libc::SIGUSR2).unwrap();
+
+        let pending = inner.drain().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].signo(), libc::SIGUSR2);
+        assert!(inner.read_one().unwrap().is_none());
+        assert!(inner.close().unwrap().is_empty());
+        sigmask(libc::SIG_SETMASK, &previous).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+//! Reaping of exited child processes.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Result};
+use std::os::unix::process::ExitStatusExt;
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Waker};
+
+use futures_core::Stream;
+
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+use crate::pidfd::PidFd;
+
+/// A stream of `(pid, status)` pairs for child processes that have exited.
+///
+/// It wraps a stream of `SIGCHLD` notifications, typically a `SignalFd`
+/// watching just `SIGCHLD`. Since multiple `SIGCHLD`s arriving close together
+/// are coalesced by the kernel, every notification is followed by a
+/// `waitpid(-1, WNOHANG)` loop that collects all children that have exited
+/// so far. Children that exited before the reaper was first polled are
+/// collected as well.
+///
+/// `SIGCHLD` is sent to the whole process, so it has to be blocked in every
+/// thread for the notifications to arrive reliably; create the `SignalFd`
+/// before spawning any other threads.
+///
+/// The reaper collects *every* child of the process, including ones spawned
+/// by `std::process::Command`, so waiting on those separately will fail. Use
+/// [`watch`](ChildReaper::watch) to get a dedicated completion for a
+/// particular child instead.
+pub struct ChildReaper<S> {
+    signals: S,
+    draining: bool,
+    watched: Arc<Mutex<HashMap<libc::pid_t, Watch>>>,
+}
+
+#[derive(Default)]
+struct Watch {
+    status: Option<ExitStatus>,
+    waker: Option<Waker>,
+}
+
+impl<S> ChildReaper<S> {
+    /// Creates a reaper driven by `signals`.
+    pub fn new(signals: S) -> Self {
+        ChildReaper {
+            signals,
+            draining: true,
+            watched: Arc::default(),
+        }
+    }
+
+    /// Returns a future resolving to the exit status of the child `pid`.
+    ///
+    /// The child is then no longer yielded by the stream. Within a tokio
+    /// runtime on Linux with IO enabled, the future waits on a
+    /// [`PidFd`](crate::PidFd) and resolves on its own. Elsewhere, or if
+    /// the pidfd can't be used, it only makes progress while the reaper
+    /// itself is being polled.
+    ///
+    /// Fails with `AlreadyExists` if `pid` is watched already, and with
+    /// `ECHILD` if it isn't a child or has been collected already.
+    pub fn watch(&self, pid: libc::pid_t) -> Result<ChildExit> {
+        let mut watched = self.watched.lock().unwrap();
+        if watched.contains_key(&pid) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("child {} is watched already", pid),
+            ));
+        }
+        // Whatever the reaper collects from here on is handed over, since
+        // it needs the lock to do so.
+        check_child(pid)?;
+        watched.insert(pid, Watch::default());
+        Ok(ChildExit {
+            pid,
+            watched: self.watched.clone(),
+            #[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+            pidfd: open_pidfd(pid),
+        })
+    }
+
+    /// Returns whether `pid` is watched through [`watch`](ChildReaper::watch).
+    #[cfg(feature = "tokio-1")]
+    pub(crate) fn is_watched(&self, pid: libc::pid_t) -> bool {
+        self.watched.lock().unwrap().contains_key(&pid)
+    }
+
+    /// Hands `pid`'s status to its watcher, returning `false` if there is
+    /// none.
+    fn complete(&self, pid: libc::pid_t, status: ExitStatus) -> bool {
+        let mut watched = self.watched.lock().unwrap();
+        match watched.get_mut(&pid) {
+            Some(watch) => {
+                watch.status = Some(status);
+                if let Some(waker) = watch.waker.take() {
+                    waker.wake();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+}
+
+/// Fails unless `pid` is a child that hasn't been collected yet.
+fn check_child(pid: libc::pid_t) -> Result<()> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    while unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Opens a pidfd for `pid` if there is a tokio runtime with IO enabled.
+#[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+fn open_pidfd(pid: libc::pid_t) -> Option<PidFd> {
+    tokio1::runtime::Handle::try_current().ok()?;
+    // Tokio has no way to ask whether IO is enabled but to try, which
+    // panics without it.
+    std::panic::catch_unwind(|| PidFd::open(pid)).ok()?.ok()
+}
+
+/// Collects one exited child, if there is any.
+pub(crate) fn reap() -> Result<Option<(libc::pid_t, ExitStatus)>> {
+    let mut status = 0;
+    loop {
+        let pid = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
+        match pid {
+            0 => return Ok(None),
+            pid if pid > 0 => {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("signalfd_children_reaped_total").increment(1);
+                return Ok(Some((pid, ExitStatus::from_raw(status))));
+            }
+            _ => {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) => continue,
+                    Some(libc::ECHILD) => return Ok(None),
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+impl<S, T> Stream for ChildReaper<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    type Item = Result<(libc::pid_t, ExitStatus)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.draining {
+                match reap() {
+                    Ok(Some((pid, status))) if this.complete(pid, status) => continue,
+                    Ok(Some(child)) => return Poll::Ready(Some(Ok(child))),
+                    Ok(None) => this.draining = false,
+                    Err(err) => {
+                        this.draining = false;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+            }
+            match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+                Some(Ok(_)) => this.draining = true,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Future returned by [`ChildReaper::watch`].
+pub struct ChildExit {
+    pid: libc::pid_t,
+    watched: Arc<Mutex<HashMap<libc::pid_t, Watch>>>,
+    #[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+    pidfd: Option<PidFd>,
+}
+
+impl ChildExit {
+    /// Returns the process ID of the watched child.
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+}
+
+impl Future for ChildExit {
+    type Output = ExitStatus;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ExitStatus> {
+        let this = self.get_mut();
+        // Held across waiting on the pidfd, so that a status collected by
+        // the reaper meanwhile is handed over only once the waker is set.
+        let mut watched = this.watched.lock().unwrap();
+        let watch = watched.entry(this.pid).or_default();
+        if let Some(status) = watch.status {
+            return Poll::Ready(status);
+        }
+        #[cfg(all(feature = "tokio-1", any(target_os = "linux", target_os = "android")))]
+        if let Some(pidfd) = &mut this.pidfd {
+            match pidfd.poll_wait(cx) {
+                Poll::Ready(Ok(status)) => return Poll::Ready(status),
+                // Collected by the reaper, or a kernel without `P_PIDFD`.
+                Poll::Ready(Err(_)) => this.pidfd = None,
+                Poll::Pending => {}
+            }
+        }
+        watch.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ChildExit {
+    fn drop(&mut self) {
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.remove(&self.pid);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use crate::Signal;
+    use futures_util::StreamExt;
+    use std::process::Command;
+    use tokio1::runtime::Builder;
+
+    /// Waits for `pid` to exit without collecting it.
+    fn wait_exited(pid: u32) {
+        let mut info = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOWAIT;
+        let rv = unsafe { libc::waitid(libc::P_PID, pid, &mut info, flags) };
+        assert_eq!(rv, 0);
+    }
+
+    #[test]
+    fn reaps_children() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[Signal::Chld]).unwrap();
+            let mut reaper = ChildReaper::new(signals);
+
+            let child = Command::new("sh")
+                .args(["-c", "exit 3"])
+                .spawn()
+                .unwrap()
+                .id();
+            wait_exited(child);
+            let (pid, status) = reaper.next().await.unwrap().unwrap();
+            assert_eq!(pid as u32, child);
+            assert_eq!(status.code(), Some(3));
+
+            // The kernel may hand the process-wide SIGCHLD to another test
+            // thread, so deliver one to this thread explicitly.
+            let child = Command::new("true").spawn().unwrap().id();
+            wait_exited(child);
+            unsafe { libc::raise(libc::SIGCHLD) };
+            let (pid, status) = reaper.next().await.unwrap().unwrap();
+            assert_eq!(pid as u32, child);
+            assert!(status.success());
+        });
+    }
+
+    #[test]
+    fn watch_child() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[Signal::Chld]).unwrap();
+            let mut reaper = ChildReaper::new(signals);
+
+            let watched = Command::new("sh")
+                .args(["-c", "exit 7"])
+                .spawn()
+                .unwrap()
+                .id();
+            let other = Command::new("true").spawn().unwrap().id();
+            let exit = reaper.watch(watched as libc::pid_t).unwrap();
+            wait_exited(watched);
+            wait_exited(other);
+
+            let (pid, _) = reaper.next().await.unwrap().unwrap();
+            assert_eq!(pid as u32, other);
+            assert_eq!(exit.await.code(), Some(7));
+        });
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn watch_without_polling() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let reaper = ChildReaper::new(SignalFd::new(&[Signal::Chld]).unwrap());
+            let child = Command::new("sh")
+                .args(["-c", "sleep 0.05; exit 4"])
+                .spawn()
+                .unwrap()
+                .id();
+            let exit = reaper.watch(child as libc::pid_t).unwrap();
+            assert_eq!(exit.await.code(), Some(4));
+        });
+    }
+
+    #[test]
+    fn watch_errors() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let reaper = ChildReaper::new(SignalFd::new(&[Signal::Chld]).unwrap());
+            let mut child = Command::new("true").spawn().unwrap();
+            let pid = child.id() as libc::pid_t;
+            let exit = reaper.watch(pid).unwrap();
+            let err = reaper.watch(pid).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            drop(exit);
+
+            child.wait().unwrap();
+            let err = reaper.watch(pid).err().unwrap();
+            assert_eq!(err.raw_os_error(), Some(libc::ECHILD));
+        });
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn watch_without_io() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().build().unwrap();
+        let mut child = Command::new("true").spawn().unwrap();
+        let reaper = ChildReaper::new(futures_util::stream::pending::<Result<()>>());
+        let _runtime = rt.enter();
+        let exit = reaper.watch(child.id() as libc::pid_t).unwrap();
+        assert!(exit.pidfd.is_none());
+        drop(exit);
+        child.wait().unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+//! Reloading configuration on `SIGHUP`.
+
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+/// A stream running a reload for every `SIGHUP`, yielding the outcome of
+/// each.
+///
+/// It wraps a stream of `SIGHUP` notifications, typically a `SignalFd`
+/// watching just that signal. Reloads never overlap: signals arriving while a
+/// reload is running are coalesced into a single further reload, started once
+/// the running one has finished, so that the last change is always picked up
+/// without queueing a reload per signal.
+///
+/// Each finished reload yields whatever its future resolved to, commonly a
+/// `Result` to report failures with. Errors receiving the signals are yielded
+/// as the outer `Err`. Reloads only make progress while the stream is being
+/// polled.
+pub struct Reloader<S, F, Fut> {
+    signals: S,
+    reload: F,
+    running: Option<Pin<Box<Fut>>>,
+    pending: bool,
+    ended: bool,
+}
+
+impl<S, F, Fut> Reloader<S, F, Fut>
+where
+    F: FnMut() -> Fut,
+    Fut: Future,
+{
+    /// Creates a stream calling `reload` for the signals from `signals`.
+    pub fn new(signals: S, reload: F) -> Self {
+        Reloader {
+            signals,
+            reload,
+            running: None,
+            pending: false,
+            ended: false,
+        }
+    }
+
+    /// Returns `true` while a reload is running.
+    pub fn is_reloading(&self) -> bool {
+        self.running.is_some()
+    }
+}
+
+impl<S, F, Fut, T> Stream for Reloader<S, F, Fut>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    F: FnMut() -> Fut + Unpin,
+    Fut: Future,
+{
+    type Item = Result<Fut::Output>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.ended {
+            match Pin::new(&mut this.signals).poll_next(cx) {
+                Poll::Ready(Some(Ok(_))) if this.running.is_some() => this.pending = true,
+                Poll::Ready(Some(Ok(_))) => this.running = Some(Box::pin((this.reload)())),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => this.ended = true,
+                Poll::Pending => break,
+            }
+        }
+        if let Some(running) = &mut this.running {
+            if let Poll::Ready(outcome) = running.as_mut().poll(cx) {
+                this.running = None;
+                if std::mem::take(&mut this.pending) {
+                    this.running = Some(Box::pin((this.reload)()));
+                    // Make sure the next reload gets polled.
+                    cx.waker().wake_by_ref();
+                }
+                return Poll::Ready(Some(Ok(outcome)));
+            }
+        }
+        if this.ended && this.running.is_none() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use futures_util::{future, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio1::runtime::Builder;
+    use tokio1::time::sleep;
+
+    #[test]
+    fn coalesces_while_reloading() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGHUP]).unwrap();
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counted = calls.clone();
+            let reloads = Reloader::new(signals, move || {
+                let call = counted.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    sleep(Duration::from_millis(100)).await;
+                    if call == 1 {
+                        Ok(call)
+                    } else {
+                        Err("bad config")
+                    }
+                }
+            });
+            let raise = async {
+                for _ in 0..3 {
+                    unsafe { libc::raise(libc::SIGHUP) };
+                    sleep(Duration::from_millis(20)).await;
+                }
+            };
+
+            let (outcomes, ()) = future::join(reloads.take(2).collect::<Vec<_>>(), raise).await;
+            let outcomes: Vec<_> = outcomes
+                .into_iter()
+                .map(|outcome| outcome.unwrap())
+                .collect();
+            assert_eq!(outcomes, [Ok(1), Err("bad config")]);
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+}
@@ -0,0 +1,99 @@
+//! Terminal size changes.
+
+use std::io::{self, Result};
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+
+/// Returns the size of the terminal `fd` refers to, as `(cols, rows)`.
+pub fn window_size(fd: RawFd) -> Result<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((size.ws_col, size.ws_row))
+}
+
+/// A stream of terminal sizes, yielding `(cols, rows)` after every `SIGWINCH`.
+///
+/// It wraps a stream of `SIGWINCH` notifications, typically a `SignalFd`
+/// watching just that signal, and queries the terminal on standard output
+/// for its new size each time. Several resizes in quick succession may be
+/// coalesced into one, but the size yielded is always the current one.
+pub struct ResizeEvents<S> {
+    signals: S,
+    fd: RawFd,
+}
+
+impl<S> ResizeEvents<S> {
+    /// Creates a stream of the sizes of the terminal on standard output.
+    pub fn new(signals: S) -> Self {
+        ResizeEvents {
+            signals,
+            fd: libc::STDOUT_FILENO,
+        }
+    }
+
+    /// Queries the terminal on `fd` instead of standard output.
+    pub fn fd(mut self, fd: RawFd) -> Self {
+        self.fd = fd;
+        self
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+}
+
+impl<S, T> Stream for ResizeEvents<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    type Item = Result<(u16, u16)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+            Some(Ok(_)) => Poll::Ready(Some(window_size(this.fd))),
+            Some(Err(err)) => Poll::Ready(Some(Err(err))),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "tokio-1",
+    any(target_os = "linux", target_os = "android")
+))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use futures_util::StreamExt;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn yields_new_size() {
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+        assert!(master >= 0);
+        let size = libc::winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        assert_eq!(unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) }, 0);
+
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGWINCH]).unwrap();
+            let mut resizes = ResizeEvents::new(signals).fd(master);
+            unsafe { libc::raise(libc::SIGWINCH) };
+            assert_eq!(resizes.next().await.unwrap().unwrap(), (80, 24));
+        });
+        unsafe { libc::close(master) };
+    }
+}
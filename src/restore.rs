@@ -0,0 +1,66 @@
+//! Recreating descriptors after a checkpoint is restored, e.g. with CRIU.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+type Hook = Box<dyn Fn() + Send>;
+
+/// How many restores were notified.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+
+/// Records that the process was restored from a checkpoint, then runs the
+/// hooks added with [`on_restore`].
+///
+/// Not every checkpoint/restore setup brings signalfd descriptors back
+/// intact, so descriptors created before the restore report
+/// `needs_reopen` from then on, until they are reopened with `reopen`.
+/// Call this from whatever learns of the restore first, such as a CRIU
+/// action script signalling the process, or a socket it reconnects to.
+pub fn notify_restored() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    for hook in HOOKS.lock().unwrap().iter() {
+        hook();
+    }
+}
+
+/// Adds `hook`, run by every [`notify_restored`], e.g. to wake the task
+/// owning a stream so that it reopens it.
+///
+/// Hooks run on the thread notifying the restore, and must not call
+/// `notify_restored` or `on_restore` themselves.
+pub fn on_restore<F: Fn() + Send + 'static>(hook: F) {
+    HOOKS.lock().unwrap().push(Box::new(hook));
+}
+
+/// Returns the number of restores notified so far.
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::RawSignalFd;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    #[test]
+    fn reopens_after_restore() {
+        let mut inner = RawSignalFd::new(&[libc::SIGUSR2]).unwrap();
+        assert!(!inner.needs_reopen());
+        let ran = Arc::new(AtomicBool::new(false));
+        let hook_ran = ran.clone();
+        on_restore(move || hook_ran.store(true, Ordering::Release));
+
+        notify_restored();
+        assert!(ran.load(Ordering::Acquire));
+        assert!(inner.needs_reopen());
+        inner.reopen().unwrap();
+        assert!(!inner.needs_reopen());
+
+        unsafe { libc::raise(libc::SIGUSR2) };
+        assert_eq!(inner.read_one().unwrap().unwrap().signo(), libc::SIGUSR2);
+    }
+}
@@ -0,0 +1,152 @@
+//! A shutdown notification shared across a whole program.
+
+use std::future::{pending, poll_fn, Future, IntoFuture};
+use std::io::Result;
+use std::pin::{pin, Pin};
+use std::task::Poll;
+
+use tokio1::sync::watch;
+
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::source::SignalSource;
+use crate::tokio::SignalFd;
+
+/// Triggered by the first of a set of signals, for every part of a program
+/// that has to stop on shutdown.
+///
+/// Tokens are cheap to clone and hand out, and each can be awaited, as many
+/// times as needed, resolving to the signal that triggered it:
+///
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use tokio_signalfd::ShutdownToken;
+///
+/// let shutdown = ShutdownToken::terminating()?;
+/// let worker = tokio1::spawn({
+///     let shutdown = shutdown.clone();
+///     async move {
+///         while !shutdown.is_triggered() {
+///             // Do a unit of work.
+///         }
+///     }
+/// });
+/// let signal = shutdown.await;
+/// println!("shutting down on {}", signal);
+/// # worker.await.unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The signals are received by a task, which keeps them blocked until
+/// every token is dropped: signals after the first one are ignored rather
+/// than killing the process halfway through its shutdown. Should receiving
+/// fail, which doesn't happen in practice, the token never triggers.
+#[derive(Clone, Debug)]
+pub struct ShutdownToken {
+    signal: watch::Receiver<Option<Signal>>,
+}
+
+impl ShutdownToken {
+    /// Blocks `signals` on the calling thread and triggers on the first one
+    /// received.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        Ok(ShutdownToken::from_source(SignalFd::new(signals)?))
+    }
+
+    /// Blocks every signal in `set` on the calling thread and triggers on the
+    /// first one received.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        Ok(ShutdownToken::from_source(SignalFd::from_set(set)?))
+    }
+
+    /// Triggers on the first of the signals asking the process to shut down,
+    /// see [`SigSet::terminating`].
+    pub fn terminating() -> Result<Self> {
+        ShutdownToken::from_set(&SigSet::terminating())
+    }
+
+    /// Triggers on the first signal from `source`, e.g. a
+    /// [`MockSignalSource`](crate::MockSignalSource) in tests.
+    pub fn from_source<R: SignalSource + Send + 'static>(source: R) -> Self {
+        let (sender, signal) = watch::channel(None);
+        tokio1::spawn(trigger(source, sender));
+        ShutdownToken { signal }
+    }
+
+    /// Returns `true` once the token has triggered.
+    pub fn is_triggered(&self) -> bool {
+        self.signal.borrow().is_some()
+    }
+
+    /// Returns the signal the token triggered on, if it has.
+    pub fn signal(&self) -> Option<Signal> {
+        *self.signal.borrow()
+    }
+
+    /// Waits for the token to trigger, returning the signal it triggered on.
+    pub async fn triggered(&self) -> Signal {
+        let mut signal = self.signal.clone();
+        let triggered = match signal.wait_for(Option::is_some).await {
+            Ok(signal) => *signal,
+            Err(_) => None,
+        };
+        match triggered {
+            Some(signal) => signal,
+            None => pending().await,
+        }
+    }
+}
+
+impl IntoFuture for ShutdownToken {
+    type Output = Signal;
+    type IntoFuture = Pin<Box<dyn Future<Output = Signal> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move { self.triggered().await })
+    }
+}
+
+/// Publishes the first signal from `source` on `sender`, then holds on to
+/// the source until every token is dropped.
+async fn trigger<R: SignalSource>(mut source: R, sender: watch::Sender<Option<Signal>>) {
+    let mut closed = pin!(sender.closed());
+    let first = poll_fn(|cx| match closed.as_mut().poll(cx) {
+        Poll::Ready(()) => Poll::Ready(None),
+        Poll::Pending => source.poll_recv(cx).map(Some),
+    });
+    if let Some(Ok(info)) = first.await {
+        sender.send_replace(Some(Signal::from_raw(info.signo())));
+        closed.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSignalSource;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn triggers_once() {
+        let rt = Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let source = MockSignalSource::new();
+            let sender = source.sender();
+            let token = ShutdownToken::from_source(source);
+            let clone = token.clone();
+            assert!(!clone.is_triggered());
+
+            sender.send(Signal::Term);
+            sender.send(Signal::Int);
+            assert_eq!(clone.triggered().await, Signal::Term);
+            assert!(token.is_triggered());
+            assert_eq!(token.signal(), Some(Signal::Term));
+            assert_eq!(token.await, Signal::Term);
+        });
+    }
+}
@@ -0,0 +1,651 @@
+use std::fmt;
+use std::time::{Instant, SystemTime};
+
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+
+/// Mirror of the kernel's `struct signalfd_siginfo`.
+///
+/// Every field is an integer and the padding is spelled out, so records
+/// convert to and from bytes without `unsafe`.
+#[repr(C)]
+#[derive(Clone, Copy, FromBytes, IntoBytes, KnownLayout, Immutable)]
+struct signalfd_siginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    ssi_utime: u64,
+    ssi_stime: u64,
+    ssi_addr: u64,
+    ssi_addr_lsb: u16,
+    _pad2: u16,
+    ssi_syscall: i32,
+    ssi_call_addr: u64,
+    ssi_arch: u32,
+    _pad: [u8; 28],
+}
+
+pub(crate) const SIGINFO_SIZE: usize = std::mem::size_of::<signalfd_siginfo>();
+
+/// Information about a received signal, as reported by the kernel.
+///
+/// Which fields are meaningful depends on the signal and on how it was sent;
+/// see `signalfd(2)` and `sigaction(2)` for details. On macOS and the BSDs,
+/// where signals are received through kqueue, only the signal number is
+/// available and every other field is zero.
+///
+/// Records read from a descriptor also carry the time they were read at, see
+/// [`received_at`](SignalInfo::received_at).
+#[derive(Clone, Copy)]
+pub struct SignalInfo(signalfd_siginfo, Option<Received>);
+
+/// When a record was read, on both clocks.
+#[derive(Clone, Copy)]
+pub(crate) struct Received {
+    instant: Instant,
+    wall: SystemTime,
+}
+
+impl Received {
+    pub(crate) fn now() -> Self {
+        Received {
+            instant: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+}
+
+impl SignalInfo {
+    pub(crate) fn from_bytes(buf: &[u8; SIGINFO_SIZE]) -> Self {
+        SignalInfo(zerocopy::transmute!(*buf), None)
+    }
+
+    /// Creates a record carrying nothing but the signal number, for engines
+    /// that don't report anything else.
+    #[cfg_attr(not(kqueue), allow(dead_code))]
+    pub(crate) fn from_signo(signo: i32) -> Self {
+        let mut info = signalfd_siginfo::new_zeroed();
+        info.ssi_signo = signo as u32;
+        SignalInfo(info, None)
+    }
+
+    /// Converts a `siginfo_t` as filled in by `sigwaitinfo(2)`.
+    #[cfg_attr(not(any(sigwait, feature = "signal-hook")), allow(dead_code))]
+    pub(crate) fn from_siginfo(info: &libc::siginfo_t) -> Self {
+        let mut record = signalfd_siginfo::new_zeroed();
+        record.ssi_signo = info.si_signo as u32;
+        record.ssi_errno = info.si_errno;
+        record.ssi_code = info.si_code;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        unsafe {
+            record.ssi_pid = info.si_pid() as u32;
+            record.ssi_uid = info.si_uid();
+            record.ssi_status = info.si_status();
+            let value = info.si_value();
+            record.ssi_ptr = value.sival_ptr as u64;
+            // `sigval` is a union of an int and a pointer; libc only spells
+            // out the pointer, so the int member is read through its address.
+            record.ssi_int = std::ptr::read(&value as *const libc::sigval as *const libc::c_int);
+        }
+        SignalInfo(record, None)
+    }
+
+    #[cfg_attr(not(any(kqueue, sigwait, feature = "signal-hook")), allow(dead_code))]
+    pub(crate) fn to_bytes(self) -> [u8; SIGINFO_SIZE] {
+        zerocopy::transmute!(self.0)
+    }
+
+    /// Stamps the record with the time it was read at.
+    pub(crate) fn received(mut self, at: Received) -> Self {
+        self.1 = Some(at);
+        self
+    }
+
+    /// Returns when the record was read from the descriptor, or `None` for
+    /// records that weren't, e.g. converted from another crate's type.
+    ///
+    /// All the records taken by a single read share a timestamp. Comparing it
+    /// with [`Instant::now`] when handling the signal gives the time it spent
+    /// waiting in the buffer.
+    pub fn received_at(&self) -> Option<Instant> {
+        self.1.map(|at| at.instant)
+    }
+
+    /// Returns the wall-clock time the record was read at, to correlate it
+    /// with logs, or `None` for records that weren't read from a descriptor.
+    ///
+    /// Unlike [`received_at`](SignalInfo::received_at), this follows changes
+    /// to the system clock.
+    pub fn received_wall_time(&self) -> Option<SystemTime> {
+        self.1.map(|at| at.wall)
+    }
+
+    /// Signal number.
+    pub fn signo(&self) -> i32 {
+        self.0.ssi_signo as i32
+    }
+
+    /// Error number (unused by the kernel for most signals).
+    pub fn errno(&self) -> i32 {
+        self.0.ssi_errno
+    }
+
+    /// Signal code, e.g. `SI_USER` or `CLD_EXITED`.
+    pub fn code(&self) -> i32 {
+        self.0.ssi_code
+    }
+
+    /// Signal code, decoded according to the signal.
+    ///
+    /// Codes are interpreted with Linux's values. Elsewhere the kqueue
+    /// backend reports every signal as [`SignalCode::User`], and codes the
+    /// sigwait thread passes on from the platform show up as
+    /// [`SignalCode::Other`].
+    pub fn origin(&self) -> SignalCode {
+        SignalCode::decode(self.signo(), self.code())
+    }
+
+    /// PID of the sender.
+    pub fn pid(&self) -> u32 {
+        self.0.ssi_pid
+    }
+
+    /// Real UID of the sender.
+    pub fn uid(&self) -> u32 {
+        self.0.ssi_uid
+    }
+
+    /// File descriptor (for `SIGIO`).
+    pub fn fd(&self) -> i32 {
+        self.0.ssi_fd
+    }
+
+    /// Kernel timer ID (for POSIX timers).
+    pub fn tid(&self) -> u32 {
+        self.0.ssi_tid
+    }
+
+    /// Band event (for `SIGIO`).
+    pub fn band(&self) -> u32 {
+        self.0.ssi_band
+    }
+
+    /// Overrun count (for POSIX timers).
+    pub fn overrun(&self) -> u32 {
+        self.0.ssi_overrun
+    }
+
+    /// Trap number that caused the signal.
+    pub fn trapno(&self) -> u32 {
+        self.0.ssi_trapno
+    }
+
+    /// Exit status or signal (for `SIGCHLD`).
+    pub fn status(&self) -> i32 {
+        self.0.ssi_status
+    }
+
+    /// Integer sent by `sigqueue(3)`.
+    pub fn int(&self) -> i32 {
+        self.0.ssi_int
+    }
+
+    /// Pointer sent by `sigqueue(3)`.
+    pub fn ptr(&self) -> u64 {
+        self.0.ssi_ptr
+    }
+
+    /// Integer passed along with the signal, or `None` if it doesn't carry
+    /// one.
+    ///
+    /// Signals sent by `sigqueue(3)` carry the value they were queued with;
+    /// POSIX timers and message queue notifications the one from their
+    /// `sigevent`. Since realtime signals are queued rather than coalesced,
+    /// this makes them usable as a lightweight channel between processes.
+    /// Use [`ptr`](SignalInfo::ptr) for values that don't fit in an `i32`.
+    pub fn value(&self) -> Option<i32> {
+        match self.origin() {
+            SignalCode::Queue | SignalCode::Timer | SignalCode::MesgQ => Some(self.int()),
+            _ => None,
+        }
+    }
+
+    /// User CPU time consumed (for `SIGCHLD`).
+    pub fn utime(&self) -> u64 {
+        self.0.ssi_utime
+    }
+
+    /// System CPU time consumed (for `SIGCHLD`).
+    pub fn stime(&self) -> u64 {
+        self.0.ssi_stime
+    }
+
+    /// Address that generated the signal (for hardware-generated signals).
+    pub fn addr(&self) -> u64 {
+        self.0.ssi_addr
+    }
+
+    /// Least significant bit of address (for `SIGBUS`).
+    pub fn addr_lsb(&self) -> u16 {
+        self.0.ssi_addr_lsb
+    }
+
+    /// System call number (for `SIGSYS` raised by seccomp).
+    pub fn syscall(&self) -> i32 {
+        self.0.ssi_syscall
+    }
+
+    /// Address of the system call instruction (for `SIGSYS`).
+    pub fn call_addr(&self) -> u64 {
+        self.0.ssi_call_addr
+    }
+
+    /// Architecture of the attempted system call (for `SIGSYS`).
+    pub fn arch(&self) -> u32 {
+        self.0.ssi_arch
+    }
+}
+
+/// How a signal was generated, as decoded by [`SignalInfo::origin`].
+///
+/// Negative codes and `SI_USER`/`SI_KERNEL` mean the same for every signal,
+/// positive ones are specific to the signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SignalCode {
+    /// Sent by `kill(2)` or `raise(3)` (`SI_USER`).
+    User,
+    /// Sent by the kernel (`SI_KERNEL`).
+    Kernel,
+    /// Sent by `sigqueue(3)` (`SI_QUEUE`).
+    Queue,
+    /// Expiry of a POSIX timer (`SI_TIMER`).
+    Timer,
+    /// A message arrived on a POSIX message queue (`SI_MESGQ`).
+    MesgQ,
+    /// Completion of asynchronous I/O (`SI_ASYNCIO`).
+    AsyncIo,
+    /// Queued `SIGIO` (`SI_SIGIO`).
+    Sigio,
+    /// Sent by `tkill(2)`, `tgkill(2)` or `pthread_kill(3)` (`SI_TKILL`).
+    Tkill,
+    /// A child changed state (for `SIGCHLD`).
+    Child(ChildCode),
+    /// Invalid memory reference (for `SIGSEGV`).
+    Segv(SegvCode),
+    /// Bus error (for `SIGBUS`).
+    Bus(BusCode),
+    /// I/O readiness (for `SIGIO`).
+    Poll(PollCode),
+    /// Any other code.
+    Other(i32),
+}
+
+/// Child state changes, `CLD_*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChildCode {
+    /// The child exited.
+    Exited,
+    /// The child was killed.
+    Killed,
+    /// The child was killed and dumped core.
+    Dumped,
+    /// A traced child trapped.
+    Trapped,
+    /// The child was stopped.
+    Stopped,
+    /// A stopped child was continued.
+    Continued,
+}
+
+/// Causes of `SIGSEGV`, `SEGV_*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegvCode {
+    /// The address isn't mapped.
+    MapErr,
+    /// The mapping doesn't permit the access.
+    AccErr,
+    /// The address failed a bounds check.
+    BndErr,
+    /// The access was denied by a memory protection key.
+    PkuErr,
+}
+
+/// Causes of `SIGBUS`, `BUS_*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BusCode {
+    /// Invalid address alignment.
+    AdrAln,
+    /// Nonexistent physical address.
+    AdrErr,
+    /// Object specific hardware error.
+    ObjErr,
+    /// Hardware memory error consumed on a machine check.
+    MceErrAr,
+    /// Hardware memory error detected but not consumed.
+    MceErrAo,
+}
+
+/// I/O events reported with `SIGIO`, `POLL_*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PollCode {
+    /// Data input available.
+    In,
+    /// Output buffers available.
+    Out,
+    /// Input message available.
+    Msg,
+    /// I/O error.
+    Err,
+    /// High priority input available.
+    Pri,
+    /// Device disconnected.
+    Hup,
+}
+
+impl SignalCode {
+    fn decode(signo: i32, code: i32) -> SignalCode {
+        match (signo, code) {
+            (_, 0) => SignalCode::User,
+            (_, 0x80) => SignalCode::Kernel,
+            (_, -1) => SignalCode::Queue,
+            (_, -2) => SignalCode::Timer,
+            (_, -3) => SignalCode::MesgQ,
+            (_, -4) => SignalCode::AsyncIo,
+            (_, -5) => SignalCode::Sigio,
+            (_, -6) => SignalCode::Tkill,
+            (libc::SIGCHLD, 1..=6) => SignalCode::Child(
+                [
+                    ChildCode::Exited,
+                    ChildCode::Killed,
+                    ChildCode::Dumped,
+                    ChildCode::Trapped,
+                    ChildCode::Stopped,
+                    ChildCode::Continued,
+                ][code as usize - 1],
+            ),
+            (libc::SIGSEGV, 1..=4) => SignalCode::Segv(
+                [
+                    SegvCode::MapErr,
+                    SegvCode::AccErr,
+                    SegvCode::BndErr,
+                    SegvCode::PkuErr,
+                ][code as usize - 1],
+            ),
+            (libc::SIGBUS, 1..=5) => SignalCode::Bus(
+                [
+                    BusCode::AdrAln,
+                    BusCode::AdrErr,
+                    BusCode::ObjErr,
+                    BusCode::MceErrAr,
+                    BusCode::MceErrAo,
+                ][code as usize - 1],
+            ),
+            (libc::SIGIO, 1..=6) => SignalCode::Poll(
+                [
+                    PollCode::In,
+                    PollCode::Out,
+                    PollCode::Msg,
+                    PollCode::Err,
+                    PollCode::Pri,
+                    PollCode::Hup,
+                ][code as usize - 1],
+            ),
+            _ => SignalCode::Other(code),
+        }
+    }
+}
+
+impl fmt::Debug for SignalInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalInfo")
+            .field("signo", &self.signo())
+            .field("errno", &self.errno())
+            .field("code", &self.code())
+            .field("pid", &self.pid())
+            .field("uid", &self.uid())
+            .field("fd", &self.fd())
+            .field("tid", &self.tid())
+            .field("band", &self.band())
+            .field("overrun", &self.overrun())
+            .field("trapno", &self.trapno())
+            .field("status", &self.status())
+            .field("int", &self.int())
+            .field("ptr", &self.ptr())
+            .field("utime", &self.utime())
+            .field("stime", &self.stime())
+            .field("addr", &self.addr())
+            .field("addr_lsb", &self.addr_lsb())
+            .field("syscall", &self.syscall())
+            .field("call_addr", &self.call_addr())
+            .field("arch", &self.arch())
+            .field("received_at", &self.received_at())
+            .finish()
+    }
+}
+
+/// The record's fields, by the names of their accessors.
+///
+/// Receive timestamps aren't included, since `Instant`s only make sense
+/// within the process. Missing fields deserialize as zero.
+#[cfg(feature = "serde")]
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename = "SignalInfo", default)]
+struct Fields {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    pid: u32,
+    uid: u32,
+    fd: i32,
+    tid: u32,
+    band: u32,
+    overrun: u32,
+    trapno: u32,
+    status: i32,
+    int: i32,
+    ptr: u64,
+    utime: u64,
+    stime: u64,
+    addr: u64,
+    addr_lsb: u16,
+    syscall: i32,
+    call_addr: u64,
+    arch: u32,
+}
+
+/// Serializes the record's fields, by the names of their accessors.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SignalInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Fields {
+            signo: self.signo(),
+            errno: self.errno(),
+            code: self.code(),
+            pid: self.pid(),
+            uid: self.uid(),
+            fd: self.fd(),
+            tid: self.tid(),
+            band: self.band(),
+            overrun: self.overrun(),
+            trapno: self.trapno(),
+            status: self.status(),
+            int: self.int(),
+            ptr: self.ptr(),
+            utime: self.utime(),
+            stime: self.stime(),
+            addr: self.addr(),
+            addr_lsb: self.addr_lsb(),
+            syscall: self.syscall(),
+            call_addr: self.call_addr(),
+            arch: self.arch(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Missing fields are zero, and the record carries no receive timestamps.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SignalInfo {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = Fields::deserialize(deserializer)?;
+        let mut info = SignalInfo::from_signo(fields.signo);
+        let record = &mut info.0;
+        record.ssi_errno = fields.errno;
+        record.ssi_code = fields.code;
+        record.ssi_pid = fields.pid;
+        record.ssi_uid = fields.uid;
+        record.ssi_fd = fields.fd;
+        record.ssi_tid = fields.tid;
+        record.ssi_band = fields.band;
+        record.ssi_overrun = fields.overrun;
+        record.ssi_trapno = fields.trapno;
+        record.ssi_status = fields.status;
+        record.ssi_int = fields.int;
+        record.ssi_ptr = fields.ptr;
+        record.ssi_utime = fields.utime;
+        record.ssi_stime = fields.stime;
+        record.ssi_addr = fields.addr;
+        record.ssi_addr_lsb = fields.addr_lsb;
+        record.ssi_syscall = fields.syscall;
+        record.ssi_call_addr = fields.call_addr;
+        record.ssi_arch = fields.arch;
+        Ok(info)
+    }
+}
+
+impl From<SignalInfo> for i32 {
+    fn from(info: SignalInfo) -> i32 {
+        info.signo()
+    }
+}
+
+#[cfg(all(feature = "nix", any(target_os = "linux", target_os = "android")))]
+impl From<nix::sys::signalfd::siginfo> for SignalInfo {
+    fn from(info: nix::sys::signalfd::siginfo) -> SignalInfo {
+        // Both mirror the kernel's record, which `transmute` checks the size
+        // of.
+        SignalInfo::from_bytes(&unsafe {
+            std::mem::transmute::<nix::sys::signalfd::siginfo, [u8; SIGINFO_SIZE]>(info)
+        })
+    }
+}
+
+#[cfg(all(feature = "nix", any(target_os = "linux", target_os = "android")))]
+impl From<SignalInfo> for nix::sys::signalfd::siginfo {
+    fn from(info: SignalInfo) -> nix::sys::signalfd::siginfo {
+        unsafe {
+            std::mem::transmute::<[u8; SIGINFO_SIZE], nix::sys::signalfd::siginfo>(info.to_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(signo: i32, code: i32) -> SignalInfo {
+        let mut info = SignalInfo::from_signo(signo);
+        info.0.ssi_code = code;
+        info
+    }
+
+    #[test]
+    fn decode_origin() {
+        assert_eq!(record(libc::SIGTERM, 0).origin(), SignalCode::User);
+        assert_eq!(record(libc::SIGUSR1, -1).origin(), SignalCode::Queue);
+        assert_eq!(
+            record(libc::SIGCHLD, 2).origin(),
+            SignalCode::Child(ChildCode::Killed)
+        );
+        assert_eq!(
+            record(libc::SIGSEGV, 1).origin(),
+            SignalCode::Segv(SegvCode::MapErr)
+        );
+        assert_eq!(record(libc::SIGUSR1, 2).origin(), SignalCode::Other(2));
+    }
+
+    #[test]
+    fn queued_value() {
+        let mut info = record(libc::SIGUSR1, -1);
+        info.0.ssi_int = 42;
+        assert_eq!(info.value(), Some(42));
+        info.0.ssi_code = 0;
+        assert_eq!(info.value(), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn converts_siginfo_value() {
+        let set = crate::sigset::SigSet::from_signals(&[libc::SIGUSR1]).unwrap();
+        let mut previous = crate::sigset::SigSet::empty();
+        unsafe {
+            libc::pthread_sigmask(libc::SIG_BLOCK, set.as_raw(), previous.as_raw_mut());
+            let mut value: libc::sigval = std::mem::zeroed();
+            std::ptr::write(&mut value as *mut libc::sigval as *mut libc::c_int, 42);
+            assert_eq!(
+                libc::pthread_sigqueue(libc::pthread_self(), libc::SIGUSR1, value),
+                0
+            );
+            let mut raw: libc::siginfo_t = std::mem::zeroed();
+            assert_eq!(libc::sigwaitinfo(set.as_raw(), &mut raw), libc::SIGUSR1);
+            libc::pthread_sigmask(libc::SIG_SETMASK, previous.as_raw(), std::ptr::null_mut());
+            assert_eq!(SignalInfo::from_siginfo(&raw).value(), Some(42));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        let mut info = record(libc::SIGUSR1, -1);
+        info.0.ssi_pid = 1234;
+        info.0.ssi_int = 42;
+        let json = serde_json::to_string(&info).unwrap();
+        let prefix = format!(
+            r#"{{"signo":{},"errno":0,"code":-1,"pid":1234,"#,
+            libc::SIGUSR1
+        );
+        assert!(json.starts_with(&prefix));
+        let back: SignalInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.pid(), back.value()), (1234, Some(42)));
+
+        let partial: SignalInfo =
+            serde_json::from_str(&format!(r#"{{"signo":{}}}"#, libc::SIGTERM)).unwrap();
+        assert_eq!((partial.signo(), partial.pid()), (libc::SIGTERM, 0));
+        let signal = serde_json::to_string(&crate::Signal::Realtime(2)).unwrap();
+        assert_eq!(signal, r#""SIGRTMIN+2""#);
+        let back: crate::Signal = serde_json::from_str(&signal).unwrap();
+        assert_eq!(back, crate::Signal::Realtime(2));
+        assert!(serde_json::from_str::<crate::Signal>(r#""SIGFOO""#).is_err());
+    }
+
+    #[test]
+    fn receive_timestamps() {
+        let info = record(libc::SIGUSR1, 0);
+        assert!(info.received_at().is_none());
+        let before = Instant::now();
+        let info = info.received(Received::now());
+        assert!(info.received_at().unwrap() >= before);
+        assert!(info.received_wall_time().is_some());
+    }
+
+    #[test]
+    #[cfg(all(feature = "nix", any(target_os = "linux", target_os = "android")))]
+    fn nix_siginfo() {
+        let mut info = record(libc::SIGUSR1, -1);
+        info.0.ssi_int = 42;
+        let raw = nix::sys::signalfd::siginfo::from(info);
+        assert_eq!(raw.ssi_signo, libc::SIGUSR1 as u32);
+        assert_eq!(raw.ssi_int, 42);
+        assert_eq!(SignalInfo::from(raw).value(), Some(42));
+    }
+}
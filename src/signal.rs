@@ -0,0 +1,741 @@
+//! Typed signal numbers.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::error::SignalFdError;
+use crate::siginfo::SignalInfo;
+
+/// A signal, as a typed alternative to raw signal numbers.
+///
+/// Conversions from raw numbers always produce the named variant when one
+/// exists, so `Other` only ever holds numbers without a name of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Signal {
+    /// `SIGHUP`: the controlling terminal hung up, or by convention a request
+    /// to reload the configuration.
+    Hup,
+    /// `SIGINT`: interrupt from the keyboard, usually Ctrl-C.
+    Int,
+    /// `SIGQUIT`: quit from the keyboard, usually `Ctrl-\`, dumping core.
+    Quit,
+    /// `SIGILL`: illegal instruction.
+    Ill,
+    /// `SIGTRAP`: trace or breakpoint trap.
+    Trap,
+    /// `SIGABRT`: raised by `abort(3)`.
+    Abrt,
+    /// `SIGBUS`: bus error, e.g. a misaligned or unmapped access.
+    Bus,
+    /// `SIGFPE`: arithmetic error, e.g. an integer division by zero.
+    Fpe,
+    /// `SIGKILL`: kills the process; it can't be caught, blocked or ignored.
+    Kill,
+    /// `SIGUSR1`: for use by the application.
+    Usr1,
+    /// `SIGSEGV`: invalid memory reference.
+    Segv,
+    /// `SIGUSR2`: for use by the application.
+    Usr2,
+    /// `SIGPIPE`: write to a pipe or socket nobody reads from.
+    Pipe,
+    /// `SIGALRM`: timer set by `alarm(2)` expired.
+    Alrm,
+    /// `SIGTERM`: request to terminate, sent by `kill` by default.
+    Term,
+    /// `SIGSTKFLT`: coprocessor stack fault, unused on Linux.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Stkflt,
+    /// `SIGCHLD`: a child process stopped, continued or exited.
+    Chld,
+    /// `SIGCONT`: continue the process if it is stopped.
+    Cont,
+    /// `SIGSTOP`: stops the process; it can't be caught, blocked or ignored.
+    Stop,
+    /// `SIGTSTP`: stop from the keyboard, usually Ctrl-Z.
+    Tstp,
+    /// `SIGTTIN`: a background process read from its terminal.
+    Ttin,
+    /// `SIGTTOU`: a background process wrote to its terminal.
+    Ttou,
+    /// `SIGURG`: urgent data arrived on a socket.
+    Urg,
+    /// `SIGXCPU`: the CPU time limit was exceeded.
+    Xcpu,
+    /// `SIGXFSZ`: the file size limit was exceeded.
+    Xfsz,
+    /// `SIGVTALRM`: virtual timer expired.
+    Vtalrm,
+    /// `SIGPROF`: profiling timer expired.
+    Prof,
+    /// `SIGWINCH`: the terminal window was resized.
+    Winch,
+    /// `SIGIO`: a descriptor became ready for IO.
+    Io,
+    /// `SIGPWR`: power failure.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Pwr,
+    /// `SIGSYS`: bad system call, also raised by seccomp filters.
+    Sys,
+    /// Realtime signal `SIGRTMIN + n`.
+    ///
+    /// Unlike standard signals, realtime signals are queued: every instance
+    /// sent is delivered as a separate event, in order, together with its
+    /// `sigqueue(3)` payload.
+    ///
+    /// Create it with [`Signal::rt`], which checks `n` against the number of
+    /// realtime signals. A larger offset names no signal: its number is past
+    /// `SIGRTMAX`, and is rejected as invalid wherever it is used.
+    Realtime(u8),
+    /// Any other signal number, which may not be a valid signal.
+    Other(i32),
+}
+
+impl Signal {
+    /// Returns the realtime signal `SIGRTMIN + n`.
+    ///
+    /// Fails with `InvalidInput` if the result would be above `SIGRTMAX`.
+    pub fn rt(n: u8) -> io::Result<Signal> {
+        if i32::from(n) > sigrtmax() - sigrtmin() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "SIGRTMIN+{} is out of range, at most {} realtime signals are available",
+                    n,
+                    sigrtmax() - sigrtmin() + 1
+                ),
+            ));
+        }
+        Ok(Signal::Realtime(n))
+    }
+
+    /// Returns `true` for realtime signals.
+    pub fn is_realtime(self) -> bool {
+        let signo = self.as_raw();
+        signo >= sigrtmin() && signo <= sigrtmax()
+    }
+
+    /// Converts a raw signal number.
+    pub fn from_raw(signo: i32) -> Signal {
+        match signo {
+            libc::SIGHUP => Signal::Hup,
+            libc::SIGINT => Signal::Int,
+            libc::SIGQUIT => Signal::Quit,
+            libc::SIGILL => Signal::Ill,
+            libc::SIGTRAP => Signal::Trap,
+            libc::SIGABRT => Signal::Abrt,
+            libc::SIGBUS => Signal::Bus,
+            libc::SIGFPE => Signal::Fpe,
+            libc::SIGKILL => Signal::Kill,
+            libc::SIGUSR1 => Signal::Usr1,
+            libc::SIGSEGV => Signal::Segv,
+            libc::SIGUSR2 => Signal::Usr2,
+            libc::SIGPIPE => Signal::Pipe,
+            libc::SIGALRM => Signal::Alrm,
+            libc::SIGTERM => Signal::Term,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::SIGSTKFLT => Signal::Stkflt,
+            libc::SIGCHLD => Signal::Chld,
+            libc::SIGCONT => Signal::Cont,
+            libc::SIGSTOP => Signal::Stop,
+            libc::SIGTSTP => Signal::Tstp,
+            libc::SIGTTIN => Signal::Ttin,
+            libc::SIGTTOU => Signal::Ttou,
+            libc::SIGURG => Signal::Urg,
+            libc::SIGXCPU => Signal::Xcpu,
+            libc::SIGXFSZ => Signal::Xfsz,
+            libc::SIGVTALRM => Signal::Vtalrm,
+            libc::SIGPROF => Signal::Prof,
+            libc::SIGWINCH => Signal::Winch,
+            libc::SIGIO => Signal::Io,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::SIGPWR => Signal::Pwr,
+            libc::SIGSYS => Signal::Sys,
+            n if n >= sigrtmin() && n <= sigrtmax() => Signal::Realtime((n - sigrtmin()) as u8),
+            n => Signal::Other(n),
+        }
+    }
+
+    /// Returns the raw signal number.
+    pub fn as_raw(self) -> i32 {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Int => libc::SIGINT,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Ill => libc::SIGILL,
+            Signal::Trap => libc::SIGTRAP,
+            Signal::Abrt => libc::SIGABRT,
+            Signal::Bus => libc::SIGBUS,
+            Signal::Fpe => libc::SIGFPE,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Segv => libc::SIGSEGV,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Pipe => libc::SIGPIPE,
+            Signal::Alrm => libc::SIGALRM,
+            Signal::Term => libc::SIGTERM,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Signal::Stkflt => libc::SIGSTKFLT,
+            Signal::Chld => libc::SIGCHLD,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Tstp => libc::SIGTSTP,
+            Signal::Ttin => libc::SIGTTIN,
+            Signal::Ttou => libc::SIGTTOU,
+            Signal::Urg => libc::SIGURG,
+            Signal::Xcpu => libc::SIGXCPU,
+            Signal::Xfsz => libc::SIGXFSZ,
+            Signal::Vtalrm => libc::SIGVTALRM,
+            Signal::Prof => libc::SIGPROF,
+            Signal::Winch => libc::SIGWINCH,
+            Signal::Io => libc::SIGIO,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Signal::Pwr => libc::SIGPWR,
+            Signal::Sys => libc::SIGSYS,
+            Signal::Realtime(n) => sigrtmin() + i32::from(n),
+            Signal::Other(n) => n,
+        }
+    }
+}
+
+impl Signal {
+    /// Returns the signal's conventional name, e.g. `"SIGTERM"`, or `None`
+    /// for realtime and other signals without one.
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Signal::Hup => "SIGHUP",
+            Signal::Int => "SIGINT",
+            Signal::Quit => "SIGQUIT",
+            Signal::Ill => "SIGILL",
+            Signal::Trap => "SIGTRAP",
+            Signal::Abrt => "SIGABRT",
+            Signal::Bus => "SIGBUS",
+            Signal::Fpe => "SIGFPE",
+            Signal::Kill => "SIGKILL",
+            Signal::Usr1 => "SIGUSR1",
+            Signal::Segv => "SIGSEGV",
+            Signal::Usr2 => "SIGUSR2",
+            Signal::Pipe => "SIGPIPE",
+            Signal::Alrm => "SIGALRM",
+            Signal::Term => "SIGTERM",
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Signal::Stkflt => "SIGSTKFLT",
+            Signal::Chld => "SIGCHLD",
+            Signal::Cont => "SIGCONT",
+            Signal::Stop => "SIGSTOP",
+            Signal::Tstp => "SIGTSTP",
+            Signal::Ttin => "SIGTTIN",
+            Signal::Ttou => "SIGTTOU",
+            Signal::Urg => "SIGURG",
+            Signal::Xcpu => "SIGXCPU",
+            Signal::Xfsz => "SIGXFSZ",
+            Signal::Vtalrm => "SIGVTALRM",
+            Signal::Prof => "SIGPROF",
+            Signal::Winch => "SIGWINCH",
+            Signal::Io => "SIGIO",
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Signal::Pwr => "SIGPWR",
+            Signal::Sys => "SIGSYS",
+            Signal::Realtime(_) | Signal::Other(_) => return None,
+        })
+    }
+
+    /// Returns the signal's name as a metrics label, without allocating for
+    /// every record.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn label(self) -> metrics::SharedString {
+        use std::sync::OnceLock;
+
+        static UNNAMED: OnceLock<Vec<metrics::SharedString>> = OnceLock::new();
+        if let Some(name) = self.name() {
+            return metrics::SharedString::const_str(name);
+        }
+        let unnamed = UNNAMED.get_or_init(|| {
+            (0..=sigrtmax())
+                .map(|signo| {
+                    metrics::SharedString::from_shared(Signal::from_raw(signo).to_string().into())
+                })
+                .collect()
+        });
+        match unnamed.get(self.as_raw() as usize) {
+            Some(label) => label.clone(),
+            None => self.to_string().into(),
+        }
+    }
+}
+
+/// Formats the signal's name, `SIGRTMIN+n` for realtime signals and the bare
+/// number for any other signal.
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), *self) {
+            (Some(name), _) => f.write_str(name),
+            (None, Signal::Realtime(0)) => f.write_str("SIGRTMIN"),
+            (None, Signal::Realtime(n)) => write!(f, "SIGRTMIN+{}", n),
+            (None, signal) => write!(f, "{}", signal.as_raw()),
+        }
+    }
+}
+
+/// Parses a signal name, with or without the `SIG` prefix and in any case,
+/// a number, or a realtime signal as `RTMIN+n` or `RTMAX-n`.
+///
+/// Fails with `InvalidInput` for unknown names and numbers that aren't valid
+/// signals.
+impl FromStr for Signal {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Signal> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a signal", s),
+            )
+        };
+        let upper = s.trim().to_ascii_uppercase();
+        if let Ok(signo) = upper.parse::<i32>() {
+            if !(1..=sigrtmax()).contains(&signo) {
+                return Err(InvalidSignal::new(signo).into());
+            }
+            return Ok(Signal::from_raw(signo));
+        }
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        let realtime = |offset: &str, sign: char| match offset {
+            "" => Some(0),
+            offset => offset.strip_prefix(sign)?.parse::<u8>().ok(),
+        };
+        if let Some(offset) = name.strip_prefix("RTMIN") {
+            let n = realtime(offset, '+').ok_or_else(invalid)?;
+            return Signal::rt(n).map_err(|_| invalid());
+        }
+        if let Some(offset) = name.strip_prefix("RTMAX") {
+            let n = realtime(offset, '-').ok_or_else(invalid)?;
+            let signo = sigrtmax() - i32::from(n);
+            if signo < sigrtmin() {
+                return Err(invalid());
+            }
+            return Ok(Signal::from_raw(signo));
+        }
+        match name {
+            "IOT" => return Ok(Signal::Abrt),
+            "POLL" => return Ok(Signal::Io),
+            _ => {}
+        }
+        (1..sigrtmin().min(65))
+            .map(Signal::from_raw)
+            .find(|signal| signal.name().map(|full| &full[3..]) == Some(name))
+            .ok_or_else(invalid)
+    }
+}
+
+/// Serializes the signal as formatted by `Display`, so that realtime signals
+/// keep their name rather than an offset from the local `SIGRTMIN`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Signal {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes anything `FromStr` parses.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Signal {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = Signal;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a signal name or number")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Signal, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+/// A signal that can't be received, reported by functions taking signals as
+/// [`SignalFdError::InvalidSignal`](crate::SignalFdError::InvalidSignal).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSignal {
+    signo: i32,
+}
+
+impl InvalidSignal {
+    pub(crate) fn new(signo: i32) -> Self {
+        InvalidSignal { signo }
+    }
+
+    /// Returns the offending signal number.
+    pub fn signo(&self) -> i32 {
+        self.signo
+    }
+
+    /// Returns `true` for `SIGKILL` and `SIGSTOP`, which are valid signals
+    /// but can be neither caught nor blocked; `false` for numbers that aren't
+    /// signals at all.
+    pub fn is_uncatchable(&self) -> bool {
+        self.signo == libc::SIGKILL || self.signo == libc::SIGSTOP
+    }
+}
+
+impl fmt::Display for InvalidSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_uncatchable() {
+            write!(
+                f,
+                "{} can't be caught or blocked",
+                Signal::from_raw(self.signo)
+            )
+        } else {
+            write!(f, "{} is not a valid signal number", self.signo)
+        }
+    }
+}
+
+impl std::error::Error for InvalidSignal {}
+
+impl From<InvalidSignal> for io::Error {
+    fn from(invalid: InvalidSignal) -> io::Error {
+        SignalFdError::from(invalid).into()
+    }
+}
+
+/// Returns the lowest realtime signal number.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn sigrtmin() -> i32 {
+    libc::SIGRTMIN()
+}
+
+/// Returns the highest signal number.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn sigrtmax() -> i32 {
+    libc::SIGRTMAX()
+}
+
+/// Returns the lowest realtime signal number; realtime signals aren't
+/// supported on this platform, so the range is empty.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn sigrtmin() -> i32 {
+    sigrtmax() + 1
+}
+
+/// Returns the highest signal number, large enough for every BSD.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn sigrtmax() -> i32 {
+    128
+}
+
+/// Sends `signal` to the process `pid`, using `kill(2)`.
+///
+/// As with `kill(2)`, a `pid` of zero or below addresses process groups; see
+/// its manual page. Fails with `PermissionDenied` if the caller may not
+/// signal the process. If there is no such process, the error is the OS's
+/// `ESRCH`, which has no `ErrorKind` of its own: match on `raw_os_error`.
+pub fn send<S: Into<i32>>(pid: libc::pid_t, signal: S) -> io::Result<()> {
+    if unsafe { libc::kill(pid, signal.into()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sends `signal` to the thread `tid` of the process `tgid`, using
+/// `tgkill(2)`.
+///
+/// The signal is delivered to that thread only, so it is received by a
+/// signalfd created there, and otherwise handled as if that thread raised it.
+/// Errors are reported as by [`send`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn send_to_thread<S: Into<i32>>(
+    tgid: libc::pid_t,
+    tid: libc::pid_t,
+    signal: S,
+) -> io::Result<()> {
+    let rv = unsafe { libc::syscall(libc::SYS_tgkill, tgid, tid, signal.into()) };
+    if rv < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sends `signal` to `thread` of the calling process, using
+/// `pthread_kill(3)`.
+///
+/// The thread can be the current one, from `pthread_self(3)`, or one spawned
+/// by `std::thread`, from `JoinHandleExt::as_pthread_t`. Errors are reported
+/// as by [`send`].
+///
+/// How a thread-directed signal is handled depends on the target thread's
+/// mask, not on any other thread's:
+///
+/// - If the thread has the signal blocked, it stays pending for that thread
+///   until a signalfd created *on that thread* reads it. A signalfd on any
+///   other thread never sees it.
+/// - Otherwise it goes to the signal's disposition, as if the thread raised
+///   it. With a handler installed without `SA_RESTART`, this interrupts a
+///   system call the thread is blocked in with `EINTR`, which is how a
+///   particular worker can be woken up. With the default disposition most
+///   signals terminate the whole process.
+pub fn send_to_pthread<S: Into<i32>>(thread: libc::pthread_t, signal: S) -> io::Result<()> {
+    let rv = unsafe { libc::pthread_kill(thread, signal.into()) };
+    if rv != 0 {
+        return Err(io::Error::from_raw_os_error(rv));
+    }
+    Ok(())
+}
+
+/// Raises `signal` with its default disposition, as if it weren't blocked or
+/// handled.
+///
+/// `signal` has to be blocked on the calling thread. It is raised while still
+/// blocked and then briefly unblocked, so that the default action happens
+/// right here: a stop signal returns once the process is continued, a
+/// terminating one doesn't return at all. The previous disposition is
+/// reinstated afterwards. Only async-signal-safe functions are called, so
+/// this can be used in a forked child.
+#[cfg(feature = "futures-03")]
+pub(crate) fn raise_default<S: Into<i32>>(signal: S) -> io::Result<()> {
+    let signo = signal.into();
+    let mut set: libc::sigset_t = unsafe { std::mem::zeroed() };
+    let mut old: libc::sigaction = unsafe { std::mem::zeroed() };
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = libc::SIG_DFL;
+    unsafe {
+        libc::sigemptyset(&mut set);
+        if libc::sigaddset(&mut set, signo) < 0 || libc::sigaction(signo, &action, &mut old) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    let mut result = Ok(());
+    if unsafe { libc::raise(signo) } != 0 {
+        result = Err(io::Error::last_os_error());
+    } else {
+        unsafe {
+            libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+            libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        }
+    }
+    unsafe { libc::sigaction(signo, &old, std::ptr::null_mut()) };
+    result
+}
+
+/// Queues `signal` for the process `pid`, together with `value`, using
+/// `sigqueue(3)`.
+///
+/// The receiver gets the value back from [`SignalInfo::value`]. Realtime
+/// signals are the natural choice here, since every instance sent is
+/// delivered rather than merged with one that is already pending. Fails with
+/// [`SignalFdError::QueueFull`](crate::SignalFdError::QueueFull) when the
+/// limit of queued signals has been reached; other errors are reported as by
+/// [`send`].
+#[cfg(not(any(
+    target_vendor = "apple",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+pub fn send_value<S: Into<i32>>(pid: libc::pid_t, signal: S, value: i32) -> io::Result<()> {
+    // `sigval` is a union of an int and a pointer; libc only spells out the
+    // pointer, so the int member is written through its address.
+    let mut sigval: libc::sigval = unsafe { std::mem::zeroed() };
+    unsafe { std::ptr::write(&mut sigval as *mut libc::sigval as *mut libc::c_int, value) };
+    if unsafe { libc::sigqueue(pid, signal.into(), sigval) } < 0 {
+        return Err(queue_error(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Returns the error of a failed `sigqueue(3)`, telling a full queue apart
+/// from an ordinary `WouldBlock`.
+#[cfg(not(any(
+    target_vendor = "apple",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+fn queue_error(err: io::Error) -> io::Error {
+    match err.raw_os_error() {
+        Some(libc::EAGAIN) => crate::SignalFdError::QueueFull(err).into(),
+        _ => err,
+    }
+}
+
+impl From<i32> for Signal {
+    fn from(signo: i32) -> Signal {
+        Signal::from_raw(signo)
+    }
+}
+
+impl From<Signal> for i32 {
+    fn from(signal: Signal) -> i32 {
+        signal.as_raw()
+    }
+}
+
+impl From<SignalInfo> for Signal {
+    fn from(info: SignalInfo) -> Signal {
+        Signal::from_raw(info.signo())
+    }
+}
+
+#[cfg(feature = "tokio-signal-compat")]
+impl From<tokio1::signal::unix::SignalKind> for Signal {
+    fn from(kind: tokio1::signal::unix::SignalKind) -> Signal {
+        Signal::from_raw(kind.as_raw_value())
+    }
+}
+
+#[cfg(feature = "tokio-signal-compat")]
+impl From<Signal> for tokio1::signal::unix::SignalKind {
+    fn from(signal: Signal) -> tokio1::signal::unix::SignalKind {
+        tokio1::signal::unix::SignalKind::from_raw(signal.as_raw())
+    }
+}
+
+#[cfg(feature = "nix")]
+impl From<nix::sys::signal::Signal> for Signal {
+    fn from(signal: nix::sys::signal::Signal) -> Signal {
+        Signal::from_raw(signal as i32)
+    }
+}
+
+/// Fails with `InvalidInput` for realtime and other signals nix has no
+/// variant for.
+#[cfg(feature = "nix")]
+impl std::convert::TryFrom<Signal> for nix::sys::signal::Signal {
+    type Error = io::Error;
+
+    fn try_from(signal: Signal) -> io::Result<nix::sys::signal::Signal> {
+        nix::sys::signal::Signal::try_from(signal.as_raw()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} has no nix::sys::signal::Signal equivalent", signal),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_roundtrip() {
+        for signo in 1..=sigrtmax() {
+            assert_eq!(Signal::from_raw(signo).as_raw(), signo);
+        }
+        assert_eq!(Signal::from_raw(libc::SIGTERM), Signal::Term);
+        assert_eq!(Signal::from_raw(0), Signal::Other(0));
+    }
+
+    #[test]
+    fn names() {
+        for signo in 1..=sigrtmax() {
+            let signal = Signal::from_raw(signo);
+            assert_eq!(signal.to_string().parse::<Signal>().unwrap(), signal);
+        }
+        assert_eq!(Signal::Term.to_string(), "SIGTERM");
+        for name in ["SIGTERM", "TERM", "term", " 15 "] {
+            assert_eq!(
+                name.parse::<Signal>().unwrap(),
+                Signal::from_raw(libc::SIGTERM)
+            );
+        }
+        assert!("SIGFOO".parse::<Signal>().is_err());
+        assert!("0".parse::<Signal>().is_err());
+        assert!("RTMAX+1".parse::<Signal>().is_err());
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            assert_eq!("RTMIN+3".parse::<Signal>().unwrap(), Signal::Realtime(3));
+            assert_eq!(Signal::Realtime(3).to_string(), "SIGRTMIN+3");
+            assert_eq!("SIGRTMAX".parse::<Signal>().unwrap().as_raw(), sigrtmax());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "nix")]
+    fn nix_conversions() {
+        use crate::sigset::SigSet;
+        use nix::sys::signal::Signal as NixSignal;
+        use std::convert::TryFrom;
+
+        assert_eq!(Signal::from(NixSignal::SIGTERM), Signal::Term);
+        assert_eq!(NixSignal::try_from(Signal::Hup).unwrap(), NixSignal::SIGHUP);
+        let err = NixSignal::try_from(Signal::rt(1).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let set = SigSet::from_signals(&[Signal::Usr1, Signal::Usr2]).unwrap();
+        let nix_set = nix::sys::signal::SigSet::from(set);
+        assert!(nix_set.contains(NixSignal::SIGUSR1));
+        assert!(!nix_set.contains(NixSignal::SIGHUP));
+        assert!(SigSet::from(nix_set).contains(Signal::Usr2));
+    }
+
+    #[test]
+    #[cfg(not(any(
+        target_vendor = "apple",
+        target_os = "openbsd",
+        target_os = "dragonfly"
+    )))]
+    fn queue_value() {
+        // The null signal only checks that the process can be signalled.
+        send_value(unsafe { libc::getpid() }, 0, 7).unwrap();
+        let err = send_value(libc::pid_t::MAX, Signal::Usr1, 7).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ESRCH));
+
+        let full = queue_error(io::Error::from_raw_os_error(libc::EAGAIN));
+        assert_eq!(full.kind(), io::ErrorKind::WouldBlock);
+        match crate::SignalFdError::from_io(&full) {
+            Some(crate::SignalFdError::QueueFull(err)) => {
+                assert_eq!(err.raw_os_error(), Some(libc::EAGAIN))
+            }
+            other => panic!("expected a full queue, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn send_errors() {
+        send(unsafe { libc::getpid() }, 0).unwrap();
+        let err = send(libc::pid_t::MAX, Signal::Term).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ESRCH));
+    }
+
+    #[test]
+    fn send_to_pthread_mask() {
+        let set = crate::sigset::SigSet::from_signals(&[Signal::Usr1]).unwrap();
+        let mut inner = crate::raw::RawSignalFd::from_set(&set).unwrap();
+        send_to_pthread(unsafe { libc::pthread_self() }, Signal::Usr1).unwrap();
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR1);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn send_to_own_thread() {
+        let set = crate::sigset::SigSet::from_signals(&[Signal::Usr2]).unwrap();
+        let mut inner = crate::raw::RawSignalFd::from_set(&set).unwrap();
+        let tid = unsafe { libc::gettid() };
+        send_to_thread(unsafe { libc::getpid() }, tid, Signal::Usr2).unwrap();
+        assert_eq!(inner.read_info().unwrap().signo(), libc::SIGUSR2);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn realtime_range() {
+        let count = sigrtmax() - sigrtmin();
+        assert_eq!(Signal::rt(0).unwrap().as_raw(), sigrtmin());
+        assert_eq!(Signal::rt(count as u8).unwrap().as_raw(), sigrtmax());
+        assert!(Signal::rt(count as u8 + 1).is_err());
+        let unchecked = Signal::Realtime(count as u8 + 1);
+        assert!(!unchecked.is_realtime());
+        assert!(crate::SigSet::empty().add(unchecked).is_err());
+        let err = send(unsafe { libc::getpid() }, unchecked).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+        assert!(Signal::rt(3).unwrap().is_realtime());
+        assert_eq!(Signal::from_raw(sigrtmin() + 2), Signal::Realtime(2));
+        assert!(!Signal::Term.is_realtime());
+    }
+}
@@ -0,0 +1,328 @@
+use std::env;
+use std::fmt;
+use std::io::{self, Result};
+use std::str::FromStr;
+
+use crate::signal::{sigrtmax, InvalidSignal, Signal};
+
+/// A set of signals, safe wrapper around `sigset_t`.
+#[derive(Clone, Copy)]
+pub struct SigSet(libc::sigset_t);
+
+impl SigSet {
+    /// Creates an empty set.
+    pub fn empty() -> Self {
+        let mut set = SigSet(unsafe { std::mem::zeroed() });
+        set.clear();
+        set
+    }
+
+    /// Creates a set containing every signal.
+    pub fn full() -> Self {
+        let mut set = SigSet::empty();
+        set.fill();
+        set
+    }
+
+    /// Creates a set containing `signals`.
+    pub fn from_signals<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        let mut set = SigSet::empty();
+        for signal in signals {
+            set.add(*signal)?;
+        }
+        Ok(set)
+    }
+
+    /// Creates the set of signals asking a process to shut down: `SIGINT`,
+    /// `SIGTERM`, `SIGQUIT` and `SIGHUP`.
+    pub fn terminating() -> Self {
+        SigSet::of(&[Signal::Int, Signal::Term, Signal::Quit, Signal::Hup])
+    }
+
+    /// Creates the set of every signal that can be caught, that is all of
+    /// them but `SIGKILL` and `SIGSTOP`.
+    ///
+    /// Blocking it doesn't keep hardware faults such as a `SIGSEGV` raised
+    /// by an invalid access from killing the process.
+    pub fn catchable() -> Self {
+        let mut set = SigSet::full();
+        let _ = set.remove(Signal::Kill);
+        let _ = set.remove(Signal::Stop);
+        set
+    }
+
+    /// Creates the set of job control signals, sent by the terminal and the
+    /// shell: `SIGTSTP`, `SIGTTIN`, `SIGTTOU` and `SIGCONT`.
+    pub fn job_control() -> Self {
+        SigSet::of(&[Signal::Tstp, Signal::Ttin, Signal::Ttou, Signal::Cont])
+    }
+
+    /// Creates a set of signals known to be valid.
+    fn of(signals: &[Signal]) -> Self {
+        SigSet::from_signals(signals).expect("standard signals are valid")
+    }
+
+    /// Parses a list of signals separated by commas or whitespace, e.g.
+    /// `"TERM,HUP,USR1"`, as read from a command line flag or a config file.
+    ///
+    /// Each signal is parsed as by [`Signal`]'s `FromStr`, so names with or
+    /// without the `SIG` prefix, numbers and realtime signals as `RTMIN+n`
+    /// are accepted. Fails with `InvalidInput`, naming the offending entry.
+    pub fn from_str_list(list: &str) -> Result<Self> {
+        let mut set = SigSet::empty();
+        for name in list.split(|c: char| c == ',' || c.is_whitespace()) {
+            if !name.is_empty() {
+                set.add(name.parse::<Signal>()?)?;
+            }
+        }
+        Ok(set)
+    }
+
+    /// Parses the environment variable `var` as by
+    /// [`from_str_list`](SigSet::from_str_list), returning `None` if it isn't
+    /// set.
+    ///
+    /// Fails with `InvalidData` if the variable isn't valid unicode.
+    pub fn from_env(var: &str) -> Result<Option<Self>> {
+        match env::var(var) {
+            Ok(list) => SigSet::from_str_list(&list).map(Some),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(err) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", var, err),
+            )),
+        }
+    }
+
+    /// Adds `signal` to the set.
+    ///
+    /// Fails with an [`InvalidSignal`] error if `signal` isn't a valid signal
+    /// number.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        let signal = signal.into();
+        if unsafe { libc::sigaddset(&mut self.0, signal) } < 0 {
+            return Err(InvalidSignal::new(signal).into());
+        }
+        Ok(())
+    }
+
+    /// Removes `signal` from the set.
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        let signal = signal.into();
+        if unsafe { libc::sigdelset(&mut self.0, signal) } < 0 {
+            return Err(InvalidSignal::new(signal).into());
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `signal` is in the set.
+    pub fn contains<S: Into<i32>>(&self, signal: S) -> bool {
+        unsafe { libc::sigismember(&self.0, signal.into()) == 1 }
+    }
+
+    /// Adds every signal to the set.
+    pub fn fill(&mut self) {
+        unsafe { libc::sigfillset(&mut self.0) };
+    }
+
+    /// Removes every signal from the set.
+    pub fn clear(&mut self) {
+        unsafe { libc::sigemptyset(&mut self.0) };
+    }
+
+    /// Returns `true` if the set has no signals.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Returns the calling thread's signal mask, the set of signals it has
+    /// blocked.
+    pub fn blocked() -> Result<Self> {
+        let mut set = SigSet::empty();
+        let rv =
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), set.as_raw_mut()) };
+        if rv != 0 {
+            return Err(io::Error::from_raw_os_error(rv));
+        }
+        Ok(set)
+    }
+
+    /// Blocks the signals in the set on the calling thread, returning the
+    /// mask from before.
+    ///
+    /// Threads inherit the mask of the thread starting them, so calling this
+    /// first thing in `main` covers every thread started afterwards,
+    /// including a runtime's.
+    pub fn block(&self) -> Result<Self> {
+        let mut old = SigSet::empty();
+        let rv = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &self.0, old.as_raw_mut()) };
+        if rv != 0 {
+            return Err(io::Error::from_raw_os_error(rv));
+        }
+        Ok(old)
+    }
+
+    /// Returns the signals pending for the calling thread or for the process:
+    /// raised while blocked, and not yet received or delivered.
+    ///
+    /// A signal that stays pending, such as a `SIGCHLD` nobody reads, hints
+    /// at a stalled consumer.
+    pub fn pending() -> Result<Self> {
+        let mut set = SigSet::empty();
+        if unsafe { libc::sigpending(set.as_raw_mut()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(set)
+    }
+
+    /// Iterates over the signals in the set, in ascending order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { set: self, next: 1 }
+    }
+
+    pub(crate) fn as_raw(&self) -> &libc::sigset_t {
+        &self.0
+    }
+
+    pub(crate) fn as_raw_mut(&mut self) -> &mut libc::sigset_t {
+        &mut self.0
+    }
+}
+
+/// Parses a list of signals, see [`SigSet::from_str_list`].
+impl FromStr for SigSet {
+    type Err = io::Error;
+
+    fn from_str(list: &str) -> Result<Self> {
+        SigSet::from_str_list(list)
+    }
+}
+
+impl Default for SigSet {
+    fn default() -> Self {
+        SigSet::empty()
+    }
+}
+
+impl fmt::Debug for SigSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a SigSet {
+    type Item = Signal;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the signals in a [`SigSet`].
+pub struct Iter<'a> {
+    set: &'a SigSet,
+    next: i32,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Signal;
+
+    fn next(&mut self) -> Option<Signal> {
+        while self.next <= sigrtmax() {
+            let signo = self.next;
+            self.next += 1;
+            if self.set.contains(signo) {
+                return Some(Signal::from_raw(signo));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "nix")]
+impl From<nix::sys::signal::SigSet> for SigSet {
+    fn from(set: nix::sys::signal::SigSet) -> SigSet {
+        SigSet(*set.as_ref())
+    }
+}
+
+#[cfg(feature = "nix")]
+impl From<SigSet> for nix::sys::signal::SigSet {
+    fn from(set: SigSet) -> nix::sys::signal::SigSet {
+        // Every `sigset_t` we hold was initialized by `sigemptyset(3)`.
+        unsafe { nix::sys::signal::SigSet::from_sigset_t_unchecked(set.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_remove_contains() {
+        let mut set = SigSet::empty();
+        assert!(set.is_empty());
+        set.add(libc::SIGHUP).unwrap();
+        set.add(Signal::Term).unwrap();
+        assert!(set.contains(Signal::Hup));
+        assert!(!set.contains(libc::SIGINT));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [Signal::Hup, Signal::Term]);
+        set.remove(libc::SIGHUP).unwrap();
+        assert_eq!(set.iter().collect::<Vec<_>>(), [Signal::Term]);
+        assert!(set.add(0).is_err());
+
+        let mut set = SigSet::full();
+        set.remove(Signal::Prof).unwrap();
+        assert!(set.contains(Signal::Usr1));
+        assert!(!set.contains(Signal::Prof));
+        set.clear();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn presets() {
+        assert!(SigSet::terminating().contains(Signal::Term));
+        assert!(!SigSet::terminating().contains(Signal::Usr1));
+        let catchable = SigSet::catchable();
+        assert!(!catchable.contains(Signal::Kill) && !catchable.contains(Signal::Stop));
+        assert!(catchable.contains(Signal::Chld));
+        assert_eq!(SigSet::job_control().iter().count(), 4);
+    }
+
+    #[test]
+    fn parse_list() {
+        let set = SigSet::from_str_list("TERM, hup\tSIGUSR1,,").unwrap();
+        let signals: Vec<_> = set.iter().collect();
+        let mut expected = vec![Signal::Hup, Signal::Usr1, Signal::Term];
+        expected.sort_by_key(|signal| signal.as_raw());
+        assert_eq!(signals, expected);
+        let err = "TERM,BOGUS".parse::<SigSet>().unwrap_err();
+        assert!(err.to_string().contains("BOGUS"));
+        assert!(SigSet::from_env("TOKIO_SIGNALFD_TEST_UNSET")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn blocked_mask() {
+        let set = SigSet::from_signals(&[libc::SIGUSR2]).unwrap();
+        assert!(!SigSet::blocked().unwrap().contains(libc::SIGUSR2));
+        unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, set.as_raw(), std::ptr::null_mut()) };
+        assert!(SigSet::blocked().unwrap().contains(libc::SIGUSR2));
+        unsafe { libc::pthread_sigmask(libc::SIG_UNBLOCK, set.as_raw(), std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn pending_signals() {
+        let set = SigSet::from_signals(&[libc::SIGWINCH]).unwrap();
+        unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, set.as_raw(), std::ptr::null_mut()) };
+        unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGWINCH) };
+        assert!(SigSet::pending().unwrap().contains(libc::SIGWINCH));
+        let mut signo = 0;
+        assert_eq!(unsafe { libc::sigwait(set.as_raw(), &mut signo) }, 0);
+        assert_eq!(signo, libc::SIGWINCH);
+        assert!(!SigSet::pending().unwrap().contains(libc::SIGWINCH));
+        unsafe { libc::pthread_sigmask(libc::SIG_UNBLOCK, set.as_raw(), std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,219 @@
+//! A common receiving interface, and a source signals are injected into.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Result};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::siginfo::{Received, SignalInfo};
+
+/// Something signals are received from.
+///
+/// Implemented by the async signalfd types and by [`MockSignalSource`], so
+/// that code reacting to signals can be written once against this trait and
+/// unit-tested without raising real signals:
+///
+/// ```
+/// use std::io;
+/// use tokio_signalfd::{MockSignalSource, SignalSource, SIGTERM};
+///
+/// async fn shutdown<S: SignalSource>(signals: &mut S) -> io::Result<i32> {
+///     Ok(signals.recv().await?.signo())
+/// }
+///
+/// let mut signals = MockSignalSource::new();
+/// signals.sender().send(SIGTERM);
+/// # let rt = tokio1::runtime::Builder::new_current_thread().build().unwrap();
+/// # rt.block_on(async {
+/// assert_eq!(shutdown(&mut signals).await?, SIGTERM);
+/// # Ok::<(), io::Error>(())
+/// # }).unwrap();
+/// ```
+pub trait SignalSource {
+    /// Polls for the next signal.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>>;
+
+    /// Waits for the next signal.
+    ///
+    /// Cancelling the returned future is safe as long as `poll_recv` only
+    /// takes a signal when returning it, as the ones of this crate do.
+    fn recv(&mut self) -> Recv<'_, Self>
+    where
+        Self: Sized,
+    {
+        Recv { source: self }
+    }
+}
+
+impl<S: SignalSource + ?Sized> SignalSource for &mut S {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        (**self).poll_recv(cx)
+    }
+}
+
+impl<S: SignalSource + ?Sized> SignalSource for Box<S> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        (**self).poll_recv(cx)
+    }
+}
+
+/// The future returned by [`SignalSource::recv`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<'a, S: ?Sized> {
+    source: &'a mut S,
+}
+
+impl<S: SignalSource + ?Sized> Future for Recv<'_, S> {
+    type Output = Result<SignalInfo>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.source.poll_recv(cx)
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<Result<SignalInfo>>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// A [`SignalSource`] yielding whatever is handed to its [`MockSender`]s,
+/// in order.
+///
+/// Nothing about the process signal state is touched. Once every event was
+/// received and [`MockSender::close`] was called, `poll_recv` fails with
+/// `BrokenPipe` and the stream ends.
+#[derive(Default)]
+pub struct MockSignalSource {
+    queue: Arc<Mutex<Queue>>,
+}
+
+/// Injects events into a [`MockSignalSource`]; cheap to clone.
+#[derive(Clone)]
+pub struct MockSender {
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl MockSignalSource {
+    /// Creates a source with nothing to receive yet.
+    pub fn new() -> Self {
+        MockSignalSource::default()
+    }
+
+    /// Returns a handle injecting events into this source.
+    pub fn sender(&self) -> MockSender {
+        MockSender {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Returns the next event if one was injected, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.queue.lock().unwrap().events.pop_front().transpose()
+    }
+
+    /// Polls for the next event, resolving to `None` once closed and
+    /// drained.
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<SignalInfo>>> {
+        let mut queue = self.queue.lock().unwrap();
+        match queue.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if queue.closed => Poll::Ready(None),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl SignalSource for MockSignalSource {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        self.poll_event(cx).map(|event| {
+            event.unwrap_or_else(|| {
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the mock signal source was closed",
+                ))
+            })
+        })
+    }
+}
+
+/// Ends once closed and drained.
+#[cfg(feature = "futures-03")]
+impl futures_core::Stream for MockSignalSource {
+    type Item = Result<SignalInfo>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_event(cx)
+    }
+}
+
+impl MockSender {
+    /// Queues `signal` as if it had been sent with `kill(2)`, carrying only
+    /// its number.
+    pub fn send<S: Into<i32>>(&self, signal: S) {
+        self.send_info(SignalInfo::from_signo(signal.into()).received(Received::now()));
+    }
+
+    /// Queues `info` as it is.
+    pub fn send_info(&self, info: SignalInfo) {
+        self.push(Ok(info));
+    }
+
+    /// Queues an error, returned in place of a signal.
+    pub fn fail(&self, err: io::Error) {
+        self.push(Err(err));
+    }
+
+    /// Ends the source after the events queued so far.
+    pub fn close(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn push(&self, event: Result<SignalInfo>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.events.push_back(event);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_yields_injected_events() {
+        let mut source = MockSignalSource::new();
+        let sender = source.sender();
+        assert!(source.try_recv().unwrap().is_none());
+        sender.send(libc::SIGHUP);
+        sender.fail(io::Error::from(io::ErrorKind::Other));
+        sender.send(libc::SIGTERM);
+        sender.close();
+
+        let mut source: Box<dyn SignalSource> = Box::new(source);
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut next = || match source.poll_recv(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("nothing queued"),
+        };
+        assert_eq!(next().unwrap().signo(), libc::SIGHUP);
+        assert_eq!(next().unwrap_err().kind(), io::ErrorKind::Other);
+        let info = next().unwrap();
+        assert_eq!(info.signo(), libc::SIGTERM);
+        assert!(info.received_at().is_some());
+        assert_eq!(next().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,139 @@
+//! Signal handlers installed through `signal-hook-registry`, for signals that
+//! other code in the process already handles.
+//!
+//! Instead of blocking the signals, each one gets a registry action writing a
+//! record to a pipe, whose read end serves as the descriptor. The registry
+//! chains actions, so handlers installed before, by hand or through
+//! signal-hook, keep running as well.
+use std::io::{self, Result};
+use std::os::unix::io::RawFd;
+use std::sync::Mutex;
+
+use signal_hook_registry::SigId;
+
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+
+struct Hooks {
+    write_end: RawFd,
+    ids: Vec<(i32, SigId)>,
+}
+
+/// The actions of open descriptors, by read end.
+static HOOKS: Mutex<Vec<(RawFd, Hooks)>> = Mutex::new(Vec::new());
+
+pub(crate) fn open(set: &SigSet) -> Result<RawFd> {
+    // Both ends are non-blocking: the handler drops records rather than
+    // wait for a full pipe to be read.
+    let [read_end, write_end] = super::pipe()?;
+    let mut hooks = Hooks {
+        write_end,
+        ids: Vec::new(),
+    };
+    for signal in set.iter().map(i32::from) {
+        if let Err(err) = hooks.register(signal) {
+            hooks.unregister_all();
+            unsafe {
+                libc::close(read_end);
+                libc::close(write_end);
+            }
+            return Err(err);
+        }
+    }
+    HOOKS.lock().unwrap().push((read_end, hooks));
+    Ok(read_end)
+}
+
+impl Hooks {
+    fn register(&mut self, signal: i32) -> Result<()> {
+        let write_end = self.write_end;
+        let id = unsafe {
+            signal_hook_registry::register_sigaction(signal, move |info| {
+                // Records are smaller than PIPE_BUF, so they are written
+                // whole, and nothing here allocates or takes locks.
+                let record = SignalInfo::from_siginfo(info).to_bytes();
+                libc::write(write_end, record.as_ptr() as *const _, record.len());
+            })
+        }?;
+        self.ids.push((signal, id));
+        Ok(())
+    }
+
+    fn unregister(&mut self, signal: i32) {
+        self.ids.retain(|&(registered, id)| {
+            registered != signal || !signal_hook_registry::unregister(id)
+        });
+    }
+
+    fn unregister_all(&mut self) {
+        for (_, id) in self.ids.drain(..) {
+            signal_hook_registry::unregister(id);
+        }
+    }
+}
+
+pub(crate) fn update(fd: RawFd, old: &SigSet, new: &SigSet) -> Result<()> {
+    let mut all = HOOKS.lock().unwrap();
+    let hooks = all
+        .iter_mut()
+        .find(|(read_end, _)| *read_end == fd)
+        .map(|(_, hooks)| hooks)
+        .ok_or_else(|| io::Error::from_raw_os_error(libc::EBADF))?;
+    for signal in new.iter().filter(|&signal| !old.contains(signal)) {
+        hooks.register(signal.into())?;
+    }
+    for signal in old.iter().filter(|&signal| !new.contains(signal)) {
+        hooks.unregister(signal.into());
+    }
+    Ok(())
+}
+
+pub(crate) fn release(fd: RawFd) {
+    let mut all = HOOKS.lock().unwrap();
+    if let Some(index) = all.iter().position(|(read_end, _)| *read_end == fd) {
+        let (_, mut hooks) = all.swap_remove(index);
+        // Unregistering waits for running actions, so the write end is no
+        // longer in use once it returns.
+        hooks.unregister_all();
+        unsafe { libc::close(hooks.write_end) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn runs_alongside_handlers() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let existing = unsafe {
+            signal_hook_registry::register(libc::SIGPROF, move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+        }
+        .unwrap();
+
+        let set = SigSet::from_signals(&[libc::SIGPROF]).unwrap();
+        let fd = open(&set).unwrap();
+        unsafe { libc::raise(libc::SIGPROF) };
+        let mut record = [0; crate::siginfo::SIGINFO_SIZE];
+        assert_eq!(
+            super::super::read_fd(fd, &mut record).unwrap(),
+            record.len()
+        );
+        assert_eq!(SignalInfo::from_bytes(&record).signo(), libc::SIGPROF);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        update(fd, &set, &SigSet::empty()).unwrap();
+        unsafe { libc::raise(libc::SIGPROF) };
+        let err = super::super::read_fd(fd, &mut record).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        release(fd);
+        unsafe { libc::close(fd) };
+        signal_hook_registry::unregister(existing);
+    }
+}
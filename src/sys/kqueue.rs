@@ -0,0 +1,125 @@
+//! kqueue `EVFILT_SIGNAL`, for macOS and the BSDs.
+//!
+//! The filter only reports which signal arrived, so the records read from a
+//! kqueue carry nothing but the signal number. It also doesn't consume the
+//! signal: since the signals are blocked they would stay pending, and be
+//! delivered with their default action once unblocked. Every reported signal
+//! is therefore collected with `sigwait(3)` as well, provided it's pending for
+//! the reading thread or the process.
+
+use std::io::{self, Result};
+use std::os::unix::io::RawFd;
+use std::{mem, ptr};
+
+use crate::siginfo::{SignalInfo, SIGINFO_SIZE};
+use crate::sigset::SigSet;
+
+/// Maximum number of events collected by a single `kevent(2)`.
+const BATCH: usize = 16;
+
+fn event(signo: i32, add: bool) -> libc::kevent {
+    let mut event: libc::kevent = unsafe { mem::zeroed() };
+    event.ident = signo as _;
+    event.filter = libc::EVFILT_SIGNAL;
+    event.flags = if add { libc::EV_ADD } else { libc::EV_DELETE };
+    event
+}
+
+fn change(kq: RawFd, changes: &[libc::kevent]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let rv = unsafe {
+        libc::kevent(
+            kq,
+            changes.as_ptr(),
+            changes.len() as _,
+            ptr::null_mut(),
+            0,
+            ptr::null(),
+        )
+    };
+    if rv < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn open(set: &SigSet) -> Result<RawFd> {
+    let kq = unsafe { libc::kqueue() };
+    if kq < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { libc::fcntl(kq, libc::F_SETFD, libc::FD_CLOEXEC) };
+    let changes: Vec<_> = set
+        .iter()
+        .map(|signal| event(signal.as_raw(), true))
+        .collect();
+    if let Err(err) = change(kq, &changes) {
+        unsafe { libc::close(kq) };
+        return Err(err);
+    }
+    Ok(kq)
+}
+
+pub(crate) fn update(kq: RawFd, old: &SigSet, new: &SigSet) -> Result<()> {
+    let removed = old
+        .iter()
+        .filter(|&signal| !new.contains(signal))
+        .map(|signal| event(signal.as_raw(), false));
+    let added = new
+        .iter()
+        .filter(|&signal| !old.contains(signal))
+        .map(|signal| event(signal.as_raw(), true));
+    change(kq, &removed.chain(added).collect::<Vec<_>>())
+}
+
+/// Collects `signo` if it's pending, so that it isn't delivered later.
+fn consume(signo: i32) {
+    let mut pending = SigSet::empty();
+    if unsafe { libc::sigpending(pending.as_raw_mut()) } < 0 || !pending.contains(signo) {
+        return;
+    }
+    if let Ok(set) = SigSet::from_signals(&[signo]) {
+        let mut received = 0;
+        unsafe { libc::sigwait(set.as_raw(), &mut received) };
+    }
+}
+
+pub(crate) fn read(kq: RawFd, buf: &mut [u8]) -> Result<usize> {
+    let max = (buf.len() / SIGINFO_SIZE).min(BATCH);
+    if max == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer too small for a signal record",
+        ));
+    }
+    let mut events: [libc::kevent; BATCH] = unsafe { mem::zeroed() };
+    let timeout = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let count = loop {
+        let rv =
+            unsafe { libc::kevent(kq, ptr::null(), 0, events.as_mut_ptr(), max as _, &timeout) };
+        if rv >= 0 {
+            break rv as usize;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    };
+    if count == 0 {
+        return Err(io::ErrorKind::WouldBlock.into());
+    }
+    for (event, record) in events[..count]
+        .iter()
+        .zip(buf.chunks_exact_mut(SIGINFO_SIZE))
+    {
+        let signo = event.ident as i32;
+        consume(signo);
+        record.copy_from_slice(&SignalInfo::from_signo(signo).to_bytes());
+    }
+    Ok(count * SIGINFO_SIZE)
+}
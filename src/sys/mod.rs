@@ -0,0 +1,267 @@
+//! Kernel interfaces delivering signals through a pollable descriptor.
+//!
+//! Every engine provides the same operations, on a descriptor whose reads
+//! produce `signalfd_siginfo` records:
+//!
+//! * `open(set)` creates a non-blocking, close-on-exec descriptor receiving
+//!   the signals in `set`,
+//! * `update(fd, old, new)` changes the received signals from `old` to `new`,
+//! * `read(fd, buf)` reads as many whole records as fit in `buf`, failing with
+//!   `WouldBlock` when none are pending,
+//! * `release(fd)` frees whatever the engine keeps for the descriptor, which
+//!   its owner closes right after.
+//!
+//! The native engine is signalfd on Linux and kqueue on macOS and the BSDs,
+//! as chosen by the build script. The sigwait thread is compiled in with the
+//! `sigwait-thread` feature, and is the only engine everywhere else. The
+//! signal-hook engine, with the `signal-hook` feature, is never picked
+//! automatically.
+
+use std::io::{self, Result};
+use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+
+use crate::sigset::SigSet;
+
+#[cfg(feature = "signal-hook")]
+mod hook;
+#[cfg(kqueue)]
+mod kqueue;
+#[cfg(signalfd)]
+mod signalfd;
+#[cfg(sigwait)]
+mod sigwait;
+
+/// The mechanism signals are received through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Linux `signalfd(2)`.
+    Signalfd,
+    /// kqueue `EVFILT_SIGNAL`, on macOS and the BSDs.
+    ///
+    /// Only the signal number of received signals is available.
+    Kqueue,
+    /// A dedicated thread waiting for the signals with `sigtimedwait(2)`.
+    ///
+    /// It only sees signals sent to the process or to the thread itself, not
+    /// ones directed at another particular thread.
+    SigwaitThread,
+    /// Handlers installed through `signal-hook-registry`, alongside any
+    /// other handlers for the same signals.
+    ///
+    /// The signals aren't blocked, so previously installed handlers keep
+    /// running. Requires the `signal-hook` feature.
+    SignalHook,
+}
+
+impl Backend {
+    /// Returns the platform's native backend, if it has one.
+    pub fn native() -> Option<Backend> {
+        if cfg!(signalfd) {
+            Some(Backend::Signalfd)
+        } else if cfg!(kqueue) {
+            Some(Backend::Kqueue)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the backend is compiled in.
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Signalfd => cfg!(signalfd),
+            Backend::Kqueue => cfg!(kqueue),
+            Backend::SigwaitThread => cfg!(sigwait),
+            Backend::SignalHook => cfg!(feature = "signal-hook"),
+        }
+    }
+}
+
+fn unsupported(backend: Backend) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the {:?} backend is not available", backend),
+    )
+}
+
+/// Opens a descriptor for `set` with `backend`, or with the best available
+/// backend when `None`.
+///
+/// When the native backend is refused by the kernel, as it is by seccomp
+/// filters that don't allow its syscalls, the sigwait thread is used instead.
+pub(crate) fn open(set: &SigSet, backend: Option<Backend>) -> Result<(OwnedFd, Backend)> {
+    if let Some(backend) = backend {
+        return Ok((open_with(backend, set)?, backend));
+    }
+    let native = match Backend::native() {
+        Some(native) => native,
+        None => {
+            return Ok((
+                open_with(Backend::SigwaitThread, set)?,
+                Backend::SigwaitThread,
+            ))
+        }
+    };
+    match open_with(native, set) {
+        Ok(fd) => Ok((fd, native)),
+        Err(err)
+            if Backend::SigwaitThread.is_available()
+                && matches!(err.raw_os_error(), Some(libc::ENOSYS) | Some(libc::EPERM)) =>
+        {
+            Ok((
+                open_with(Backend::SigwaitThread, set)?,
+                Backend::SigwaitThread,
+            ))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn open_with(backend: Backend, set: &SigSet) -> Result<OwnedFd> {
+    let fd = match backend {
+        #[cfg(signalfd)]
+        Backend::Signalfd => signalfd::open(set),
+        #[cfg(kqueue)]
+        Backend::Kqueue => kqueue::open(set),
+        #[cfg(sigwait)]
+        Backend::SigwaitThread => sigwait::open(set),
+        #[cfg(feature = "signal-hook")]
+        Backend::SignalHook => hook::open(set),
+        #[allow(unreachable_patterns)]
+        _ => Err(unsupported(backend)),
+    }?;
+    // Every engine hands over a descriptor nothing else owns.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+pub(crate) fn update(backend: Backend, fd: RawFd, old: &SigSet, new: &SigSet) -> Result<()> {
+    match backend {
+        #[cfg(signalfd)]
+        Backend::Signalfd => signalfd::update(fd, old, new),
+        #[cfg(kqueue)]
+        Backend::Kqueue => kqueue::update(fd, old, new),
+        #[cfg(sigwait)]
+        Backend::SigwaitThread => sigwait::update(fd, old, new),
+        #[cfg(feature = "signal-hook")]
+        Backend::SignalHook => hook::update(fd, old, new),
+        #[allow(unreachable_patterns)]
+        _ => Err(unsupported(backend)),
+    }
+}
+
+pub(crate) fn read(backend: Backend, fd: RawFd, buf: &mut [u8]) -> Result<usize> {
+    match backend {
+        #[cfg(kqueue)]
+        Backend::Kqueue => kqueue::read(fd, buf),
+        _ => read_fd(fd, buf),
+    }
+}
+
+#[cfg_attr(not(any(sigwait, feature = "signal-hook")), allow(unused_variables))]
+pub(crate) fn release(backend: Backend, fd: RawFd) {
+    match backend {
+        #[cfg(sigwait)]
+        Backend::SigwaitThread => sigwait::release(fd),
+        #[cfg(feature = "signal-hook")]
+        Backend::SignalHook => hook::release(fd),
+        _ => {}
+    }
+}
+
+/// Reads from `fd`, retrying reads interrupted by a signal handler.
+fn read_fd(fd: RawFd, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        let rv = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut std::ffi::c_void, buf.len()) };
+        if rv >= 0 {
+            return Ok(rv as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Creates a pipe whose ends are both non-blocking and close-on-exec, for
+/// the engines writing records into one.
+#[cfg(any(sigwait, feature = "signal-hook"))]
+fn pipe() -> Result<[RawFd; 2]> {
+    let mut fds = [0; 2];
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+    ))]
+    {
+        let flags = libc::O_CLOEXEC | libc::O_NONBLOCK;
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fds)
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+    )))]
+    {
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let configured = fds.iter().all(|&fd| unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) >= 0
+                && flags >= 0
+                && libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) >= 0
+        });
+        if !configured {
+            let err = io::Error::last_os_error();
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+            return Err(err);
+        }
+        Ok(fds)
+    }
+}
+
+#[cfg(all(test, signalfd))]
+mod tests {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn select_backend() {
+        let set = SigSet::from_signals(&[libc::SIGUSR1]).unwrap();
+        let (fd, backend) = open(&set, None).unwrap();
+        assert_eq!(backend, Backend::Signalfd);
+        release(backend, fd.as_raw_fd());
+
+        let err = open(&set, Some(Backend::Kqueue)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(!Backend::Kqueue.is_available());
+    }
+
+    #[test]
+    #[cfg(any(sigwait, feature = "signal-hook"))]
+    fn pipe_flags() {
+        for fd in pipe().unwrap() {
+            unsafe {
+                assert_ne!(libc::fcntl(fd, libc::F_GETFD) & libc::FD_CLOEXEC, 0);
+                assert_ne!(libc::fcntl(fd, libc::F_GETFL) & libc::O_NONBLOCK, 0);
+                libc::close(fd);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Linux `signalfd(2)`.
+
+use std::io::{self, Result};
+use std::os::unix::io::RawFd;
+
+use crate::sigset::SigSet;
+
+pub(crate) fn open(set: &SigSet) -> Result<RawFd> {
+    let fd = unsafe { libc::signalfd(-1, set.as_raw(), libc::SFD_NONBLOCK | libc::SFD_CLOEXEC) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        // Kernels before 2.6.27 have no flags argument, and some seccomp
+        // sandboxes only allow it to be zero.
+        if err.raw_os_error() == Some(libc::EINVAL) {
+            return open_without_flags(set);
+        }
+        return Err(err);
+    }
+    Ok(fd)
+}
+
+/// Creates the descriptor with plain `signalfd`, setting its flags after the
+/// fact.
+///
+/// The descriptor leaks into processes spawned by other threads in between.
+fn open_without_flags(set: &SigSet) -> Result<RawFd> {
+    let fd = unsafe { libc::signalfd(-1, set.as_raw(), 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0
+        || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0
+        || unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0
+    {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(err);
+    }
+    Ok(fd)
+}
+
+pub(crate) fn update(fd: RawFd, _old: &SigSet, new: &SigSet) -> Result<()> {
+    if unsafe { libc::signalfd(fd, new.as_raw(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_without_flags_argument() {
+        let set = SigSet::from_signals(&[libc::SIGUSR1]).unwrap();
+        let fd = open_without_flags(&set).unwrap();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        assert_ne!(flags & libc::O_NONBLOCK, 0);
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, libc::FD_CLOEXEC);
+        unsafe { libc::close(fd) };
+    }
+}
@@ -0,0 +1,158 @@
+//! A dedicated thread collecting signals with `sigtimedwait(2)`, for platforms
+//! without signalfd or kqueue and as a fallback where those are refused.
+//!
+//! The thread has every signal blocked, waits for the ones in the set and writes a
+//! record for each of them to a pipe, whose read end serves as the descriptor.
+//! It only sees signals directed at the process or at itself, not ones sent
+//! to a particular other thread, such as with `raise(3)`. The wait times out
+//! regularly so that set changes and closing are noticed.
+use std::io::{self, Result};
+use std::os::unix::io::RawFd;
+use std::os::unix::thread::JoinHandleExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::siginfo::SignalInfo;
+use crate::sigset::SigSet;
+
+extern "C" {
+    // Not bound by `libc` on every platform this engine is used on.
+    fn sigtimedwait(
+        set: *const libc::sigset_t,
+        info: *mut libc::siginfo_t,
+        timeout: *const libc::timespec,
+    ) -> libc::c_int;
+}
+
+/// How long the thread waits before looking at the set again.
+const POLL_INTERVAL_NS: libc::c_long = 50_000_000;
+
+struct Shared {
+    set: Mutex<SigSet>,
+    closed: AtomicBool,
+    #[cfg_attr(not(all(test, signalfd)), allow(dead_code))]
+    thread: libc::pthread_t,
+}
+
+/// The threads serving open descriptors, by read end.
+static THREADS: Mutex<Vec<(RawFd, Arc<Shared>)>> = Mutex::new(Vec::new());
+
+fn shared(fd: RawFd) -> Option<Arc<Shared>> {
+    let threads = THREADS.lock().unwrap();
+    threads
+        .iter()
+        .find(|(read_end, _)| *read_end == fd)
+        .map(|(_, shared)| shared.clone())
+}
+
+pub(crate) fn open(set: &SigSet) -> Result<RawFd> {
+    let [read_end, write_end] = super::pipe()?;
+
+    let set = Mutex::new(*set);
+    let closed = AtomicBool::new(false);
+    let (tx, rx) = std::sync::mpsc::channel::<Arc<Shared>>();
+    // The thread must start out with every signal blocked, or a signal could
+    // reach it before it's ready to wait for it.
+    let all = SigSet::full();
+    let mut mask = SigSet::empty();
+    unsafe { libc::pthread_sigmask(libc::SIG_SETMASK, all.as_raw(), mask.as_raw_mut()) };
+    let spawned = thread::Builder::new()
+        .name("signalfd-sigwait".into())
+        .spawn(move || {
+            if let Ok(shared) = rx.recv() {
+                run(&shared, write_end);
+            }
+            unsafe { libc::close(write_end) };
+        });
+    unsafe { libc::pthread_sigmask(libc::SIG_SETMASK, mask.as_raw(), std::ptr::null_mut()) };
+    let handle = match spawned {
+        Ok(handle) => handle,
+        Err(err) => {
+            unsafe {
+                libc::close(read_end);
+                libc::close(write_end);
+            }
+            return Err(err);
+        }
+    };
+    let shared = Arc::new(Shared {
+        set,
+        closed,
+        thread: handle.as_pthread_t() as libc::pthread_t,
+    });
+    THREADS.lock().unwrap().push((read_end, shared.clone()));
+    let _ = tx.send(shared);
+    Ok(read_end)
+}
+
+fn run(shared: &Shared, write_end: RawFd) {
+    let timeout = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: POLL_INTERVAL_NS,
+    };
+    while !shared.closed.load(Ordering::Acquire) {
+        let set = *shared.set.lock().unwrap();
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { sigtimedwait(set.as_raw(), &mut info, &timeout) } < 0 {
+            continue;
+        }
+        // Records are smaller than PIPE_BUF, so they are written whole.
+        // While the pipe is full they are dropped, as by the signal-hook
+        // handlers, rather than stall the thread until it is read.
+        let record = SignalInfo::from_siginfo(&info).to_bytes();
+        let rv = unsafe { libc::write(write_end, record.as_ptr() as *const _, record.len()) };
+        if rv < 0 {
+            match io::Error::last_os_error().kind() {
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => {}
+                // The read end was closed.
+                _ => return,
+            }
+        }
+    }
+}
+
+pub(crate) fn update(fd: RawFd, _old: &SigSet, new: &SigSet) -> Result<()> {
+    let shared = shared(fd).ok_or_else(|| io::Error::from_raw_os_error(libc::EBADF))?;
+    *shared.set.lock().unwrap() = *new;
+    Ok(())
+}
+
+pub(crate) fn release(fd: RawFd) {
+    let mut threads = THREADS.lock().unwrap();
+    if let Some(index) = threads.iter().position(|(read_end, _)| *read_end == fd) {
+        let (_, shared) = threads.swap_remove(index);
+        shared.closed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, signalfd))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receives_signals() {
+        let set = SigSet::from_signals(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+        let fd = open(&set).unwrap();
+        let thread = shared(fd).unwrap().thread;
+        update(fd, &set, &SigSet::from_signals(&[libc::SIGUSR2]).unwrap()).unwrap();
+        unsafe { libc::pthread_kill(thread, libc::SIGUSR2) };
+
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        assert_eq!(unsafe { libc::poll(&mut pollfd, 1, 5000) }, 1);
+        let mut record = [0; crate::siginfo::SIGINFO_SIZE];
+        assert_eq!(
+            super::super::read_fd(fd, &mut record).unwrap(),
+            record.len()
+        );
+        let info = SignalInfo::from_bytes(&record);
+        assert_eq!(info.signo(), libc::SIGUSR2);
+        assert_eq!(info.pid(), unsafe { libc::getpid() } as u32);
+        release(fd);
+        unsafe { libc::close(fd) };
+    }
+}
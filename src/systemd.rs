@@ -0,0 +1,90 @@
+//! Reporting service state to systemd with `sd_notify(3)`.
+//!
+//! Messages go to the datagram socket named by `$NOTIFY_SOCKET`, which
+//! systemd sets for services of `Type=notify`. Every function here returns
+//! `Ok(false)` without doing anything when the variable isn't set, so they
+//! can be called unconditionally.
+
+use std::ffi::OsStr;
+use std::io::{self, Result};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Sends `state`, a newline-separated list of assignments such as
+/// `READY=1`, to the service manager.
+pub fn notify(state: &str) -> Result<bool> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => send(&socket, state).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+fn send(socket: &OsStr, state: &str) -> Result<()> {
+    let addr = match socket.as_bytes() {
+        [b'@', name @ ..] => SocketAddr::from_abstract_name(name)?,
+        [b'/', ..] => SocketAddr::from_pathname(socket)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported NOTIFY_SOCKET {:?}", socket),
+            ))
+        }
+    };
+    let sender = UnixDatagram::unbound()?;
+    sender.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// Reports that the service is shutting down (`STOPPING=1`).
+///
+/// With a `timeout`, systemd is also asked to wait that long for the
+/// shutdown to finish (`EXTEND_TIMEOUT_USEC`) before it resorts to
+/// `SIGKILL`, even if that is longer than the unit's `TimeoutStopSec`.
+pub fn notify_stopping(timeout: Option<Duration>) -> Result<bool> {
+    match timeout {
+        Some(timeout) => notify(&format!(
+            "STOPPING=1\nEXTEND_TIMEOUT_USEC={}",
+            timeout.as_micros()
+        )),
+        None => notify("STOPPING=1"),
+    }
+}
+
+/// Sends a watchdog keepalive (`WATCHDOG=1`).
+pub fn notify_watchdog() -> Result<bool> {
+    notify("WATCHDOG=1")
+}
+
+/// Returns how often the service has to send keepalives, if systemd's
+/// watchdog is enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_to_socket() {
+        let path = std::env::temp_dir().join(format!("signalfd-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        send(path.as_os_str(), "STOPPING=1").unwrap();
+        let mut buf = [0; 64];
+        let count = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..count], b"STOPPING=1");
+        std::fs::remove_file(&path).unwrap();
+
+        let err = send(OsStr::new("relative"), "READY=1").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
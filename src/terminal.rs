@@ -0,0 +1,75 @@
+//! Ending signal streams on a terminating signal.
+
+use crate::sigset::SigSet;
+
+/// A signal stream that ends after yielding the first signal from a set of
+/// terminating ones.
+///
+/// Created by the streams' `take_until_terminal` methods, so that loops over
+/// the stream finish on their own at shutdown:
+///
+/// ```no_run
+/// # #[cfg(feature = "tokio-1")]
+/// # async fn run() -> std::io::Result<()> {
+/// use futures_util::StreamExt;
+/// use tokio_signalfd::{tokio::SignalFd, SigSet, Signal};
+///
+/// let terminal = SigSet::from_signals(&[Signal::Int, Signal::Term])?;
+/// let mut signals = SignalFd::new(&[Signal::Hup, Signal::Int, Signal::Term])?
+///     .signal_stream()
+///     .take_until_terminal(&terminal);
+/// while let Some(signal) = signals.next().await {
+///     println!("received {:?}", signal?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The terminating signal itself is yielded before the stream ends. Errors
+/// don't end it.
+pub struct TakeUntilTerminal<S> {
+    pub(crate) stream: S,
+    pub(crate) terminal: SigSet,
+    pub(crate) done: bool,
+}
+
+impl<S> TakeUntilTerminal<S> {
+    pub(crate) fn new(stream: S, terminal: &SigSet) -> Self {
+        TakeUntilTerminal {
+            stream,
+            terminal: *terminal,
+            done: false,
+        }
+    }
+
+    /// Records a received signal, ending the stream if it is terminating.
+    pub(crate) fn observe(&mut self, signo: i32) {
+        self.done = self.terminal.contains(signo);
+    }
+
+    /// Returns whether a terminating signal has been received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Returns the set of signals ending the stream.
+    pub fn terminal(&self) -> &SigSet {
+        &self.terminal
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the underlying stream, which keeps yielding signals past the
+    /// terminating one.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
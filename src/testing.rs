@@ -0,0 +1,204 @@
+//! Helpers for testing code that reacts to signals.
+//!
+//! Signals are sent to the thread that sets them up, with
+//! `pthread_kill(3)`, rather than to the whole process. The test harness
+//! runs tests on several threads at once, and a process-directed signal may
+//! be delivered to any of them that hasn't got it blocked, usually killing
+//! the process. A thread-directed one is only ever received by a signalfd
+//! created on the targeted thread, so read it there: on the test thread, or
+//! on a current-thread runtime started on it.
+//!
+//! ```
+//! use std::time::Duration;
+//! use tokio_signalfd::blocking::SignalFd;
+//! use tokio_signalfd::testing::{assert_received, Script};
+//! use tokio_signalfd::{SIGHUP, SIGTERM};
+//!
+//! let mut signals = SignalFd::new(&[SIGHUP, SIGTERM])?;
+//! let script = Script::new()
+//!     .send(SIGHUP)
+//!     .wait(Duration::from_millis(10))
+//!     .send(SIGTERM)
+//!     .spawn();
+//! let received = [signals.recv()?, signals.recv()?];
+//! script.join().unwrap()?;
+//! assert_received(&received, &[SIGHUP, SIGTERM]);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io::Result;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::siginfo::SignalInfo;
+use crate::signal::{self, Signal};
+use crate::source::{MockSender, SignalSource};
+
+/// Sends `signal` to the calling thread once `delay` has passed.
+///
+/// The signal is sent from a helper thread, whose handle resolves to the
+/// outcome of sending it.
+pub fn raise_after<S: Into<i32>>(delay: Duration, signal: S) -> JoinHandle<Result<()>> {
+    Script::new().wait(delay).send(signal).spawn()
+}
+
+/// A sequence of signals with pauses in between.
+///
+/// A script either sends real signals to a thread, with [`run`](Script::run)
+/// and [`spawn`](Script::spawn), or feeds a [`MockSignalSource`] with
+/// [`play`](Script::play).
+///
+/// [`MockSignalSource`]: crate::MockSignalSource
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Send(i32),
+    Wait(Duration),
+}
+
+/// The calling thread, to be signalled from a helper thread.
+struct Target(libc::pthread_t);
+
+// `pthread_t` is a pointer on some platforms, but only ever used as an
+// identifier here.
+unsafe impl Send for Target {}
+
+impl Script {
+    /// Creates an empty script.
+    pub fn new() -> Self {
+        Script::default()
+    }
+
+    /// Appends sending `signal`.
+    pub fn send<S: Into<i32>>(mut self, signal: S) -> Self {
+        self.steps.push(Step::Send(signal.into()));
+        self
+    }
+
+    /// Appends a pause of `delay`.
+    pub fn wait(mut self, delay: Duration) -> Self {
+        self.steps.push(Step::Wait(delay));
+        self
+    }
+
+    /// Runs the script on the calling thread, sending the signals to it.
+    ///
+    /// They mustn't be handled with their default disposition, so have them
+    /// blocked, normally by creating the signalfd first.
+    pub fn run(&self) -> Result<()> {
+        self.run_on(&Target(unsafe { libc::pthread_self() }))
+    }
+
+    /// Runs the script on a helper thread, sending the signals to the
+    /// calling thread.
+    ///
+    /// The handle resolves to the first error sending a signal, which ends
+    /// the script.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        let target = Target(unsafe { libc::pthread_self() });
+        thread::spawn(move || self.run_on(&target))
+    }
+
+    /// Runs the script on a helper thread, queueing the signals on `sender`
+    /// instead of sending them.
+    pub fn play(self, sender: MockSender) -> JoinHandle<()> {
+        thread::spawn(move || {
+            for step in self.steps {
+                match step {
+                    Step::Send(signal) => sender.send(signal),
+                    Step::Wait(delay) => thread::sleep(delay),
+                }
+            }
+        })
+    }
+
+    fn run_on(&self, target: &Target) -> Result<()> {
+        for step in &self.steps {
+            match *step {
+                Step::Send(signal) => signal::send_to_pthread(target.0, signal)?,
+                Step::Wait(delay) => thread::sleep(delay),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Panics unless `received` holds exactly the `expected` signals, in order.
+///
+/// The message lists both sequences by name.
+#[track_caller]
+pub fn assert_received<S: Copy + Into<i32>>(received: &[SignalInfo], expected: &[S]) {
+    let received: Vec<Signal> = received.iter().map(|info| info.signo().into()).collect();
+    let expected: Vec<Signal> = expected
+        .iter()
+        .map(|&signal| signal.into().into())
+        .collect();
+    if received != expected {
+        panic!(
+            "received signals [{}], expected [{}]",
+            names(&received),
+            names(&expected)
+        );
+    }
+}
+
+/// Receives as many signals from `source` as there are `expected`, and
+/// panics unless they are those, in order.
+///
+/// Bound the wait with the runtime's timeout, e.g. `tokio::time::timeout`,
+/// so that a missing signal fails the test rather than hanging it.
+pub async fn expect_signals<R, S>(source: &mut R, expected: &[S]) -> Vec<SignalInfo>
+where
+    R: SignalSource,
+    S: Copy + Into<i32>,
+{
+    let mut received = Vec::with_capacity(expected.len());
+    while received.len() < expected.len() {
+        match source.recv().await {
+            Ok(info) => received.push(info),
+            Err(err) => panic!("receiving signal {}: {}", received.len() + 1, err),
+        }
+    }
+    assert_received(&received, expected);
+    received
+}
+
+fn names(signals: &[Signal]) -> String {
+    let names: Vec<String> = signals.iter().map(Signal::to_string).collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking;
+    use crate::source::MockSignalSource;
+
+    #[test]
+    fn raises_on_calling_thread() {
+        let mut signals = blocking::SignalFd::new(&[libc::SIGUSR1]).unwrap();
+        let raised = raise_after(Duration::from_millis(10), libc::SIGUSR1);
+        let received = signals.recv_timeout(Duration::from_secs(5)).unwrap();
+        raised.join().unwrap().unwrap();
+        assert_received(&received.into_iter().collect::<Vec<_>>(), &[libc::SIGUSR1]);
+    }
+
+    #[test]
+    fn plays_into_mock() {
+        let mut source = MockSignalSource::new();
+        let played = Script::new()
+            .send(Signal::Hup)
+            .wait(Duration::from_millis(10))
+            .send(Signal::Term)
+            .play(source.sender());
+        let rt = tokio1::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(expect_signals(&mut source, &[Signal::Hup, Signal::Term]));
+        played.join().unwrap();
+    }
+}
@@ -0,0 +1,1230 @@
+//! signalfd integration for tokio 1.x runtimes.
+//!
+//! The descriptor is registered with the runtime's reactor through
+//! [`AsyncFd`](tokio1::io::unix::AsyncFd), so [`SignalFd::new`] must be called
+//! from within a runtime context.
+
+use std::convert::TryFrom;
+use std::future::{poll_fn, Future};
+use std::io::Result;
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::pin::{pin, Pin};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio1::io::unix::AsyncFd;
+use tokio1::sync::watch;
+
+use crate::builder::Builder;
+use crate::debounce::Debounced;
+use crate::error::SignalFdError;
+use crate::raw::RawSignalFd;
+use crate::reload::Reloader;
+use crate::resize::ResizeEvents;
+use crate::siginfo::SignalInfo;
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::source::SignalSource;
+use crate::sys::Backend;
+use crate::terminal::TakeUntilTerminal;
+
+/// A stream of signals delivered through a signalfd registered with a tokio 1.x
+/// reactor.
+///
+/// The stream yields signal numbers by default; use
+/// [`signal_stream`](SignalFd::signal_stream) or
+/// [`info_stream`](SignalFd::info_stream) to receive typed [`Signal`]s or full
+/// [`SignalInfo`] records instead.
+///
+/// The stream never ends, and is [`Unpin`] whatever it yields, so it can be
+/// used with `futures::select!` as is.
+pub struct SignalFd<T = i32> {
+    io: AsyncFd<RawSignalFd>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl SignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    ///
+    /// Signals can be given either as raw numbers or as [`Signal`]s.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime with IO enabled.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        let inner = RawSignalFd::new(signals)?;
+        SignalFd::from_inner(inner)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        let inner = RawSignalFd::from_set(set)?;
+        SignalFd::from_inner(inner)
+    }
+
+    /// Like [`new`](SignalFd::new), but registers the descriptor with the
+    /// runtime behind `handle` instead of the current one, and so works
+    /// outside of a runtime too.
+    ///
+    /// The runtime needs IO enabled; its driver then wakes the stream
+    /// wherever it is polled.
+    pub fn new_with_handle<S: Copy + Into<i32>>(
+        handle: &tokio1::runtime::Handle,
+        signals: &[S],
+    ) -> Result<Self> {
+        let _enter = handle.enter();
+        SignalFd::new(signals)
+    }
+
+    /// Starts receiving the signals in `list`, separated by commas or
+    /// whitespace, e.g. `"TERM,HUP,USR1"`.
+    ///
+    /// See [`SigSet::from_str_list`].
+    pub fn from_str_list(list: &str) -> Result<Self> {
+        SignalFd::from_set(&SigSet::from_str_list(list)?)
+    }
+
+    /// Starts receiving the signals listed in the environment variable `var`,
+    /// or `default` if it isn't set, so that deployments can choose them.
+    ///
+    /// See [`SigSet::from_env`].
+    pub fn from_env<S: Copy + Into<i32>>(var: &str, default: &[S]) -> Result<Self> {
+        match SigSet::from_env(var)? {
+            Some(set) => SignalFd::from_set(&set),
+            None => SignalFd::new(default),
+        }
+    }
+
+    /// Starts receiving the signals asking the process to shut down, see
+    /// [`SigSet::terminating`].
+    pub fn terminating() -> Result<Self> {
+        SignalFd::from_set(&SigSet::terminating())
+    }
+
+    /// Starts receiving every signal that can be caught, see
+    /// [`SigSet::catchable`].
+    pub fn catchable() -> Result<Self> {
+        SignalFd::from_set(&SigSet::catchable())
+    }
+
+    /// Starts receiving the job control signals, see
+    /// [`SigSet::job_control`].
+    pub fn job_control() -> Result<Self> {
+        SignalFd::from_set(&SigSet::job_control())
+    }
+
+    /// Creates a signalfd receiving the signals `kinds` refer to.
+    ///
+    /// Together with [`signal`], this eases moving code over from
+    /// `tokio::signal::unix`.
+    #[cfg(feature = "tokio-signal-compat")]
+    pub fn from_kinds(kinds: &[tokio1::signal::unix::SignalKind]) -> Result<Self> {
+        let signals: Vec<Signal> = kinds.iter().map(|&kind| kind.into()).collect();
+        SignalFd::new(&signals)
+    }
+
+    /// Takes over a signalfd created by another process, such as a supervisor
+    /// or the process this one replaces, recovering the signals it receives.
+    ///
+    /// See [`RawSignalFd::adopt`].
+    pub fn adopt(fd: OwnedFd) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::adopt(fd)?)
+    }
+
+    /// Adopts the first signalfd passed with systemd's `LISTEN_FDS`
+    /// protocol, among those named `name` if given.
+    ///
+    /// See [`RawSignalFd::from_listen_fds`].
+    pub fn from_listen_fds(name: Option<&str>) -> Result<Option<Self>> {
+        RawSignalFd::from_listen_fds(name)?
+            .map(SignalFd::from_inner)
+            .transpose()
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(SignalFd::from_inner)
+    }
+}
+
+impl<T> SignalFd<T> {
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        Ok(SignalFd {
+            io: AsyncFd::new(inner).map_err(SignalFdError::Register)?,
+            _item: PhantomData,
+        })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.io.get_ref().signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.io.get_ref().backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.io.get_ref().previous_mask()
+    }
+
+    /// Gives up the descriptor without closing it, e.g. to hand it to another
+    /// component.
+    ///
+    /// With `restore_mask` unset the signals stay blocked, so that they keep
+    /// arriving through the descriptor; this is what `IntoRawFd` does.
+    /// Otherwise the mask change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals read
+    /// from the descriptor but not yet returned are lost.
+    ///
+    /// The descriptor is close-on-exec; clear `FD_CLOEXEC` to pass it to a
+    /// program started with `exec`, which inherits the mask as well.
+    pub fn into_raw_fd_with(self, restore_mask: bool) -> RawFd {
+        self.io.into_inner().into_fd(restore_mask).into_raw_fd()
+    }
+
+    /// Returns the underlying descriptor, as registered with the runtime's
+    /// reactor.
+    pub fn get_ref(&self) -> &AsyncFd<RawSignalFd> {
+        &self.io
+    }
+
+    /// Returns the underlying descriptor mutably, as registered with
+    /// the runtime's reactor.
+    pub fn get_mut(&mut self) -> &mut AsyncFd<RawSignalFd> {
+        &mut self.io
+    }
+
+    /// Consumes the stream, returning the underlying descriptor still
+    /// registered with the runtime's reactor.
+    ///
+    /// Signals the stream had read but not yet returned stay buffered in it.
+    pub fn into_inner(self) -> AsyncFd<RawSignalFd> {
+        self.io
+    }
+
+    /// Stops receiving the signals, handing them back to their regular
+    /// disposition.
+    ///
+    /// The descriptor is deregistered from the reactor and closed, and the mask
+    /// change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals that
+    /// were pending on the descriptor are returned rather than delivered to
+    /// their disposition once unblocked.
+    pub fn close(self) -> Result<Vec<SignalInfo>> {
+        self.io.into_inner().close()
+    }
+
+    /// Duplicates the descriptor and registers the copy with the reactor on
+    /// its own, so that two tasks can take turns draining the same signals.
+    ///
+    /// Both read from one kernel queue: every signal is received by exactly
+    /// one of them, whichever reads first, and since reads take several
+    /// pending signals at once, one reader can end up with all of them. The
+    /// set of signals is shared too, so [`add`](Self::add) and
+    /// [`remove`](Self::remove) through either affect both, though only the
+    /// caller's [`signals`](Self::signals) reflects the change. Filters are
+    /// copied, and only the original restores the signal mask when dropped.
+    ///
+    /// Fails with `Unsupported` for the sigwait thread and signal-hook
+    /// backends.
+    pub fn try_clone(&self) -> Result<Self> {
+        SignalFd::from_inner(self.io.get_ref().try_clone()?)
+    }
+
+    /// Makes the stream usable in a child process after `fork(2)`.
+    ///
+    /// A forked child shares the descriptor with its parent, along with the
+    /// parent's reactor, whose thread didn't survive the fork. This replaces
+    /// the descriptor with a new one for the same signals and registers it
+    /// with the reactor of the runtime the child runs, leaving the parent's
+    /// registration alone. Signals read before the fork but not yet returned
+    /// are dropped.
+    ///
+    /// The child inherits the mask of the thread that forked. Set `block` to
+    /// block the signals on the calling thread again, e.g. when forking from
+    /// a thread other than the one that created the stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime with IO enabled.
+    pub fn after_fork(mut self, block: bool) -> Result<Self> {
+        self.io.get_mut().reopen_with(block)?;
+        SignalFd::from_inner(self.io.into_inner())
+    }
+
+    /// Replaces the descriptor with a new one for the same signals,
+    /// registered with the current runtime, e.g. once the process was
+    /// restored from a checkpoint.
+    ///
+    /// Filters and the mask change are kept. Signals read but not yet
+    /// returned are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime with IO enabled.
+    pub fn reopen(self) -> Result<Self> {
+        self.after_fork(false)
+    }
+
+    /// Returns `true` if a restore from a checkpoint was notified since the
+    /// descriptor was opened, see [`restore`](crate::restore).
+    pub fn needs_reopen(&self) -> bool {
+        self.io.get_ref().needs_reopen()
+    }
+
+    /// Returns the calling thread's signal mask.
+    ///
+    /// Any thread that doesn't block the [`signals`](Self::signals) can have
+    /// them delivered to their disposition instead of the descriptor, as can
+    /// threads spawned before the signalfd was created. See
+    /// [`SigSet::blocked`].
+    pub fn blocked_mask() -> Result<SigSet> {
+        SigSet::blocked()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.io.get_mut().add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.io.get_mut().remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.io.get_mut().pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.io.get_mut().resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.io.get_ref().is_paused()
+    }
+
+    /// Converts this stream into one yielding typed [`Signal`]s.
+    pub fn signal_stream(self) -> SignalFd<Signal> {
+        SignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one yielding full [`SignalInfo`] records.
+    pub fn info_stream(self) -> SignalFd<SignalInfo> {
+        SignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one ending after the first signal from
+    /// `terminal`.
+    pub fn take_until_terminal(self, terminal: &SigSet) -> TakeUntilTerminal<Self> {
+        TakeUntilTerminal::new(self, terminal)
+    }
+
+    /// Converts this stream into one delivering each burst of a signal as a
+    /// single event, once the signal has been quiet for `quiet`.
+    pub fn debounce(self, quiet: Duration) -> Debounced<Self> {
+        Debounced::new(self, quiet)
+    }
+
+    /// Hands the stream to a task keeping the most recent signal in a watch
+    /// channel, for code that only checks the latest state, such as whether
+    /// a shutdown was requested, instead of consuming every signal.
+    ///
+    /// The channel holds `None` until the first signal. The task stops once
+    /// every receiver is dropped, or when receiving fails, which receivers
+    /// see as the sender being closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime.
+    pub fn into_watch(self) -> watch::Receiver<Option<SignalInfo>> {
+        let (sender, receiver) = watch::channel(None);
+        tokio1::spawn(forward_latest(self.info_stream(), sender));
+        receiver
+    }
+
+    /// Polls for the next signals, appending up to `limit` of them to `out`.
+    ///
+    /// Once at least one signal is available, every signal that can be
+    /// received without waiting is taken, so a burst of signals is drained in
+    /// as few system calls as possible. Returns the number of signals
+    /// appended, which is only zero when `limit` is zero.
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        out: &mut Vec<SignalInfo>,
+        limit: usize,
+    ) -> Poll<Result<usize>> {
+        let mut count = 0;
+        while count < limit {
+            match self.poll_recv(cx) {
+                Poll::Ready(Ok(info)) => {
+                    out.push(info);
+                    count += 1;
+                }
+                Poll::Ready(Err(err)) if count == 0 => return Poll::Ready(Err(err)),
+                Poll::Ready(Err(err)) => {
+                    // Reported by the next call, after these signals.
+                    self.io.get_mut().defer_error(err);
+                    break;
+                }
+                Poll::Pending if count == 0 => return Poll::Pending,
+                Poll::Pending => break,
+            }
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    /// Waits for the next signals, appending up to `limit` of them to `out`.
+    ///
+    /// See [`poll_recv_many`](SignalFd::poll_recv_many).
+    pub async fn recv_many(&mut self, out: &mut Vec<SignalInfo>, limit: usize) -> Result<usize> {
+        poll_fn(|cx| self.poll_recv_many(cx, out, limit)).await
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    ///
+    /// Useful to check for signals once per iteration of a loop driven by
+    /// something other than the reactor.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.io.get_mut().read_one()
+    }
+
+    /// Returns every signal that can be received without waiting, e.g. to
+    /// deal with the ones that arrived between creating the signalfd and
+    /// starting to poll it.
+    pub fn drain_pending(&mut self) -> Result<Vec<SignalInfo>> {
+        self.io.get_mut().drain()
+    }
+
+    /// Runs `fut` while holding the signals back, to be received once it
+    /// completes.
+    ///
+    /// The stream is borrowed for the duration, so nothing can receive from
+    /// it meanwhile. Signals arriving in the meantime are read into its
+    /// buffer all the same, so that repeated instances of a standard signal
+    /// aren't merged into one by the kernel.
+    pub async fn masked<F: Future>(&mut self, fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        poll_fn(|cx| {
+            if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+            self.poll_fill(cx);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Buffers pending signals until none are left, registering for
+    /// readiness. An error is kept for the next read to report, and doesn't
+    /// stop the signals after it from being buffered.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) {
+        // Signals raised by `fut` itself are pending before the reactor has
+        // noticed them.
+        match self.io.get_mut().fill() {
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            _ => return,
+        }
+        while let Poll::Ready(Ok(mut guard)) = self.io.poll_read_ready_mut(cx) {
+            if guard.try_io(|inner| inner.get_mut().fill()).is_ok() {
+                return;
+            }
+        }
+    }
+
+    /// Polls for the next signal.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        self.io.get_mut().take_error()?;
+        if let Some(info) = self.io.get_mut().pop() {
+            return Poll::Ready(Ok(info));
+        }
+        loop {
+            let mut guard = ready!(self.io.poll_read_ready_mut(cx))?;
+            if let Ok(result) = guard.try_io(|inner| inner.get_mut().read_info()) {
+                return Poll::Ready(result);
+            }
+        }
+    }
+
+    /// Waits for the next signal, without going through the stream.
+    ///
+    /// Cancelling the returned future is safe: a signal is only taken off the
+    /// descriptor when the future completes with it.
+    pub async fn recv(&mut self) -> Result<SignalInfo> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Waits for the next signal for at most `timeout`, resolving to `None`
+    /// if none arrived in time.
+    ///
+    /// This needs the runtime's time driver to be enabled.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<SignalInfo>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline).await,
+            None => self.recv().await.map(Some),
+        }
+    }
+
+    /// Waits for the next signal until `deadline`, resolving to `None` if none
+    /// arrived by then.
+    ///
+    /// This needs the runtime's time driver to be enabled.
+    pub async fn recv_deadline(&mut self, deadline: Instant) -> Result<Option<SignalInfo>> {
+        match tokio1::time::timeout_at(deadline.into(), self.recv()).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Publishes every signal from `signals` on `sender` until it is closed.
+async fn forward_latest(
+    mut signals: SignalFd<SignalInfo>,
+    sender: watch::Sender<Option<SignalInfo>>,
+) {
+    let mut closed = pin!(sender.closed());
+    loop {
+        let next = poll_fn(|cx| match closed.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(None),
+            Poll::Pending => signals.poll_recv(cx).map(Some),
+        });
+        match next.await {
+            Some(Ok(info)) => {
+                sender.send_replace(Some(info));
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Creates a signalfd receiving `kind`, in place of
+/// `tokio::signal::unix::signal`.
+///
+/// Unlike tokio's own implementation, the signal is blocked on the calling
+/// thread rather than handled process-wide, realtime signals are queued
+/// instead of coalesced, and the stream can yield full [`SignalInfo`]
+/// records.
+#[cfg(feature = "tokio-signal-compat")]
+pub fn signal(kind: tokio1::signal::unix::SignalKind) -> Result<SignalFd> {
+    SignalFd::from_kinds(&[kind])
+}
+
+/// Waits for the first of `signals` to arrive.
+///
+/// The signals are blocked on the thread first polling the returned future, and
+/// unblocked again once it completes (if it completes on that same thread).
+///
+/// Only that thread has them blocked, though. On a multi-threaded runtime,
+/// such as the default of `#[tokio::main]`, the kernel may deliver them to
+/// another worker, where `SIGINT` and `SIGTERM` kill the process before this
+/// ever resolves. Block them on every thread of the runtime, with
+/// [`mask_on_thread_start`] or by using `#[tokio_signalfd::main]`, or use a
+/// current-thread runtime.
+pub async fn any_of<S: Copy + Into<i32>>(signals: &[S]) -> Result<Signal> {
+    let mut signals = SignalFd::new(signals)?;
+    poll_fn(|cx| signals.poll_recv(cx)).await.map(Signal::from)
+}
+
+/// How [`run_until_signal`] ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// The future completed with this output.
+    Completed(T),
+    /// This signal arrived first, and the future was dropped.
+    Interrupted(Signal),
+}
+
+/// Runs `fut` until it completes or one of `signals` arrives, whichever
+/// happens first.
+///
+/// ```no_run
+/// use tokio_signalfd::tokio::{run_until_signal, Outcome};
+/// use tokio_signalfd::{SIGINT, SIGTERM};
+///
+/// # async fn serve() {}
+/// # async fn run() -> std::io::Result<()> {
+/// match run_until_signal(serve(), &[SIGINT, SIGTERM]).await? {
+///     Outcome::Completed(()) => println!("server stopped"),
+///     Outcome::Interrupted(signal) => println!("shutting down on {}", signal),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The signals are blocked before `fut` is first polled, and unblocked
+/// again once this completes, as with [`any_of`]. If `fut` is ready at the
+/// same time as a signal, its output wins.
+pub async fn run_until_signal<F, S>(fut: F, signals: &[S]) -> Result<Outcome<F::Output>>
+where
+    F: Future,
+    S: Copy + Into<i32>,
+{
+    let mut signals = SignalFd::new(signals)?;
+    let mut fut = pin!(fut);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+            return Poll::Ready(Ok(Outcome::Completed(output)));
+        }
+        signals
+            .poll_recv(cx)
+            .map_ok(|info| Outcome::Interrupted(Signal::from(info)))
+    })
+    .await
+}
+
+/// Returns a callback for `runtime::Builder::on_thread_start` that blocks
+/// `set` on each of the runtime's threads, the blocking pool's included.
+///
+/// A signalfd only receives the signals blocked in every thread; a runtime
+/// whose threads were started before the signalfd changed the mask would
+/// otherwise still have them delivered to their handlers there. The thread
+/// calling `block_on` isn't the runtime's own, use [`SigSet::block`] for it.
+///
+/// ```no_run
+/// use tokio_signalfd::tokio::{mask_on_thread_start, SignalFd};
+/// use tokio_signalfd::SigSet;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let set = SigSet::terminating();
+/// set.block()?;
+/// let rt = tokio1::runtime::Builder::new_multi_thread()
+///     .on_thread_start(mask_on_thread_start(&set))
+///     .enable_io()
+///     .build()?;
+/// rt.block_on(async {
+///     let mut signals = SignalFd::from_set(&set)?;
+///     signals.recv().await.map(drop)
+/// })
+/// # }
+/// ```
+pub fn mask_on_thread_start(set: &SigSet) -> impl Fn() + Send + Sync + 'static {
+    let set = *set;
+    move || {
+        // Only fails for invalid arguments, which the set can't be.
+        let _ = set.block();
+    }
+}
+
+/// Returns a stream calling `reload` for every `SIGHUP`, yielding the outcome
+/// of each reload.
+///
+/// `SIGHUP` is blocked on the calling thread. See [`Reloader`] for how
+/// reloads are serialized and coalesced.
+pub fn reload_on_sighup<F, Fut>(reload: F) -> Result<Reloader<SignalFd, F, Fut>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future,
+{
+    Ok(Reloader::new(SignalFd::new(&[Signal::Hup])?, reload))
+}
+
+/// Returns a stream of the sizes of the terminal on standard output, yielding
+/// `(cols, rows)` whenever it is resized.
+///
+/// `SIGWINCH` is blocked on the calling thread. See [`ResizeEvents`].
+pub fn resize_events() -> Result<ResizeEvents<SignalFd>> {
+    Ok(ResizeEvents::new(SignalFd::new(&[Signal::Winch])?))
+}
+
+/// Waits for `SIGINT`, as sent by Ctrl-C in a terminal.
+///
+/// See [`any_of`] for how the signal mask is affected, and why the threads of
+/// a multi-threaded runtime need it too.
+pub async fn ctrl_c() -> Result<()> {
+    any_of(&[Signal::Int]).await.map(|_| ())
+}
+
+/// Waits for `SIGTERM`, the conventional request to shut down.
+///
+/// See [`any_of`] for how the signal mask is affected, and why the threads of
+/// a multi-threaded runtime need it too.
+pub async fn terminate() -> Result<()> {
+    any_of(&[Signal::Term]).await.map(|_| ())
+}
+
+impl<T> AsRawFd for SignalFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().as_raw_fd()
+    }
+}
+
+impl<T> AsFd for SignalFd<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.io.get_ref().as_fd()
+    }
+}
+
+/// The signal mask is left as is, see `into_raw_fd_with`.
+impl<T> IntoRawFd for SignalFd<T> {
+    fn into_raw_fd(self) -> RawFd {
+        self.into_raw_fd_with(false)
+    }
+}
+
+/// Adopts an existing signalfd whose signal set is unknown.
+///
+/// Fails if the descriptor can't be registered with the reactor.
+impl<T> TryFrom<OwnedFd> for SignalFd<T> {
+    type Error = std::io::Error;
+
+    fn try_from(fd: OwnedFd) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::from_fd(fd))
+    }
+}
+
+/// The signal mask is left as is, so that the signals keep arriving through
+/// the descriptor.
+impl<T> From<SignalFd<T>> for OwnedFd {
+    fn from(signals: SignalFd<T>) -> OwnedFd {
+        signals.io.into_inner().into_fd(false)
+    }
+}
+
+impl<T> SignalSource for SignalFd<T> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<SignalInfo>> {
+        SignalFd::poll_recv(self, cx)
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::Stream for SignalFd<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .poll_recv(cx)
+            .map(|info| Some(info.map(T::from)))
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::FusedStream for SignalFd<T> {
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::Stream for TakeUntilTerminal<SignalFd<T>> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let result = ready!(this.stream.poll_recv(cx));
+        if let Ok(info) = &result {
+            this.observe(info.signo());
+        }
+        Poll::Ready(Some(result.map(T::from)))
+    }
+}
+
+impl<T: From<SignalInfo>> futures_core::FusedStream for TakeUntilTerminal<SignalFd<T>> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    #[cfg(not(kqueue))]
+    fn recv_many_keeps_error() {
+        use crate::raw::BATCH;
+        use crate::siginfo::SIGINFO_SIZE;
+        use std::os::unix::io::FromRawFd;
+
+        let mut fds = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK) },
+            0
+        );
+        // A full batch, then a partial record read on its own.
+        let mut records = vec![0u8; SIGINFO_SIZE * BATCH + 8];
+        for record in records.chunks_mut(SIGINFO_SIZE) {
+            record[0] = libc::SIGHUP as u8;
+        }
+        let written = unsafe { libc::write(fds[1], records.as_ptr() as *const _, records.len()) };
+        assert_eq!(written as usize, records.len());
+
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let fd = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+            let mut signals = SignalFd::<i32>::try_from(fd).unwrap();
+            let mut out = Vec::new();
+            assert_eq!(signals.recv_many(&mut out, 2 * BATCH).await.unwrap(), BATCH);
+            // Nothing else arrives, so only the kept error can be returned.
+            let mut cx = Context::from_waker(std::task::Waker::noop());
+            match signals.poll_recv_many(&mut cx, &mut out, 2 * BATCH) {
+                Poll::Ready(Err(err)) => assert_eq!(err.kind(), std::io::ErrorKind::InvalidData),
+                other => panic!("expected the short read, got {:?}", other),
+            }
+        });
+        unsafe { libc::close(fds[1]) };
+    }
+
+    #[test]
+    fn it_works() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[libc::SIGUSR2]).unwrap();
+            unsafe {
+                libc::raise(libc::SIGUSR2);
+            }
+            let signal = signals.next().await.unwrap().unwrap();
+            assert_eq!(signal, libc::SIGUSR2);
+        });
+    }
+
+    #[test]
+    fn after_fork() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let mut signals = SignalFd::new_with_handle(rt.handle(), &[libc::SIGUSR1]).unwrap();
+        unsafe { libc::raise(libc::SIGUSR1) };
+        rt.block_on(signals.recv()).unwrap();
+
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            let child = Builder::new_current_thread().enable_io().build().unwrap();
+            let received = child.block_on(async {
+                let mut signals = signals.after_fork(false)?;
+                unsafe { libc::raise(libc::SIGUSR1) };
+                signals.recv().await
+            });
+            let code = match received {
+                Ok(info) if info.signo() == libc::SIGUSR1 => 0,
+                _ => 1,
+            };
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 0);
+        // The child leaves the parent's registration in place.
+        unsafe { libc::raise(libc::SIGUSR1) };
+        let info = rt.block_on(signals.recv()).unwrap();
+        assert_eq!(info.signo(), libc::SIGUSR1);
+    }
+
+    #[test]
+    fn into_watch() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut latest = SignalFd::new(&[libc::SIGUSR1, libc::SIGUSR2])
+                .unwrap()
+                .into_watch();
+            assert!(latest.borrow().is_none());
+            unsafe { libc::raise(libc::SIGUSR1) };
+            unsafe { libc::raise(libc::SIGUSR2) };
+            latest
+                .wait_for(|info| info.is_some_and(|info| info.signo() == libc::SIGUSR2))
+                .await
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn runs_until_signal() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let outcome = run_until_signal(async { 42 }, &[libc::SIGUSR1]).await;
+            assert_eq!(outcome.unwrap(), Outcome::Completed(42));
+            let interrupted = async {
+                unsafe { libc::raise(libc::SIGUSR1) };
+                std::future::pending::<()>().await
+            };
+            let outcome = run_until_signal(interrupted, &[libc::SIGUSR1]).await;
+            assert_eq!(outcome.unwrap(), Outcome::Interrupted(Signal::Usr1));
+        });
+    }
+
+    #[test]
+    fn masks_runtime_threads() {
+        let set = SigSet::from_signals(&[libc::SIGPROF]).unwrap();
+        let rt = Builder::new_current_thread()
+            .on_thread_start(mask_on_thread_start(&set))
+            .build()
+            .unwrap();
+        let blocked = rt
+            .block_on(async { tokio1::task::spawn_blocking(SigSet::blocked).await })
+            .unwrap()
+            .unwrap();
+        assert!(blocked.contains(libc::SIGPROF));
+        assert!(!SigSet::blocked().unwrap().contains(libc::SIGPROF));
+    }
+
+    #[test]
+    fn new_with_handle() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let mut signals = SignalFd::new_with_handle(rt.handle(), &[libc::SIGUSR1]).unwrap();
+        unsafe { libc::raise(libc::SIGUSR1) };
+        let info = rt.block_on(signals.recv()).unwrap();
+        assert_eq!(info.signo(), libc::SIGUSR1);
+    }
+
+    #[test]
+    fn clones_share_signals() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::<i32>::new(&[libc::SIGUSR1]).unwrap();
+            let mut clone = signals.try_clone().unwrap();
+            assert_ne!(clone.as_raw_fd(), signals.as_raw_fd());
+
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(clone.next().await.unwrap().unwrap(), libc::SIGUSR1);
+            assert!(futures_util::poll!(signals.next()).is_pending());
+
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(signals.next().await.unwrap().unwrap(), libc::SIGUSR1);
+            assert!(futures_util::poll!(clone.next()).is_pending());
+        });
+    }
+
+    #[test]
+    fn inner_accessors() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::<i32>::new(&[libc::SIGUSR2]).unwrap();
+            let fd = signals.as_raw_fd();
+            assert!(signals
+                .get_ref()
+                .get_ref()
+                .signals()
+                .contains(libc::SIGUSR2));
+            assert_eq!(signals.get_mut().as_raw_fd(), fd);
+            let raw = signals.into_inner().into_inner();
+            assert_eq!(raw.as_raw_fd(), fd);
+            assert_eq!(raw.backend(), Backend::native().unwrap());
+        });
+    }
+
+    #[test]
+    fn close_returns_pending() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::<i32>::new(&[libc::SIGUSR1]).unwrap();
+            unsafe { libc::raise(libc::SIGUSR1) };
+            let pending = signals.close().unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].signo(), libc::SIGUSR1);
+
+            let mut mask = SigSet::empty();
+            unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), mask.as_raw_mut()) };
+            assert!(!mask.contains(libc::SIGUSR1));
+        });
+    }
+
+    #[test]
+    fn masked_buffers_signals() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::<i32>::new(&[libc::SIGUSR1]).unwrap();
+            let done = signals
+                .masked(async {
+                    unsafe { libc::raise(libc::SIGUSR1) };
+                    tokio1::task::yield_now().await;
+                    unsafe { libc::raise(libc::SIGUSR1) };
+                    "done"
+                })
+                .await;
+            assert_eq!(done, "done");
+            assert_eq!(signals.next().await.unwrap().unwrap(), libc::SIGUSR1);
+            assert_eq!(signals.next().await.unwrap().unwrap(), libc::SIGUSR1);
+        });
+    }
+
+    #[test]
+    fn recv() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[Signal::Usr1, Signal::Usr2])
+                .unwrap()
+                .signal_stream();
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+                libc::raise(libc::SIGUSR2);
+            }
+            assert_eq!(signals.recv().await.unwrap().signo(), libc::SIGUSR1);
+            assert_eq!(signals.next().await.unwrap().unwrap(), Signal::Usr2);
+        });
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[libc::SIGUSR2]).unwrap();
+            let timeout = Duration::from_millis(20);
+            assert!(signals.recv_timeout(timeout).await.unwrap().is_none());
+            unsafe {
+                libc::raise(libc::SIGUSR2);
+            }
+            let info = signals.recv_timeout(timeout).await.unwrap().unwrap();
+            assert_eq!(info.signo(), libc::SIGUSR2);
+        });
+    }
+
+    #[test]
+    fn fused_and_unpin() {
+        fn assert_fused<S: futures_core::FusedStream + Unpin>(_: &S) {}
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::new(&[libc::SIGUSR1]).unwrap().info_stream();
+            assert_fused(&signals);
+            assert!(!futures_core::FusedStream::is_terminated(&signals));
+        });
+    }
+
+    #[test]
+    fn take_until_terminal() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let terminal = SigSet::from_signals(&[libc::SIGUSR2]).unwrap();
+            let signals = SignalFd::new(&[Signal::Usr1, Signal::Usr2])
+                .unwrap()
+                .signal_stream()
+                .take_until_terminal(&terminal);
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+                libc::raise(libc::SIGUSR2);
+            }
+            let received: Vec<_> = signals.map(Result::unwrap).collect().await;
+            assert_eq!(received, [Signal::Usr1, Signal::Usr2]);
+        });
+    }
+
+    #[test]
+    fn try_recv() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[libc::SIGUSR1]).unwrap();
+            assert!(signals.try_recv().unwrap().is_none());
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+            }
+            let info = signals.try_recv().unwrap().unwrap();
+            assert_eq!(info.signo(), libc::SIGUSR1);
+            assert!(signals.try_recv().unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn drain_pending() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::<i32>::new(&[libc::SIGUSR1, libc::SIGUSR2]).unwrap();
+            assert!(signals.drain_pending().unwrap().is_empty());
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+                libc::raise(libc::SIGUSR2);
+            }
+            let pending = signals.drain_pending().unwrap();
+            let signos: Vec<_> = pending.iter().map(SignalInfo::signo).collect();
+            assert_eq!(signos, [libc::SIGUSR1, libc::SIGUSR2]);
+            assert!(signals.drain_pending().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn owned_fd_roundtrip() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = SignalFd::<i32>::new(&[libc::SIGUSR1]).unwrap();
+            let fd = OwnedFd::from(signals);
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+            }
+            let mut signals = SignalFd::<i32>::try_from(fd).unwrap();
+            assert_eq!(signals.next().await.unwrap().unwrap(), libc::SIGUSR1);
+        });
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn info_stream() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[libc::SIGWINCH]).unwrap().info_stream();
+            unsafe {
+                libc::raise(libc::SIGWINCH);
+            }
+            let info = signals.next().await.unwrap().unwrap();
+            assert_eq!(info.signo(), libc::SIGWINCH);
+            assert_eq!(info.code(), libc::SI_TKILL);
+            assert_eq!(info.pid(), std::process::id());
+            assert_eq!(info.uid(), unsafe { libc::getuid() });
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "tokio-signal-compat")]
+    fn signal_kind() {
+        use tokio1::signal::unix::SignalKind;
+
+        assert_eq!(Signal::from(SignalKind::hangup()), Signal::Hup);
+        assert_eq!(SignalKind::from(Signal::Usr1), SignalKind::user_defined1());
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = signal(SignalKind::user_defined2()).unwrap();
+            unsafe { libc::raise(libc::SIGUSR2) };
+            assert_eq!(signals.next().await.unwrap().unwrap(), libc::SIGUSR2);
+        });
+    }
+
+    #[test]
+    fn add_remove() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let mut signals = SignalFd::new(&[libc::SIGUSR1]).unwrap();
+            signals.add(Signal::Hup).unwrap();
+            assert!(signals.signals().contains(Signal::Hup));
+            unsafe {
+                libc::raise(libc::SIGHUP);
+            }
+            let signal = signals.next().await.unwrap().unwrap();
+            assert_eq!(signal, libc::SIGHUP);
+
+            signals.remove(Signal::Hup).unwrap();
+            assert!(!signals.signals().contains(Signal::Hup));
+            unsafe {
+                let mut mask = std::mem::zeroed();
+                assert_eq!(
+                    libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), &mut mask),
+                    0
+                );
+                assert_eq!(libc::sigismember(&mask, libc::SIGHUP), 0);
+                assert_eq!(libc::sigismember(&mask, libc::SIGUSR1), 1);
+            }
+        });
+    }
+
+    #[test]
+    fn recv_many() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signals = [libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2];
+            let mut signalfd = SignalFd::new(&signals).unwrap();
+            for &signal in &signals {
+                unsafe { libc::raise(signal) };
+            }
+            let mut out = Vec::new();
+            assert_eq!(signalfd.recv_many(&mut out, 2).await.unwrap(), 2);
+            assert_eq!(signalfd.recv_many(&mut out, 2).await.unwrap(), 1);
+            let received: Vec<_> = out.iter().map(SignalInfo::signo).collect();
+            assert_eq!(received, signals);
+        });
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn signals_under_load() {
+        const COUNT: i32 = 1000;
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signal = Signal::rt(2).unwrap();
+            let mut signals = SignalFd::new(&[signal]).unwrap().info_stream();
+            let thread = unsafe { libc::pthread_self() };
+            let sender = std::thread::spawn(move || {
+                for value in 0..COUNT {
+                    let value = libc::sigval {
+                        sival_ptr: value as usize as *mut libc::c_void,
+                    };
+                    while unsafe { libc::pthread_sigqueue(thread, signal.as_raw(), value) } != 0 {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            for value in 0..COUNT {
+                let info = signals.next().await.unwrap().unwrap();
+                assert_eq!(info.int(), value);
+            }
+            sender.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn shutdown_futures() {
+        use futures_util::future::join;
+
+        let raise = |signal| async move {
+            tokio1::task::yield_now().await;
+            unsafe { libc::raise(signal) };
+        };
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let (received, ()) = join(ctrl_c(), raise(libc::SIGINT)).await;
+            received.unwrap();
+            let (received, ()) = join(terminate(), raise(libc::SIGTERM)).await;
+            received.unwrap();
+            let any = any_of(&[Signal::Quit, Signal::Hup]);
+            let (received, ()) = join(any, raise(libc::SIGHUP)).await;
+            assert_eq!(received.unwrap(), Signal::Hup);
+        });
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn realtime_signals_are_queued() {
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        rt.block_on(async {
+            let signal = Signal::rt(1).unwrap();
+            let mut signals = SignalFd::new(&[signal]).unwrap().info_stream();
+            for value in 1..=3 {
+                let value = libc::sigval {
+                    sival_ptr: value as *mut libc::c_void,
+                };
+                let rv =
+                    unsafe { libc::pthread_sigqueue(libc::pthread_self(), signal.as_raw(), value) };
+                assert_eq!(rv, 0);
+            }
+            for value in 1..=3 {
+                let info = signals.next().await.unwrap().unwrap();
+                assert_eq!(info.signo(), signal.as_raw());
+                assert_eq!(info.code(), libc::SI_QUEUE);
+                assert_eq!(info.value(), Some(value));
+            }
+        });
+    }
+}
@@ -0,0 +1,638 @@
+use std::convert::TryFrom;
+use std::io::{self, Result};
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use mio::unix::EventedFd;
+use mio::{self, Evented, PollOpt, Ready, Token};
+
+use futures::{future, try_ready, Async, Future, Poll, Stream};
+use tokio_io::AsyncRead;
+use tokio_reactor::{Handle, PollEvented};
+
+use crate::builder::Builder;
+use crate::error::SignalFdError;
+use crate::raw::RawSignalFd;
+use crate::siginfo::SignalInfo;
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+use crate::terminal::TakeUntilTerminal;
+
+impl Evented for RawSignalFd {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> Result<()> {
+        poll.register(&EventedFd(&self.as_raw_fd()), token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> Result<()> {
+        poll.reregister(&EventedFd(&self.as_raw_fd()), token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> Result<()> {
+        match poll.deregister(&EventedFd(&self.as_raw_fd())) {
+            // Not registered under its current number, after `reopen`.
+            Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// A stream of signals delivered through a signalfd registered with the
+/// tokio 0.1 reactor.
+///
+/// The stream yields signal numbers by default; use
+/// [`signal_stream`](SignalFd::signal_stream) or
+/// [`info_stream`](SignalFd::info_stream) to receive typed [`Signal`]s or full
+/// [`SignalInfo`] records instead.
+pub struct SignalFd<T = i32> {
+    io: PollEvented<RawSignalFd>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl SignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    ///
+    /// Signals can be given either as raw numbers or as [`Signal`]s.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        let inner = RawSignalFd::new(signals)?;
+        Ok(SignalFd::from_inner(inner))
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        let inner = RawSignalFd::from_set(set)?;
+        Ok(SignalFd::from_inner(inner))
+    }
+
+    /// Like [`new`](SignalFd::new), but registers the descriptor with the
+    /// reactor behind `handle` instead of the default one.
+    ///
+    /// The stream can then be polled from any task, as long as that reactor
+    /// is being driven, which suits programs running several of them.
+    pub fn new_with_handle<S: Copy + Into<i32>>(handle: &Handle, signals: &[S]) -> Result<Self> {
+        let inner = RawSignalFd::new(signals)?;
+        let io = PollEvented::new_with_handle(inner, handle).map_err(SignalFdError::Register)?;
+        Ok(SignalFd {
+            io,
+            _item: PhantomData,
+        })
+    }
+
+    /// Starts receiving the signals in `list`, separated by commas or
+    /// whitespace, e.g. `"TERM,HUP,USR1"`.
+    ///
+    /// See [`SigSet::from_str_list`].
+    pub fn from_str_list(list: &str) -> Result<Self> {
+        SignalFd::from_set(&SigSet::from_str_list(list)?)
+    }
+
+    /// Starts receiving the signals listed in the environment variable `var`,
+    /// or `default` if it isn't set, so that deployments can choose them.
+    ///
+    /// See [`SigSet::from_env`].
+    pub fn from_env<S: Copy + Into<i32>>(var: &str, default: &[S]) -> Result<Self> {
+        match SigSet::from_env(var)? {
+            Some(set) => SignalFd::from_set(&set),
+            None => SignalFd::new(default),
+        }
+    }
+
+    /// Starts receiving the signals asking the process to shut down, see
+    /// [`SigSet::terminating`].
+    pub fn terminating() -> Result<Self> {
+        SignalFd::from_set(&SigSet::terminating())
+    }
+
+    /// Starts receiving every signal that can be caught, see
+    /// [`SigSet::catchable`].
+    pub fn catchable() -> Result<Self> {
+        SignalFd::from_set(&SigSet::catchable())
+    }
+
+    /// Starts receiving the job control signals, see
+    /// [`SigSet::job_control`].
+    pub fn job_control() -> Result<Self> {
+        SignalFd::from_set(&SigSet::job_control())
+    }
+
+    /// Takes over a signalfd created by another process, such as a supervisor
+    /// or the process this one replaces, recovering the signals it receives.
+    ///
+    /// See [`RawSignalFd::adopt`].
+    pub fn adopt(fd: OwnedFd) -> Result<Self> {
+        Ok(SignalFd::from_inner(RawSignalFd::adopt(fd)?))
+    }
+
+    /// Adopts the first signalfd passed with systemd's `LISTEN_FDS`
+    /// protocol, among those named `name` if given.
+    ///
+    /// See [`RawSignalFd::from_listen_fds`].
+    pub fn from_listen_fds(name: Option<&str>) -> Result<Option<Self>> {
+        RawSignalFd::from_listen_fds(name)?
+            .map(|inner| Ok(SignalFd::from_inner(inner)))
+            .transpose()
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(|inner| Ok(SignalFd::from_inner(inner)))
+    }
+}
+
+impl<T> SignalFd<T> {
+    fn from_inner(inner: RawSignalFd) -> Self {
+        SignalFd {
+            io: PollEvented::new(inner),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.io.get_ref().signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.io.get_ref().backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.io.get_ref().previous_mask()
+    }
+
+    /// Gives up the descriptor without closing it, e.g. to hand it to another
+    /// component.
+    ///
+    /// With `restore_mask` unset the signals stay blocked, so that they keep
+    /// arriving through the descriptor; this is what `IntoRawFd` does.
+    /// Otherwise the mask change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals read
+    /// from the descriptor but not yet returned are lost.
+    ///
+    /// The descriptor is close-on-exec; clear `FD_CLOEXEC` to pass it to a
+    /// program started with `exec`, which inherits the mask as well.
+    ///
+    /// Fails if the descriptor can't be deregistered from the reactor.
+    pub fn into_raw_fd_with(self, restore_mask: bool) -> Result<RawFd> {
+        Ok(self.io.into_inner()?.into_fd(restore_mask).into_raw_fd())
+    }
+
+    /// Returns the underlying descriptor, as registered with the reactor.
+    pub fn get_ref(&self) -> &PollEvented<RawSignalFd> {
+        &self.io
+    }
+
+    /// Returns the underlying descriptor mutably, as registered with
+    /// the reactor.
+    pub fn get_mut(&mut self) -> &mut PollEvented<RawSignalFd> {
+        &mut self.io
+    }
+
+    /// Consumes the stream, returning the underlying descriptor still
+    /// registered with the reactor.
+    ///
+    /// Signals the stream had read but not yet returned stay buffered in it.
+    pub fn into_inner(self) -> PollEvented<RawSignalFd> {
+        self.io
+    }
+
+    /// Stops receiving the signals, handing them back to their regular
+    /// disposition.
+    ///
+    /// The descriptor is deregistered from the reactor and closed, and the mask
+    /// change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals that
+    /// were pending on the descriptor are returned rather than delivered to
+    /// their disposition once unblocked.
+    pub fn close(self) -> Result<Vec<SignalInfo>> {
+        self.io.into_inner()?.close()
+    }
+
+    /// Duplicates the descriptor and registers the copy with the reactor on
+    /// its own, so that two tasks can take turns draining the same signals.
+    ///
+    /// Both read from one kernel queue: every signal is received by exactly
+    /// one of them, whichever reads first, and since reads take several
+    /// pending signals at once, one reader can end up with all of them. The
+    /// set of signals is shared too, so [`add`](Self::add) and
+    /// [`remove`](Self::remove) through either affect both, though only the
+    /// caller's [`signals`](Self::signals) reflects the change. Filters are
+    /// copied, and only the original restores the signal mask when dropped.
+    ///
+    /// Fails with `Unsupported` for the sigwait thread and signal-hook
+    /// backends.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(SignalFd::from_inner(self.io.get_ref().try_clone()?))
+    }
+
+    /// Makes the stream usable in a child process after `fork(2)`.
+    ///
+    /// A forked child shares the descriptor with its parent, along with the
+    /// parent's reactor, whose thread didn't survive the fork. This replaces
+    /// the descriptor with a new one for the same signals, registered with
+    /// the child's default reactor once polled, leaving the parent's
+    /// registration alone. Signals read before the fork but not yet returned
+    /// are dropped.
+    ///
+    /// The child inherits the mask of the thread that forked. Set `block` to
+    /// block the signals on the calling thread again, e.g. when forking from
+    /// a thread other than the one that created the stream.
+    pub fn after_fork(mut self, block: bool) -> Result<Self> {
+        self.io.get_mut().reopen_with(block)?;
+        Ok(SignalFd::from_inner(self.io.into_inner()?))
+    }
+
+    /// Replaces the descriptor with a new one for the same signals, to be
+    /// registered with the default reactor, e.g. once the process was
+    /// restored from a checkpoint.
+    ///
+    /// Filters and the mask change are kept. Signals read but not yet
+    /// returned are dropped.
+    pub fn reopen(self) -> Result<Self> {
+        self.after_fork(false)
+    }
+
+    /// Returns `true` if a restore from a checkpoint was notified since the
+    /// descriptor was opened, see [`restore`](crate::restore).
+    pub fn needs_reopen(&self) -> bool {
+        self.io.get_ref().needs_reopen()
+    }
+
+    /// Returns the calling thread's signal mask.
+    ///
+    /// Any thread that doesn't block the [`signals`](Self::signals) can have
+    /// them delivered to their disposition instead of the descriptor, as can
+    /// threads spawned before the signalfd was created. See
+    /// [`SigSet::blocked`].
+    pub fn blocked_mask() -> Result<SigSet> {
+        SigSet::blocked()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.io.get_mut().add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.io.get_mut().remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.io.get_mut().pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.io.get_mut().resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.io.get_ref().is_paused()
+    }
+
+    /// Returns the next signal if one is pending, without waiting.
+    ///
+    /// Useful to check for signals once per iteration of a loop driven by
+    /// something other than the reactor.
+    pub fn try_recv(&mut self) -> Result<Option<SignalInfo>> {
+        self.io.get_mut().read_one()
+    }
+
+    /// Returns every signal that can be received without waiting, e.g. to
+    /// deal with the ones that arrived between creating the signalfd and
+    /// starting to poll it.
+    pub fn drain_pending(&mut self) -> Result<Vec<SignalInfo>> {
+        self.io.get_mut().drain()
+    }
+
+    /// Polls for the next signal.
+    pub fn poll_recv(&mut self) -> Poll<SignalInfo, io::Error> {
+        self.io.get_mut().take_error()?;
+        if let Some(info) = self.io.get_mut().pop() {
+            return Ok(Async::Ready(info));
+        }
+
+        loop {
+            try_ready!(self.io.poll_read_ready(Ready::readable()));
+
+            match self.io.get_mut().read_info() {
+                Ok(info) => return Ok(Async::Ready(info)),
+                // The descriptor is drained: clear readiness and poll it
+                // again, which either registers the task for the next event
+                // or picks up one that raced with the read.
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    self.io.clear_read_ready(Ready::readable())?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Polls for the next signals, appending up to `limit` of them to `out`.
+    ///
+    /// Once at least one signal is available, every signal that can be
+    /// received without waiting is taken, so a burst of signals is drained in
+    /// as few system calls as possible. Returns the number of signals
+    /// appended, which is only zero when `limit` is zero.
+    pub fn poll_recv_many(
+        &mut self,
+        out: &mut Vec<SignalInfo>,
+        limit: usize,
+    ) -> Poll<usize, io::Error> {
+        let mut count = 0;
+        while count < limit {
+            match self.poll_recv() {
+                Ok(Async::Ready(info)) => {
+                    out.push(info);
+                    count += 1;
+                }
+                Err(err) if count == 0 => return Err(err),
+                Err(err) => {
+                    // Reported by the next call, after these signals.
+                    self.io.get_mut().defer_error(err);
+                    break;
+                }
+                Ok(Async::NotReady) if count == 0 => return Ok(Async::NotReady),
+                Ok(Async::NotReady) => break,
+            }
+        }
+        Ok(Async::Ready(count))
+    }
+
+    /// Converts this stream into one yielding typed [`Signal`]s.
+    pub fn signal_stream(self) -> SignalFd<Signal> {
+        SignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one yielding full [`SignalInfo`] records.
+    pub fn info_stream(self) -> SignalFd<SignalInfo> {
+        SignalFd {
+            io: self.io,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one ending after the first signal from
+    /// `terminal`.
+    pub fn take_until_terminal(self, terminal: &SigSet) -> TakeUntilTerminal<Self> {
+        TakeUntilTerminal::new(self, terminal)
+    }
+}
+
+/// Returns a future resolving once the first of `signals` arrives.
+///
+/// The signals are blocked on the calling thread right away.
+pub fn any_of<S: Copy + Into<i32>>(signals: &[S]) -> impl Future<Item = Signal, Error = io::Error> {
+    future::result(SignalFd::new(signals)).and_then(|signals| {
+        signals
+            .signal_stream()
+            .into_future()
+            .map(|(signal, _)| signal.expect("signalfd streams never end"))
+            .map_err(|(err, _)| err)
+    })
+}
+
+/// Returns a future resolving once `SIGINT` arrives, as sent by Ctrl-C in a
+/// terminal.
+pub fn ctrl_c() -> impl Future<Item = (), Error = io::Error> {
+    any_of(&[Signal::Int]).map(|_| ())
+}
+
+/// Returns a future resolving once `SIGTERM`, the conventional request to
+/// shut down, arrives.
+pub fn terminate() -> impl Future<Item = (), Error = io::Error> {
+    any_of(&[Signal::Term]).map(|_| ())
+}
+
+impl<T> AsRawFd for SignalFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().as_raw_fd()
+    }
+}
+
+impl<T> AsFd for SignalFd<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.io.get_ref().as_fd()
+    }
+}
+
+/// The signal mask is left as is, see `into_raw_fd_with`.
+///
+/// # Panics
+///
+/// Panics if the descriptor can't be deregistered from the reactor, e.g.
+/// once the reactor is gone, as the descriptor is closed then. Use
+/// `into_raw_fd_with` to handle that case.
+impl<T> IntoRawFd for SignalFd<T> {
+    fn into_raw_fd(self) -> RawFd {
+        match self.into_raw_fd_with(false) {
+            Ok(fd) => fd,
+            Err(err) => panic!("deregistering the signalfd failed: {}", err),
+        }
+    }
+}
+
+impl<T> FromRawFd for SignalFd<T> {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        SignalFd::from(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+/// Adopts an existing signalfd whose signal set is unknown.
+impl<T> From<OwnedFd> for SignalFd<T> {
+    fn from(fd: OwnedFd) -> Self {
+        SignalFd::from_inner(RawSignalFd::from_fd(fd))
+    }
+}
+
+/// Fails if the descriptor can't be deregistered from the reactor.
+///
+/// The signal mask is left as is, so that the signals keep arriving through
+/// the descriptor.
+impl<T> TryFrom<SignalFd<T>> for OwnedFd {
+    type Error = io::Error;
+
+    fn try_from(signals: SignalFd<T>) -> Result<OwnedFd> {
+        Ok(signals.io.into_inner()?.into_fd(false))
+    }
+}
+
+impl<T> io::Read for SignalFd<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<T> AsyncRead for SignalFd<T> {
+    fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        self.io.poll_read(buf)
+    }
+}
+
+impl<T: From<SignalInfo>> Stream for SignalFd<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let info = try_ready!(self.poll_recv());
+        Ok(Async::Ready(Some(info.into())))
+    }
+}
+
+impl<T: From<SignalInfo>> Stream for TakeUntilTerminal<SignalFd<T>> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+        let info = try_ready!(self.stream.poll_recv());
+        self.observe(info.signo());
+        Ok(Async::Ready(Some(info.into())))
+    }
+}
+
+/// futures 0.3 `Stream` implementation.
+///
+/// The underlying registration is still driven by the tokio 0.1 reactor, so the
+/// 0.1 task machinery is bridged to the caller's `Waker` on every poll.
+#[cfg(feature = "futures-03")]
+impl<T: From<SignalInfo>> futures_core::Stream for SignalFd<T> {
+    type Item = Result<T>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use futures::executor::{self, NotifyHandle};
+        use std::task::Poll;
+
+        let notify = NotifyHandle::from(std::sync::Arc::new(WakerNotify(cx.waker().clone())));
+        match executor::spawn(self.get_mut()).poll_stream_notify(&notify, 0) {
+            Ok(Async::Ready(signal)) => Poll::Ready(signal.map(Ok)),
+            Ok(Async::NotReady) => Poll::Pending,
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+#[cfg(feature = "futures-03")]
+struct WakerNotify(std::task::Waker);
+
+#[cfg(feature = "futures-03")]
+impl futures::executor::Notify for WakerNotify {
+    fn notify(&self, _id: usize) {
+        self.0.wake_by_ref();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_works() {
+        use super::*;
+        use crate::{SIGINT, SIGTERM};
+
+        let terminal = SigSet::from_signals(&[SIGINT]).unwrap();
+        let signals = SignalFd::new(&[SIGINT, SIGTERM])
+            .unwrap()
+            .take_until_terminal(&terminal);
+        let fut = future::lazy(move || {
+            unsafe {
+                libc::raise(SIGINT);
+            }
+            signals.map_err(|err| panic!("{}", err)).for_each(|signal| {
+                assert_eq!(signal, SIGINT);
+                Ok(())
+            })
+        });
+        tokio::run(fut);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn signals_under_load() {
+        use super::*;
+        use tokio::runtime::current_thread::Runtime;
+
+        const COUNT: i32 = 1000;
+        let signal = Signal::rt(2).unwrap();
+        let signals = SignalFd::new(&[signal]).unwrap().info_stream();
+        let thread = unsafe { libc::pthread_self() };
+        let sender = std::thread::spawn(move || {
+            for value in 0..COUNT {
+                let value = libc::sigval {
+                    sival_ptr: value as usize as *mut libc::c_void,
+                };
+                while unsafe { libc::pthread_sigqueue(thread, signal.as_raw(), value) } != 0 {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut rt = Runtime::new().unwrap();
+        let received = rt.block_on(signals.take(COUNT as u64).collect()).unwrap();
+        sender.join().unwrap();
+        let values: Vec<_> = received.iter().map(SignalInfo::int).collect();
+        assert_eq!(values, (0..COUNT).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "futures-03")]
+    #[test]
+    fn futures_03_stream() {
+        use super::*;
+        use futures_util::compat::Compat;
+        use futures_util::{FutureExt, StreamExt};
+        use tokio::runtime::current_thread::Runtime;
+
+        let mut signals = SignalFd::new(&[libc::SIGUSR1]).unwrap();
+        let fut = async move {
+            unsafe {
+                libc::raise(libc::SIGUSR1);
+            }
+            signals.next().await.unwrap()
+        };
+        let mut rt = Runtime::new().unwrap();
+        let signal = rt.block_on(Compat::new(fut.boxed())).unwrap();
+        assert_eq!(signal, libc::SIGUSR1);
+    }
+}
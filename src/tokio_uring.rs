@@ -0,0 +1,251 @@
+//! signalfd integration for tokio-uring runtimes.
+//!
+//! Signals are read with io_uring operations submitted through tokio-uring, so
+//! no readiness reactor is involved. [`SignalFd::new`] can be called anywhere,
+//! but [`recv`](SignalFd::recv) has to run within `tokio_uring::start`.
+
+use std::convert::TryFrom;
+use std::io::{self, Result};
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use tokio_uring_rt::fs::File;
+
+use crate::builder::Builder;
+use crate::raw::{RawSignalFd, BATCH};
+use crate::siginfo::{SignalInfo, SIGINFO_SIZE};
+use crate::signal::Signal;
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// A signalfd read through tokio-uring.
+///
+/// Signals are received with [`recv`](SignalFd::recv), as signal numbers by
+/// default; use [`signal_stream`](SignalFd::signal_stream) or
+/// [`info_stream`](SignalFd::info_stream) to receive typed [`Signal`]s or full
+/// [`SignalInfo`] records instead. Each read collects up to 16 signals.
+pub struct SignalFd<T = i32> {
+    inner: RawSignalFd,
+    file: File,
+    /// Buffer for the next read, `None` while one is in flight or after one
+    /// was cancelled.
+    buf: Option<Vec<u8>>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl SignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    ///
+    /// Signals can be given either as raw numbers or as [`Signal`]s.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::new(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::from_set(set)?)
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(SignalFd::from_inner)
+    }
+}
+
+impl<T> SignalFd<T> {
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        // Reads go through a duplicate owned by tokio-uring, which shares the
+        // signal set and must block until there is a signal.
+        let fd = unsafe { libc::fcntl(inner.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = unsafe { std::fs::File::from_raw_fd(fd) };
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SignalFd {
+            inner,
+            file: File::from_std(file),
+            buf: None,
+            _item: PhantomData,
+        })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Gives up the descriptor without closing it, e.g. to hand it to another
+    /// component.
+    ///
+    /// With `restore_mask` unset the signals stay blocked, so that they keep
+    /// arriving through the descriptor; this is what `IntoRawFd` does.
+    /// Otherwise the mask change is undone as it would be on drop, see
+    /// [`Builder::restore_mask`](crate::Builder::restore_mask). Signals read
+    /// from the descriptor but not yet returned are lost.
+    ///
+    /// The descriptor is close-on-exec; clear `FD_CLOEXEC` to pass it to a
+    /// program started with `exec`, which inherits the mask as well.
+    pub fn into_raw_fd_with(self, restore_mask: bool) -> RawFd {
+        self.inner.into_fd(restore_mask).into_raw_fd()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.inner.pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.inner.resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    /// Converts this stream into one yielding typed [`Signal`]s.
+    pub fn signal_stream(self) -> SignalFd<Signal> {
+        SignalFd {
+            inner: self.inner,
+            file: self.file,
+            buf: self.buf,
+            _item: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one yielding full [`SignalInfo`] records.
+    pub fn info_stream(self) -> SignalFd<SignalInfo> {
+        SignalFd {
+            inner: self.inner,
+            file: self.file,
+            buf: self.buf,
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<T: From<SignalInfo>> SignalFd<T> {
+    /// Waits for the next signal.
+    ///
+    /// Cancelling the returned future is safe: the read in flight keeps its
+    /// buffer until it completes, and the signals it returns are lost.
+    pub async fn recv(&mut self) -> Result<T> {
+        loop {
+            if let Some(info) = self.inner.pop() {
+                return Ok(T::from(info));
+            }
+            let mut buf = self
+                .buf
+                .take()
+                .unwrap_or_else(|| Vec::with_capacity(SIGINFO_SIZE * BATCH));
+            buf.clear();
+            let (result, buf) = self.file.read_at(buf, 0).await;
+            let pushed = result.and_then(|count| self.inner.push_records(&buf[..count]));
+            self.buf = Some(buf);
+            match pushed {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                result => result?,
+            }
+        }
+    }
+}
+
+impl<T> AsRawFd for SignalFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl<T> AsFd for SignalFd<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// The signal mask is left as is, see `into_raw_fd_with`.
+impl<T> IntoRawFd for SignalFd<T> {
+    fn into_raw_fd(self) -> RawFd {
+        self.into_raw_fd_with(false)
+    }
+}
+
+/// Adopts an existing signalfd whose signal set is unknown.
+///
+/// Fails if the descriptor can't be duplicated for tokio-uring.
+impl<T> TryFrom<OwnedFd> for SignalFd<T> {
+    type Error = io::Error;
+
+    fn try_from(fd: OwnedFd) -> Result<Self> {
+        SignalFd::from_inner(RawSignalFd::from_fd(fd))
+    }
+}
+
+/// The signal mask is left as is, so that the signals keep arriving through
+/// the descriptor.
+impl<T> From<SignalFd<T>> for OwnedFd {
+    fn from(signals: SignalFd<T>) -> OwnedFd {
+        signals.inner.into_fd(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recv() {
+        tokio_uring_rt::start(async {
+            let mut signals = SignalFd::new(&[Signal::Usr1, Signal::Usr2])
+                .unwrap()
+                .signal_stream();
+            unsafe { libc::raise(libc::SIGUSR2) };
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(signals.recv().await.unwrap(), Signal::Usr1);
+            assert_eq!(signals.recv().await.unwrap(), Signal::Usr2);
+        });
+    }
+}
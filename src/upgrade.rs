@@ -0,0 +1,278 @@
+//! Restarting into a new binary without closing the listening sockets.
+
+use std::ffi::OsString;
+use std::io::{self, Result};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::{Child, Command};
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+
+use crate::siginfo::SignalInfo;
+use crate::signal::Signal;
+
+/// Environment variable listing the descriptors handed to the new process.
+const FDS_VAR: &str = "SIGNALFD_UPGRADE_FDS";
+/// Environment variable holding the PID of the process being replaced.
+const PID_VAR: &str = "SIGNALFD_UPGRADE_PID";
+
+/// What an [`Upgrader`] reports.
+#[derive(Debug)]
+pub enum UpgradeEvent {
+    /// `SIGUSR2` started the new binary, with this PID.
+    Spawned(u32),
+    /// The new process called [`notify_ready`] and serves on the inherited
+    /// sockets: stop accepting, finish the requests in flight, then exit.
+    TakenOver,
+    /// `SIGQUIT` asked for a graceful shutdown.
+    Quit,
+    /// Any other signal from the wrapped stream.
+    Signal(SignalInfo),
+}
+
+/// Zero-downtime upgrades in the style of nginx.
+///
+/// The stream wraps a signal stream watching `SIGUSR2`, `SIGWINCH` and
+/// `SIGQUIT`, and implements the choreography between the old and the new
+/// process:
+///
+/// 1. `SIGUSR2` makes the old process start its binary again, with the
+///    same arguments and the registered listening sockets left open. Their
+///    numbers are passed in the environment, see [`inherited_fds`].
+/// 2. Once the new process is ready to serve, it calls [`notify_ready`],
+///    which sends `SIGWINCH` to the old one. The stream yields
+///    [`UpgradeEvent::TakenOver`] for it.
+/// 3. The old process stops accepting, drains its connections and exits.
+///
+/// If the new process fails to start up, the old one keeps serving, and a
+/// later `SIGUSR2` can try again once [`child`](Upgrader::child) has
+/// exited. The new process starts out with no signals blocked.
+pub struct Upgrader<S> {
+    signals: S,
+    fds: Vec<RawFd>,
+    program: Option<PathBuf>,
+    args: Option<Vec<OsString>>,
+    child: Option<Child>,
+}
+
+impl<S> Upgrader<S> {
+    /// Creates an upgrader driven by `signals`.
+    pub fn new(signals: S) -> Self {
+        Upgrader {
+            signals,
+            fds: Vec::new(),
+            program: None,
+            args: None,
+            child: None,
+        }
+    }
+
+    /// Hands `listener` over to the new process.
+    ///
+    /// The descriptor stays open in the new process under the same number.
+    pub fn listener<L: AsRawFd>(mut self, listener: &L) -> Self {
+        self.fds.push(listener.as_raw_fd());
+        self
+    }
+
+    /// Starts `program` instead of the current executable.
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Passes `args` instead of the current process's arguments.
+    pub fn args<I, A>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        self.args = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the new process, once one has been started.
+    pub fn child(&mut self) -> Option<&mut Child> {
+        self.child.as_mut()
+    }
+
+    /// Returns the underlying signal stream.
+    pub fn into_inner(self) -> S {
+        self.signals
+    }
+
+    /// Starts the new binary right away, as `SIGUSR2` does, returning its
+    /// PID.
+    ///
+    /// Fails with `AlreadyExists` while a previously started one is still
+    /// running.
+    pub fn upgrade(&mut self) -> Result<u32> {
+        if let Some(child) = &mut self.child {
+            if child.try_wait()?.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "an upgrade is already in progress",
+                ));
+            }
+        }
+        let program = match &self.program {
+            Some(program) => program.clone(),
+            None => std::env::current_exe()?,
+        };
+        let args = match &self.args {
+            Some(args) => args.clone(),
+            None => std::env::args_os().skip(1).collect(),
+        };
+        let fds: Vec<String> = self.fds.iter().map(RawFd::to_string).collect();
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env(FDS_VAR, fds.join(","))
+            .env(PID_VAR, std::process::id().to_string());
+        let inherited = self.fds.clone();
+        unsafe {
+            command.pre_exec(move || {
+                for &fd in &inherited {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                let mut empty: libc::sigset_t = std::mem::zeroed();
+                libc::sigemptyset(&mut empty);
+                libc::pthread_sigmask(libc::SIG_SETMASK, &empty, std::ptr::null_mut());
+                Ok(())
+            });
+        }
+        let child = command.spawn()?;
+        let pid = child.id();
+        self.child = Some(child);
+        Ok(pid)
+    }
+
+    /// Tells whether `info` is the new process reporting that it has taken
+    /// over.
+    fn is_takeover(&mut self, info: &SignalInfo) -> bool {
+        let child = match &mut self.child {
+            Some(child) if info.signo() == libc::SIGWINCH => child,
+            _ => return false,
+        };
+        if info.pid() != 0 {
+            return info.pid() == child.id();
+        }
+        // Backends other than signalfd and the sigwait thread don't report
+        // the sender, so settle for a new process still running: without
+        // one, this is most likely the terminal being resized.
+        matches!(child.try_wait(), Ok(None))
+    }
+}
+
+impl<S> Stream for Upgrader<S>
+where
+    S: Stream<Item = Result<SignalInfo>> + Unpin,
+{
+    type Item = Result<UpgradeEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let info = match ready!(Pin::new(&mut this.signals).poll_next(cx)) {
+            Some(Ok(info)) => info,
+            Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            None => return Poll::Ready(None),
+        };
+        let event = match Signal::from(info) {
+            Signal::Usr2 => this.upgrade().map(UpgradeEvent::Spawned),
+            Signal::Quit => Ok(UpgradeEvent::Quit),
+            _ if this.is_takeover(&info) => Ok(UpgradeEvent::TakenOver),
+            _ => Ok(UpgradeEvent::Signal(info)),
+        };
+        Poll::Ready(Some(event))
+    }
+}
+
+/// Returns the listening sockets handed over by the process this one
+/// replaces, or an empty list if it wasn't started by an [`Upgrader`].
+///
+/// The descriptors are open and owned by the caller, who would typically
+/// turn them into listeners with `FromRawFd`.
+pub fn inherited_fds() -> Vec<RawFd> {
+    match std::env::var(FDS_VAR) {
+        Ok(fds) => parse_fds(&fds),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn parse_fds(fds: &str) -> Vec<RawFd> {
+    fds.split(',').filter_map(|fd| fd.parse().ok()).collect()
+}
+
+/// Tells the process this one replaces that it has taken over, so that it
+/// drains and exits.
+///
+/// Does nothing if this process wasn't started by an [`Upgrader`].
+pub fn notify_ready() -> Result<()> {
+    let pid = match std::env::var(PID_VAR).ok().and_then(|pid| pid.parse().ok()) {
+        Some(pid) => pid,
+        None => return Ok(()),
+    };
+    crate::signal::send(pid, Signal::Winch)
+}
+
+#[cfg(all(test, feature = "tokio-1"))]
+mod tests {
+    use super::*;
+    use crate::tokio::SignalFd;
+    use futures_util::StreamExt;
+    use std::net::TcpListener;
+    use tokio1::runtime::Builder;
+
+    #[test]
+    fn spawns_with_listeners() {
+        let _children = crate::CHILDREN
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let mut child = rt.block_on(async {
+            let signals = SignalFd::new(&[Signal::Usr2, Signal::Winch, Signal::Quit])
+                .unwrap()
+                .info_stream();
+            // The new process checks that it got the socket and our PID.
+            let script = r#"test -e "/dev/fd/$SIGNALFD_UPGRADE_FDS" && test "$SIGNALFD_UPGRADE_PID" = "$PPID""#;
+            let mut upgrader = Upgrader::new(signals)
+                .listener(&listener)
+                .program("sh")
+                .args(["-c", script]);
+
+            unsafe { libc::raise(libc::SIGUSR2) };
+            let pid = match upgrader.next().await.unwrap().unwrap() {
+                UpgradeEvent::Spawned(pid) => pid,
+                event => panic!("unexpected {:?}", event),
+            };
+            assert_eq!(upgrader.child().unwrap().id(), pid);
+            unsafe { libc::raise(libc::SIGWINCH) };
+            let event = upgrader.next().await.unwrap().unwrap();
+            assert!(matches!(event, UpgradeEvent::Signal(_)));
+            upgrader.child.take().unwrap()
+        });
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn winch_without_upgrade() {
+        let source = crate::MockSignalSource::new();
+        source.sender().send(Signal::Winch);
+        let mut upgrader = Upgrader::new(source);
+        let rt = Builder::new_current_thread().build().unwrap();
+        let event = rt.block_on(upgrader.next()).unwrap().unwrap();
+        assert!(matches!(event, UpgradeEvent::Signal(info) if info.pid() == 0));
+    }
+
+    #[test]
+    fn parses_fds() {
+        assert_eq!(parse_fds("3,4"), [3, 4]);
+        assert!(parse_fds("").is_empty());
+    }
+}
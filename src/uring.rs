@@ -0,0 +1,243 @@
+//! Reading signals through io_uring.
+
+use std::io::{self, Result};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+use crate::builder::Builder;
+use crate::raw::{RawSignalFd, BATCH};
+use crate::siginfo::{SignalInfo, SIGINFO_SIZE};
+use crate::sigset::SigSet;
+use crate::sys::Backend;
+
+/// `user_data` of the reads submitted by [`UringSignalFd::recv`].
+const RECV: u64 = 0;
+
+/// A signalfd read with `IORING_OP_READ` instead of through a readiness
+/// reactor.
+///
+/// Programs driving their own ring take a read with
+/// [`read_entry`](UringSignalFd::read_entry), submit it alongside their other
+/// operations and hand its result to [`complete`](UringSignalFd::complete),
+/// after which the signals are available from [`pop`](UringSignalFd::pop).
+/// Each read collects up to 16 signals. Alternatively,
+/// [`recv`](UringSignalFd::recv) waits for a signal on a small ring of its own.
+///
+/// The descriptor is blocking, so that a read only completes once there is a
+/// signal. As with readiness-based reactors, signals directed at a particular
+/// thread are only seen by reads submitted from that thread.
+pub struct UringSignalFd {
+    inner: RawSignalFd,
+    buf: Box<[u8]>,
+    in_flight: bool,
+    ring: Option<IoUring>,
+}
+
+impl UringSignalFd {
+    /// Blocks `signals` on the calling thread and starts receiving them.
+    pub fn new<S: Copy + Into<i32>>(signals: &[S]) -> Result<Self> {
+        UringSignalFd::from_inner(RawSignalFd::new(signals)?)
+    }
+
+    /// Blocks every signal in `set` on the calling thread and starts receiving
+    /// them.
+    pub fn from_set(set: &SigSet) -> Result<Self> {
+        UringSignalFd::from_inner(RawSignalFd::from_set(set)?)
+    }
+
+    /// Returns a builder for configuring how the signalfd is created.
+    pub fn builder() -> Builder<Self> {
+        Builder::new(UringSignalFd::from_inner)
+    }
+
+    fn from_inner(inner: RawSignalFd) -> Result<Self> {
+        let fd = inner.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(UringSignalFd {
+            inner,
+            buf: vec![0; SIGINFO_SIZE * BATCH].into_boxed_slice(),
+            in_flight: false,
+            ring: None,
+        })
+    }
+
+    /// Returns the set of signals received through this descriptor.
+    pub fn signals(&self) -> &SigSet {
+        self.inner.signals()
+    }
+
+    /// Returns the mechanism the signals are received through.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Returns the calling thread's signal mask from before it was changed to
+    /// block the signals, or `None` if it was left untouched.
+    ///
+    /// See [`MaskStrategy`](crate::MaskStrategy).
+    pub fn previous_mask(&self) -> Option<&SigSet> {
+        self.inner.previous_mask()
+    }
+
+    /// Starts receiving `signal` without recreating the descriptor.
+    ///
+    /// The signal is blocked on the calling thread first, so that it is
+    /// routed to the descriptor instead of its handler.
+    pub fn add<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.add(signal.into())
+    }
+
+    /// Stops receiving `signal` and unblocks it on the calling thread.
+    ///
+    /// Instances of `signal` still pending once it is unblocked are delivered
+    /// to its disposition (by default, most signals terminate the process).
+    pub fn remove<S: Into<i32>>(&mut self, signal: S) -> Result<()> {
+        self.inner.remove(signal.into())
+    }
+
+    /// Temporarily stops receiving the signals, keeping the descriptor.
+    ///
+    /// Signals arriving while paused stay pending, to be received after
+    /// [`resume`](Self::resume), except with kqueue, which only notices signals
+    /// while listening for them. With `unblock` set they are unblocked on the
+    /// calling thread as well and reach their disposition instead, e.g. while
+    /// an interactive child handles Ctrl-C itself; by default most signals
+    /// terminate the process, so ignore them meanwhile if need be. Signals
+    /// already read from the descriptor are still returned.
+    pub fn pause(&mut self, unblock: bool) -> Result<()> {
+        self.inner.pause(unblock)
+    }
+
+    /// Starts receiving the signals again after [`pause`](Self::pause),
+    /// blocking them again if they were unblocked.
+    pub fn resume(&mut self) -> Result<()> {
+        self.inner.resume()
+    }
+
+    /// Returns `true` while paused.
+    pub fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    /// Returns a read of the descriptor to submit to a ring, tagged with
+    /// `user_data`, or `None` if the previous read hasn't completed yet.
+    ///
+    /// The read fills a buffer owned by `self`. If `self` is dropped before
+    /// the read completes, the buffer is leaked rather than freed.
+    pub fn read_entry(&mut self, user_data: u64) -> Option<squeue::Entry> {
+        if self.in_flight {
+            return None;
+        }
+        self.in_flight = true;
+        let entry = opcode::Read::new(
+            types::Fd(self.inner.as_raw_fd()),
+            self.buf.as_mut_ptr(),
+            self.buf.len() as u32,
+        )
+        .build()
+        .user_data(user_data);
+        Some(entry)
+    }
+
+    /// Takes the result of the completed read, queueing the signals it
+    /// returned.
+    ///
+    /// Call this with the completion's result even when the read failed, so
+    /// that a new one can be taken.
+    pub fn complete(&mut self, result: i32) -> Result<()> {
+        self.in_flight = false;
+        if result < 0 {
+            return Err(io::Error::from_raw_os_error(-result));
+        }
+        let count = result as usize;
+        self.inner.push_records(&self.buf[..count])
+    }
+
+    /// Returns a signal returned by a completed read, if any.
+    pub fn pop(&mut self) -> Option<SignalInfo> {
+        self.inner.pop()
+    }
+
+    /// Waits for the next signal, on a ring owned by `self`.
+    pub fn recv(&mut self) -> Result<SignalInfo> {
+        loop {
+            if let Some(info) = self.pop() {
+                return Ok(info);
+            }
+            if self.ring.is_none() {
+                self.ring = Some(IoUring::new(2)?);
+            }
+            let entry = self.read_entry(RECV).ok_or_else(|| {
+                io::Error::other("a read submitted to another ring is still in flight")
+            })?;
+            let ring = self.ring.as_mut().expect("ring was created");
+            unsafe { ring.submission().push(&entry) }
+                .expect("submission queue of a fresh ring has room");
+            let result = loop {
+                match ring.submit_and_wait(1) {
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                    Ok(_) => {}
+                }
+                if let Some(completion) = ring.completion().next() {
+                    break completion.result();
+                }
+            };
+            match self.complete(result) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                result => result?,
+            }
+        }
+    }
+}
+
+impl AsRawFd for UringSignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UringSignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl Drop for UringSignalFd {
+    fn drop(&mut self) {
+        if self.in_flight {
+            // The kernel may still write to the buffer.
+            std::mem::forget(std::mem::take(&mut self.buf));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Signal;
+
+    #[test]
+    fn recv_and_complete() {
+        let mut signals = UringSignalFd::new(&[Signal::Usr1, Signal::Usr2]).unwrap();
+        unsafe { libc::raise(libc::SIGUSR1) };
+        unsafe { libc::raise(libc::SIGUSR2) };
+        assert_eq!(signals.recv().unwrap().signo(), libc::SIGUSR1);
+        assert_eq!(signals.pop().unwrap().signo(), libc::SIGUSR2);
+
+        let mut ring = IoUring::new(4).unwrap();
+        let entry = signals.read_entry(7).unwrap();
+        assert!(signals.read_entry(8).is_none());
+        unsafe { libc::raise(libc::SIGUSR2) };
+        unsafe { ring.submission().push(&entry).unwrap() };
+        ring.submit_and_wait(1).unwrap();
+        let completion = ring.completion().next().unwrap();
+        assert_eq!(completion.user_data(), 7);
+        signals.complete(completion.result()).unwrap();
+        assert_eq!(signals.pop().unwrap().signo(), libc::SIGUSR2);
+    }
+}